@@ -0,0 +1,2 @@
+DROP INDEX idx_zettel_user_path;
+CREATE UNIQUE INDEX idx_zettel_user_path ON zettel(user_id, path);
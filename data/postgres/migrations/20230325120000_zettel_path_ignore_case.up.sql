@@ -0,0 +1,5 @@
+-- sqlite stores `zettel.path` as COLLATE NOCASE, so paths are looked up, ordered and made unique case-insensitively.
+-- We mirror this by making the unique index on `LOWER(path)`, and comparing against `LOWER(path)` in all queries.
+-- Note that this migration will fail if a user has multiple zettels that only differ in casing.
+DROP INDEX idx_zettel_user_path;
+CREATE UNIQUE INDEX idx_zettel_user_path ON zettel(user_id, LOWER(path));
@@ -0,0 +1,3 @@
+DROP INDEX idx_zettel_user_folgezettel;
+
+ALTER TABLE zettel DROP COLUMN folgezettel;
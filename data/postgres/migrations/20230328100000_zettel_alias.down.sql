@@ -0,0 +1 @@
+DROP TABLE zettel_alias;
@@ -0,0 +1,10 @@
+-- Other paths under which a zettel can be found. An alias is unique per user, and may not be the path of any zettel.
+-- Like paths, aliases are compared case-insensitively.
+CREATE TABLE zettel_alias (
+    zettel_id BIGINT NOT NULL REFERENCES zettel(zettel_id),
+    user_id BIGINT NOT NULL REFERENCES users(user_id),
+    alias TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_zettel_alias_user_alias ON zettel_alias(user_id, LOWER(alias));
+CREATE INDEX idx_zettel_alias_zettel ON zettel_alias(zettel_id);
@@ -0,0 +1,11 @@
+-- The front matter properties of a zettel, updated whenever the zettel is saved. Keys and values are compared
+-- case-insensitively.
+CREATE TABLE zettel_property (
+    zettel_id BIGINT NOT NULL REFERENCES zettel(zettel_id),
+    user_id BIGINT NOT NULL REFERENCES users(user_id),
+    key TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+
+CREATE INDEX idx_zettel_property_zettel ON zettel_property(zettel_id);
+CREATE INDEX idx_zettel_property_user_key_value ON zettel_property(user_id, LOWER(key), LOWER(value));
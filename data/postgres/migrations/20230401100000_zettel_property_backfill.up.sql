@@ -0,0 +1,7 @@
+-- The zettels whose front matter hasn't been read into zettel_property yet, because they were saved before it existed.
+-- Front matter can only be parsed in Rust, so the storage does that on connect and empties this table.
+CREATE TABLE zettel_property_backfill (
+    zettel_id BIGINT NOT NULL REFERENCES zettel(zettel_id)
+);
+
+INSERT INTO zettel_property_backfill (zettel_id) SELECT zettel_id FROM zettel;
@@ -1,13 +1,29 @@
 use snafu::ResultExt;
+use sqlx::{
+    postgres::{PgArguments, PgListener},
+    query::Query,
+    Connection as _, PgConnection, Postgres, Row,
+};
+use std::collections::HashMap;
 use zettelkasten_shared::{
-    async_trait,
-    futures::{future::LocalBoxFuture, FutureExt},
+    async_trait, folgezettel, frontmatter,
+    futures::{
+        future::{self, LocalBoxFuture},
+        stream::BoxStream,
+        FutureExt, StreamExt,
+    },
+    namespace, query, snippet,
     storage::{
-        BcryptSnafu, ConnectableStorage, Error, InvalidRegexSnafu, JsonSnafu, SearchOpts,
-        SqlxSnafu, Storage, SystemConfig, User, UserId, Zettel, ZettelHeader, ZettelId,
+        BatchOperation, BatchResult, BcryptSnafu, ConnectableStorage, Error, InvalidQuerySnafu,
+        JsonSnafu, PathChild, PropertyFilter, SavedSearch, SearchMode, SearchOpts, SqlxSnafu,
+        Storage, StorageEvent, SystemConfig, User, UserConfig, UserId, Zettel, ZettelHeader,
+        ZettelId,
     },
 };
 
+/// The channel that is used for `LISTEN`/`NOTIFY` of [`StorageEvent`]s
+const EVENT_CHANNEL: &str = "zettelkasten_events";
+
 pub struct Connection {
     conn: sqlx::PgPool,
 }
@@ -27,7 +43,7 @@ impl Storage for Connection {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
         sqlx::query_as!(
             User,
-            "SELECT user_id as id, username as name, password, last_visited_zettel FROM users"
+            "SELECT user_id as id, username as name, password, last_visited_zettel, is_admin FROM users"
         )
         .fetch_one(&mut conn)
         .await
@@ -38,7 +54,7 @@ impl Storage for Connection {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
         let user = sqlx::query_as!(
             User,
-            "SELECT user_id as id, username as name, password, last_visited_zettel FROM users WHERE username = $1",
+            "SELECT user_id as id, username as name, password, last_visited_zettel, is_admin FROM users WHERE username = $1",
             username
         )
         .fetch_optional(&mut conn)
@@ -54,6 +70,18 @@ impl Storage for Connection {
         Ok(None)
     }
 
+    async fn get_user(&self, user: UserId) -> Result<Option<User>, Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        sqlx::query_as!(
+            User,
+            "SELECT user_id as id, username as name, password, last_visited_zettel, is_admin FROM users WHERE user_id = $1",
+            user
+        )
+        .fetch_optional(&mut conn)
+        .await
+        .context(SqlxSnafu)
+    }
+
     async fn register(&self, username: &str, password: &str) -> Result<User, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
 
@@ -62,9 +90,9 @@ impl Storage for Connection {
         sqlx::query_as!(
             User,
             r#"
-            INSERT INTO users (username, password)
-            VALUES ($1, $2)
-            RETURNING user_id as id, username as name, password, last_visited_zettel
+            INSERT INTO users (username, password, is_admin)
+            VALUES ($1, $2, NOT EXISTS (SELECT 1 FROM users))
+            RETURNING user_id as id, username as name, password, last_visited_zettel, is_admin
             "#,
             username,
             password
@@ -80,84 +108,102 @@ impl Storage for Connection {
         search: SearchOpts<'_>,
     ) -> Result<Vec<ZettelHeader>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let highlight = search.highlight_regex()?;
+        let has_query = !search.list_all && !search.query.trim().is_empty();
+        if !search.list_all && !has_query && search.properties.is_empty() {
+            return Err(Error::InvalidSearchOpts);
+        }
 
-        if search.list_all {
-            let results = sqlx::query!(
-                "SELECT zettel_id, path FROM zettel WHERE user_id = $1",
-                user
-            )
-            .fetch_all(&mut conn)
-            .await
-            .context(SqlxSnafu)?;
-            Ok(results
-                .into_iter()
-                .map(|r| ZettelHeader {
-                    id: r.zettel_id,
-                    path: r.path,
-                    highlight_text: None,
-                })
-                .collect())
-        } else if !search.query.is_empty() {
-            let regex = regex::Regex::new(search.query).context(InvalidRegexSnafu)?;
-
-            let results = sqlx::query!(
-                "SELECT zettel_id, path, body FROM zettel WHERE user_id = $1 AND (path ~ $2 OR body ~ $2)",
-                user,
-                search.query
-            )
+        let mut sql =
+            "SELECT zettel_id, path, body, folgezettel FROM zettel WHERE user_id = $1".to_owned();
+        let mut params = Vec::new();
+        if has_query {
+            sql.push_str(" AND ");
+            search_condition(&search, &mut sql, &mut params, 2)?;
+        }
+        sql.push_str(&property_conditions(search.properties, 2 + params.len()));
+        sql.push_str(" ORDER BY LOWER(path) ASC");
+
+        let mut statement = sqlx::query(&sql).bind(user);
+        for param in params {
+            statement = match param {
+                query::Param::Text(text) => statement.bind(text),
+                query::Param::Date(date) => statement.bind(date),
+            };
+        }
+        let results = bind_properties(statement, search.properties)
             .fetch_all(&mut conn)
             .await
-            .context(SqlxSnafu)?;
-            Ok(results
-                .into_iter()
-                .map(|row| {
-                    let highlight_text = if let Some(m) = regex.find(&row.body) {
-                        let start = if m.start() < 10 { 0 } else { m.start() - 10 };
-                        let end = if m.end() + 10 >= row.body.len() {
-                            row.body.len()
-                        } else {
-                            m.end() + 10
-                        };
-                        Some(row.body[start..end].to_owned())
-                    } else {
-                        None
-                    };
-                    ZettelHeader {
-                        id: row.zettel_id,
-                        path: row.path,
-                        highlight_text,
-                    }
-                })
-                .collect())
-        } else {
-            Err(Error::InvalidSearchOpts)
-        }
+            .map_err(regex_error)?;
+
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let body: String = row.get(2);
+                let snippets = highlight
+                    .as_ref()
+                    .map(|regex| snippet::build(&body, regex))
+                    .unwrap_or_default();
+                ZettelHeader {
+                    id: row.get(0),
+                    path: row.get(1),
+                    snippets,
+                    folgezettel: row.get(3),
+                }
+            })
+            .collect())
     }
 
-    async fn get_zettel(&self, user: UserId, id: ZettelId) -> Result<Zettel, Error> {
+    async fn get_children(&self, user: UserId, parent: &str) -> Result<Vec<PathChild>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
-
-        let zettel = sqlx::query!(
-            "SELECT zettel_id, path, body FROM zettel WHERE zettel_id = $1 AND user_id = $2",
-            id,
-            user
+        let results = sqlx::query!(
+            r#"SELECT
+                COALESCE(MAX(CASE WHEN is_leaf THEN name END), MIN(name)) AS "name!",
+                MAX(CASE WHEN is_leaf THEN zettel_id END) AS zettel_id,
+                COUNT(*) FILTER (WHERE NOT is_leaf) AS "descendants!"
+            FROM (
+                SELECT zettel_id, split_part(rest, '/', 1) AS name, strpos(rest, '/') = 0 AS is_leaf
+                FROM (
+                    SELECT zettel_id, substr(path, char_length($2) + 1) AS rest
+                    FROM zettel
+                    WHERE user_id = $1 AND LOWER(path) LIKE LOWER($3) ESCAPE '\'
+                ) AS paths
+            ) AS children
+            WHERE name != ''
+            GROUP BY LOWER(name)
+            ORDER BY LOWER(name)"#,
+            user,
+            namespace::join(parent, ""),
+            namespace::like_pattern(parent),
         )
-        .fetch_one(&mut conn)
+        .fetch_all(&mut conn)
         .await
         .context(SqlxSnafu)?;
-        Ok(Zettel {
-            id: zettel.zettel_id,
-            path: zettel.path,
-            body: zettel.body,
-            attachments: Vec::new(),
-        })
+
+        Ok(results
+            .into_iter()
+            .map(|row| PathChild {
+                path: namespace::join(parent, &row.name),
+                zettel: row.zettel_id,
+                descendants: row.descendants.unsigned_abs(),
+            })
+            .collect())
+    }
+
+    async fn get_zettel(&self, user: UserId, id: ZettelId) -> Result<Zettel, Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        get_zettel(&mut conn, user, id).await
     }
 
     async fn get_zettel_by_url(&self, user: UserId, url: &str) -> Result<Option<Zettel>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
 
         if let Some(zettel) = sqlx::query!(
-            "SELECT zettel_id, path, body FROM zettel WHERE user_id = $1 AND path = $2",
+            r#"SELECT zettel_id, path, body, folgezettel FROM zettel
+            WHERE user_id = $1 AND (
+                LOWER(path) = LOWER($2)
+                OR zettel_id IN (SELECT zettel_id FROM zettel_alias WHERE user_id = $1 AND LOWER(alias) = LOWER($2))
+            )"#,
             user,
             url,
         )
@@ -169,6 +215,8 @@ impl Storage for Connection {
                 id: zettel.zettel_id,
                 path: zettel.path,
                 body: zettel.body,
+                folgezettel: zettel.folgezettel,
+                aliases: get_aliases(&mut conn, zettel.zettel_id).await?,
                 attachments: Vec::new(),
             }))
         } else {
@@ -176,38 +224,89 @@ impl Storage for Connection {
         }
     }
 
-    async fn update_zettel(&self, user: UserId, zettel: &mut Zettel) -> Result<(), Error> {
+    async fn get_all_zettels(&self, user: UserId) -> Result<Vec<Zettel>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let zettels = sqlx::query!(
+            "SELECT zettel_id, path, body, folgezettel FROM zettel WHERE user_id = $1 ORDER BY LOWER(path)",
+            user
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+        let mut aliases: HashMap<ZettelId, Vec<String>> = HashMap::new();
+        for row in sqlx::query!(
+            "SELECT zettel_id, alias FROM zettel_alias WHERE user_id = $1 ORDER BY LOWER(alias)",
+            user
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(SqlxSnafu)?
+        {
+            aliases.entry(row.zettel_id).or_default().push(row.alias);
+        }
+        Ok(zettels
+            .into_iter()
+            .map(|zettel| Zettel {
+                id: zettel.zettel_id,
+                aliases: aliases.remove(&zettel.zettel_id).unwrap_or_default(),
+                path: zettel.path,
+                body: zettel.body,
+                folgezettel: zettel.folgezettel,
+                attachments: Vec::new(),
+            })
+            .collect())
+    }
 
-        if zettel.id == 0 {
-            let result = sqlx::query!(
-                r#"
-                INSERT INTO zettel (user_id, path, body)
-                VALUES ($1, $2, $3)
-                RETURNING zettel_id
-                "#,
+    async fn update_zettel(&self, user: UserId, zettel: &mut Zettel) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        update_zettel(&mut tx, user, zettel).await?;
+        notify(
+            &mut tx,
+            &StorageEvent::ZettelChanged {
                 user,
-                zettel.path,
-                zettel.body
-            )
-            .fetch_one(&mut conn)
-            .await
-            .context(SqlxSnafu)?;
-            zettel.id = result.zettel_id;
-            Ok(())
-        } else {
-            sqlx::query!(
-                "UPDATE zettel SET body = $1, PATH = $2 WHERE zettel_id = $3 AND user_id = $4",
-                zettel.body,
-                zettel.path,
-                zettel.id,
-                user
-            )
-            .execute(&mut conn)
-            .await
-            .context(SqlxSnafu)?;
-            Ok(())
+                id: zettel.id,
+            },
+        )
+        .await?;
+        tx.commit().await.context(SqlxSnafu)
+    }
+
+    async fn update_zettel_body(
+        &self,
+        user: UserId,
+        id: ZettelId,
+        base: Option<&str>,
+        body: &str,
+    ) -> Result<Zettel, Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        // the update locks the row, so nobody changes the body between the check and the update
+        let updated = sqlx::query!(
+            r#"UPDATE zettel SET body = $1
+            WHERE zettel_id = $2 AND user_id = $3 AND ($4::TEXT IS NULL OR body = $4)"#,
+            body,
+            id,
+            user,
+            base,
+        )
+        .execute(&mut tx)
+        .await
+        .context(SqlxSnafu)?;
+        let mut zettel = get_zettel(&mut tx, user, id).await?;
+        if updated.rows_affected() == 0 {
+            return Err(Error::EditConflict { id });
         }
+        // the aliases and properties of the new body
+        update_zettel(&mut tx, user, &mut zettel).await?;
+        notify(&mut tx, &StorageEvent::ZettelChanged { user, id }).await?;
+        tx.commit().await.context(SqlxSnafu)?;
+        Ok(zettel)
+    }
+
+    async fn delete_zettel(&self, user: UserId, id: ZettelId) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        delete_zettel(&mut tx, user, id).await?;
+        notify(&mut tx, &StorageEvent::ZettelDeleted { user, id }).await?;
+        tx.commit().await.context(SqlxSnafu)
     }
 
     async fn set_user_last_visited_zettel(
@@ -229,25 +328,624 @@ impl Storage for Connection {
     }
 
     async fn update_config(&self, config: &SystemConfig) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        update_config(&mut tx, config).await?;
+        notify(&mut tx, &StorageEvent::ConfigChanged).await?;
+        tx.commit().await.context(SqlxSnafu)
+    }
+
+    async fn get_user_config(&self, user: UserId) -> Result<UserConfig, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
 
-        let serde_json::Value::Object(values) = serde_json::to_value(config).context(JsonSnafu)? else {
-            panic!("SystemConfig did not serialize to an object");
-        };
+        let result = sqlx::query!(
+            "SELECT key, value FROM user_config WHERE user_id = $1",
+            user
+        )
+        .fetch_all(&mut conn)
+        .await
+        .context(SqlxSnafu)?;
+
+        key_values_to_config(result.into_iter().map(|o| (o.key, o.value)))
+    }
 
-        for (key, value) in values {
-            let str_value = serde_json::to_string(&value).context(JsonSnafu)?;
-            sqlx::query!(
-                "UPDATE config SET value = $1 WHERE key = $2",
-                str_value,
-                key
-            )
-            .execute(&mut conn)
+    async fn update_user_config(&self, user: UserId, config: &UserConfig) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        update_user_config(&mut tx, user, config).await?;
+        notify(&mut tx, &StorageEvent::UserConfigChanged { user }).await?;
+        tx.commit().await.context(SqlxSnafu)
+    }
+
+    async fn get_saved_searches(&self, user: UserId) -> Result<Vec<SavedSearch>, Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let results = sqlx::query!(
+            "SELECT name, query, mode, properties FROM saved_search WHERE user_id = $1 ORDER BY LOWER(name)",
+            user
+        )
+        .fetch_all(&mut conn)
+        .await
+        .context(SqlxSnafu)?;
+        results
+            .into_iter()
+            .map(|row| saved_search_from_row(row.name, row.query, &row.mode, &row.properties))
+            .collect()
+    }
+
+    async fn save_search(&self, user: UserId, search: &SavedSearch) -> Result<(), Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let mode = serde_json::to_string(&search.mode).context(JsonSnafu)?;
+        let properties = serde_json::to_string(&search.properties).context(JsonSnafu)?;
+        sqlx::query!(
+            r#"INSERT INTO saved_search (user_id, name, query, mode, properties) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, LOWER(name)) DO UPDATE
+            SET name = excluded.name, query = excluded.query, mode = excluded.mode, properties = excluded.properties"#,
+            user,
+            search.name,
+            search.query,
+            mode,
+            properties,
+        )
+        .execute(&mut conn)
+        .await
+        .context(SqlxSnafu)?;
+        Ok(())
+    }
+
+    async fn delete_saved_search(&self, user: UserId, name: &str) -> Result<(), Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        sqlx::query!(
+            "DELETE FROM saved_search WHERE user_id = $1 AND LOWER(name) = LOWER($2)",
+            user,
+            name
+        )
+        .execute(&mut conn)
+        .await
+        .context(SqlxSnafu)?;
+        Ok(())
+    }
+
+    async fn batch(
+        &self,
+        user: UserId,
+        operations: &mut [BatchOperation],
+    ) -> Result<Vec<BatchResult>, Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        let mut created = Vec::new();
+        let mut outcome = None;
+        for (idx, operation) in operations.iter_mut().enumerate() {
+            let result = match operation {
+                BatchOperation::UpdateZettel(zettel) => {
+                    if zettel.id == 0 {
+                        created.push(idx);
+                    }
+                    update_zettel(&mut tx, user, zettel).await
+                }
+                BatchOperation::DeleteZettel(id) => delete_zettel(&mut tx, user, *id).await,
+                BatchOperation::UpdateConfig(config) => update_config(&mut tx, config).await,
+                BatchOperation::UpdateUserConfig(config) => {
+                    update_user_config(&mut tx, user, config).await
+                }
+            };
+            if let Err(e) = result {
+                outcome = Some((idx, e));
+                break;
+            }
+        }
+        if outcome.is_none() {
+            for (idx, operation) in operations.iter().enumerate() {
+                if let Err(e) = notify(&mut tx, &operation.event(user)).await {
+                    outcome = Some((idx, e));
+                    break;
+                }
+            }
+        }
+
+        if outcome.is_some() {
+            tx.rollback().await.context(SqlxSnafu)?;
+            // the zettels that were created in this batch do not exist anymore
+            for idx in created {
+                if let BatchOperation::UpdateZettel(zettel) = &mut operations[idx] {
+                    zettel.id = 0;
+                }
+            }
+        } else {
+            tx.commit().await.context(SqlxSnafu)?;
+        }
+        Ok(BatchResult::from_outcome(operations.len(), outcome))
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, StorageEvent>, Error> {
+        let mut listener = PgListener::connect_with(&self.conn)
             .await
             .context(SqlxSnafu)?;
+        listener.listen(EVENT_CHANNEL).await.context(SqlxSnafu)?;
+        Ok(listener
+            .into_stream()
+            .filter_map(|notification| {
+                // skip notifications we can't parse, e.g. when they're sent by a newer version of zettelkasten
+                future::ready(
+                    notification
+                        .ok()
+                        .and_then(|n| serde_json::from_str(n.payload()).ok()),
+                )
+            })
+            .boxed())
+    }
+}
+
+/// Notify all subscribers of `event`. Postgres only delivers this when the transaction of `conn` is committed.
+async fn notify(conn: &mut PgConnection, event: &StorageEvent) -> Result<(), Error> {
+    let payload = serde_json::to_string(event).context(JsonSnafu)?;
+    // `pg_notify` returns `void`, which `sqlx::query!` can not describe
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(EVENT_CHANNEL)
+        .bind(payload)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    Ok(())
+}
+
+async fn get_zettel(conn: &mut PgConnection, user: UserId, id: ZettelId) -> Result<Zettel, Error> {
+    let zettel = sqlx::query!(
+        "SELECT zettel_id, path, body, folgezettel FROM zettel WHERE zettel_id = $1 AND user_id = $2",
+        id,
+        user
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .context(SqlxSnafu)?
+    .ok_or(Error::ZettelNotFound { id })?;
+    Ok(Zettel {
+        id: zettel.zettel_id,
+        path: zettel.path,
+        body: zettel.body,
+        folgezettel: zettel.folgezettel,
+        aliases: get_aliases(conn, zettel.zettel_id).await?,
+        attachments: Vec::new(),
+    })
+}
+
+async fn update_zettel(
+    conn: &mut PgConnection,
+    user: UserId,
+    zettel: &mut Zettel,
+) -> Result<(), Error> {
+    folgezettel::normalize(&mut zettel.folgezettel)?;
+    zettel.normalize_aliases();
+    // paths of other zettels are caught by the unique index, aliases are not
+    let aliased = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM zettel_alias WHERE user_id = $1 AND LOWER(alias) = LOWER($2) AND zettel_id != $3"#,
+        user,
+        zettel.path,
+        zettel.id,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    if aliased.count > 0 {
+        return Err(Error::PathInUse {
+            path: zettel.path.clone(),
+        });
+    }
+    if zettel.id == 0 {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO zettel (user_id, path, body, folgezettel, created_on, last_modified_on)
+            VALUES ($1, $2, $3, $4, NOW(), NOW())
+            RETURNING zettel_id
+            "#,
+            user,
+            zettel.path,
+            zettel.body,
+            zettel.folgezettel
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+        zettel.id = result.zettel_id;
+    } else {
+        let result = sqlx::query!(
+            r#"
+            UPDATE zettel
+            SET body = $1, path = $2, folgezettel = $3, last_modified_on = NOW()
+            WHERE zettel_id = $4 AND user_id = $5
+            "#,
+            zettel.body,
+            zettel.path,
+            zettel.folgezettel,
+            zettel.id,
+            user
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+        if result.rows_affected() == 0 {
+            return Err(Error::ZettelNotFound { id: zettel.id });
         }
-        Ok(())
     }
+
+    sqlx::query!("DELETE FROM zettel_alias WHERE zettel_id = $1", zettel.id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    for alias in &zettel.aliases {
+        if path_in_use(conn, user, alias, zettel.id).await? {
+            return Err(Error::PathInUse {
+                path: alias.clone(),
+            });
+        }
+        sqlx::query!(
+            "INSERT INTO zettel_alias (zettel_id, user_id, alias) VALUES ($1, $2, $3)",
+            zettel.id,
+            user,
+            alias
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    }
+
+    update_properties(conn, user, zettel.id, &zettel.body).await
+}
+
+/// Replace the stored front matter properties of a zettel with those in `body`
+async fn update_properties(
+    conn: &mut PgConnection,
+    user: UserId,
+    id: ZettelId,
+    body: &str,
+) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM zettel_property WHERE zettel_id = $1", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    let properties = frontmatter::parse(body).map(|front_matter| front_matter.properties);
+    for (key, value) in properties.into_iter().flatten() {
+        sqlx::query!(
+            "INSERT INTO zettel_property (zettel_id, user_id, key, value) VALUES ($1, $2, $3, $4)",
+            id,
+            user,
+            key,
+            value
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    }
+    Ok(())
+}
+
+fn saved_search_from_row(
+    name: String,
+    query: String,
+    mode: &str,
+    properties: &str,
+) -> Result<SavedSearch, Error> {
+    Ok(SavedSearch {
+        name,
+        query,
+        mode: serde_json::from_str(mode).context(JsonSnafu)?,
+        properties: serde_json::from_str(properties).context(JsonSnafu)?,
+    })
+}
+
+/// Turn the error of Postgres for a regex that it can't compile into [`Error::InvalidRegex`]. The regex is checked with
+/// the `regex` crate before, but Postgres uses POSIX regexes which don't support all of its syntax, like `\p{L}` or
+/// `(?P<name>...)`.
+fn regex_error(error: sqlx::Error) -> Error {
+    match &error {
+        // `invalid_regular_expression`
+        sqlx::Error::Database(e) if e.code().as_deref() == Some("2201B") => Error::InvalidRegex {
+            source: regex::Error::Syntax(e.message().to_owned()),
+        },
+        _ => Error::Sqlx { source: error },
+    }
+}
+
+/// The condition for [`SearchOpts::query`] in its [`SearchMode`]. Its parameters are numbered from `first_param` on,
+/// and appended to `params` in that order.
+fn search_condition(
+    search: &SearchOpts<'_>,
+    sql: &mut String,
+    params: &mut Vec<query::Param>,
+    first_param: usize,
+) -> Result<(), Error> {
+    let n = first_param + params.len();
+    let (condition, pattern) = match search.mode {
+        SearchMode::Query => {
+            let query = search.query.parse().context(InvalidQuerySnafu)?;
+            query_condition(&query, sql, params, first_param);
+            return Ok(());
+        }
+        SearchMode::Literal => (
+            format!("(strpos(path, ${n}) > 0 OR strpos(body, ${n}) > 0)"),
+            search.query.to_owned(),
+        ),
+        SearchMode::LiteralIgnoreCase => (
+            format!(r"(path ILIKE ${n} ESCAPE '\' OR body ILIKE ${n} ESCAPE '\')"),
+            format!("%{}%", query::escape_like(search.query)),
+        ),
+        SearchMode::Regex => (
+            format!("(path ~ ${n} OR body ~ ${n})"),
+            search.query.to_owned(),
+        ),
+        SearchMode::RegexIgnoreCase => (
+            format!("(path ~* ${n} OR body ~* ${n})"),
+            search.query.to_owned(),
+        ),
+    };
+    sql.push_str(&condition);
+    params.push(query::Param::Text(pattern));
+    Ok(())
+}
+
+/// Compile `query` into a condition on the `zettel` table. Its parameters are numbered from `first_param` on, and
+/// appended to `params` in that order.
+fn query_condition(
+    query: &query::Query,
+    sql: &mut String,
+    params: &mut Vec<query::Param>,
+    first_param: usize,
+) {
+    use query::{Param, Query};
+
+    // the number of the next parameter
+    let next = |params: &Vec<Param>| first_param + params.len();
+    match query {
+        Query::Term(term) => {
+            let n = next(params);
+            sql.push_str(&format!(
+                r"(path ILIKE ${n} ESCAPE '\' OR body ILIKE ${n} ESCAPE '\')"
+            ));
+            params.push(Param::Text(format!("%{}%", query::escape_like(term))));
+        }
+        Query::Path(prefix) => {
+            sql.push_str(&format!(r"path ILIKE ${} ESCAPE '\'", next(params)));
+            params.push(Param::Text(format!("{}%", query::escape_like(prefix))));
+        }
+        Query::Tag(tag) => {
+            let keys = (0..query::TAG_KEYS.len())
+                .map(|idx| format!("${}", next(params) + idx))
+                .collect::<Vec<_>>()
+                .join(", ");
+            params.extend(query::TAG_KEYS.map(|key| Param::Text(key.to_owned())));
+            sql.push_str(&format!(
+                "zettel_id IN (SELECT zettel_id FROM zettel_property WHERE LOWER(key) IN ({keys}) AND LOWER(value) = LOWER(${}))",
+                next(params)
+            ));
+            params.push(Param::Text(tag.clone()));
+        }
+        Query::Before(date) => {
+            sql.push_str(&format!("created_on::date < ${}", next(params)));
+            params.push(Param::Date(*date));
+        }
+        Query::After(date) => {
+            sql.push_str(&format!("created_on::date > ${}", next(params)));
+            params.push(Param::Date(*date));
+        }
+        Query::LinksTo(target) => {
+            sql.push_str(&format!("body ~* ${}", next(params)));
+            params.push(Param::Text(query::links_to_regex(target)));
+        }
+        Query::Regex(regex) => {
+            let n = next(params);
+            sql.push_str(&format!("(path ~ ${n} OR body ~ ${n})"));
+            params.push(Param::Text(regex.clone()));
+        }
+        Query::Not(query) => {
+            sql.push_str("NOT (");
+            query_condition(query, sql, params, first_param);
+            sql.push(')');
+        }
+        Query::And(queries) | Query::Or(queries) => {
+            let operator = if matches!(query, Query::And(_)) {
+                " AND "
+            } else {
+                " OR "
+            };
+            sql.push('(');
+            for (idx, query) in queries.iter().enumerate() {
+                if idx > 0 {
+                    sql.push_str(operator);
+                }
+                query_condition(query, sql, params, first_param);
+            }
+            sql.push(')');
+        }
+    }
+}
+
+/// The conditions for [`SearchOpts::properties`], with parameters starting at `$first_param`. See
+/// [`bind_properties`].
+fn property_conditions(filters: &[PropertyFilter], first_param: usize) -> String {
+    (0..filters.len())
+        .map(|idx| {
+            let key = first_param + 2 * idx;
+            let value = key + 1;
+            format!(
+                " AND zettel_id IN (SELECT zettel_id FROM zettel_property WHERE LOWER(key) = LOWER(${key}) AND LOWER(value) = LOWER(${value}))"
+            )
+        })
+        .collect()
+}
+
+/// Bind the parameters of [`property_conditions`]
+fn bind_properties<'q>(
+    mut query: Query<'q, Postgres, PgArguments>,
+    filters: &'q [PropertyFilter],
+) -> Query<'q, Postgres, PgArguments> {
+    for filter in filters {
+        query = query.bind(&filter.key).bind(&filter.value);
+    }
+    query
+}
+
+/// Whether a zettel other than `except` has `path` as its path or as an alias
+async fn path_in_use(
+    conn: &mut PgConnection,
+    user: UserId,
+    path: &str,
+    except: ZettelId,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM zettel WHERE user_id = $1 AND LOWER(path) = LOWER($2) AND zettel_id != $3
+            UNION ALL
+            SELECT 1 FROM zettel_alias WHERE user_id = $1 AND LOWER(alias) = LOWER($2) AND zettel_id != $3
+        ) AS "in_use!""#,
+        user,
+        path,
+        except,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    Ok(result.in_use)
+}
+
+async fn get_aliases(conn: &mut PgConnection, id: ZettelId) -> Result<Vec<String>, Error> {
+    let result = sqlx::query!(
+        "SELECT alias FROM zettel_alias WHERE zettel_id = $1 ORDER BY LOWER(alias)",
+        id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    Ok(result.into_iter().map(|row| row.alias).collect())
+}
+
+async fn delete_zettel(conn: &mut PgConnection, user: UserId, id: ZettelId) -> Result<(), Error> {
+    // make sure the zettel belongs to this user before we remove everything that references it
+    let exists = sqlx::query!(
+        "SELECT zettel_id FROM zettel WHERE zettel_id = $1 AND user_id = $2",
+        id,
+        user
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    if exists.is_none() {
+        return Err(Error::ZettelNotFound { id });
+    }
+
+    sqlx::query!(
+        "UPDATE users SET last_visited_zettel = NULL WHERE last_visited_zettel = $1",
+        id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    sqlx::query!(
+        "DELETE FROM zettel_link WHERE source_zettel_id = $1 OR destination_zettel_id = $1",
+        id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_alias WHERE zettel_id = $1", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_property WHERE zettel_id = $1", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_attachment WHERE zettel_id = $1", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_history WHERE zettel_id = $1", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel WHERE zettel_id = $1", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    Ok(())
+}
+
+/// Read the front matter properties of the zettels that were saved before `zettel_property` existed
+async fn backfill_properties(conn: &mut PgConnection) -> Result<(), Error> {
+    let mut tx = conn.begin().await.context(SqlxSnafu)?;
+    let zettels = sqlx::query!(
+        r#"SELECT zettel.zettel_id, zettel.user_id, zettel.body
+        FROM zettel_property_backfill
+        JOIN zettel ON zettel.zettel_id = zettel_property_backfill.zettel_id"#
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .context(SqlxSnafu)?;
+    for zettel in zettels {
+        update_properties(&mut tx, zettel.user_id, zettel.zettel_id, &zettel.body).await?;
+    }
+    sqlx::query!("DELETE FROM zettel_property_backfill")
+        .execute(&mut *tx)
+        .await
+        .context(SqlxSnafu)?;
+    tx.commit().await.context(SqlxSnafu)
+}
+
+async fn update_config(conn: &mut PgConnection, config: &SystemConfig) -> Result<(), Error> {
+    let serde_json::Value::Object(values) = serde_json::to_value(config).context(JsonSnafu)? else {
+        panic!("SystemConfig did not serialize to an object");
+    };
+
+    for (key, value) in values {
+        let str_value = serde_json::to_string(&value).context(JsonSnafu)?;
+        sqlx::query!(
+            "UPDATE config SET value = $1 WHERE key = $2",
+            str_value,
+            key
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    }
+    Ok(())
+}
+
+async fn update_user_config(
+    conn: &mut PgConnection,
+    user: UserId,
+    config: &UserConfig,
+) -> Result<(), Error> {
+    let serde_json::Value::Object(values) = serde_json::to_value(config).context(JsonSnafu)? else {
+        panic!("UserConfig did not serialize to an object");
+    };
+
+    for (key, value) in values {
+        let str_value = serde_json::to_string(&value).context(JsonSnafu)?;
+        sqlx::query!(
+            r#"
+            INSERT INTO user_config (user_id, key, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, key) DO UPDATE SET value = EXCLUDED.value
+            "#,
+            user,
+            key,
+            str_value
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    }
+    Ok(())
+}
+
+/// Deserialize a config struct from rows of `key` and json encoded `value`.
+fn key_values_to_config<T>(rows: impl Iterator<Item = (String, String)>) -> Result<T, Error>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    // Map them into a `serde_json::Map`
+    let map: serde_json::Map<String, serde_json::Value> = rows
+        .map(|(key, value)| Ok((key, serde_json::from_str(&value)?)))
+        .collect::<Result<_, _>>()
+        .context(JsonSnafu)?;
+
+    // Now we can deserialize this from a `serde_json::Value::Object(map)`
+    serde_json::from_value(serde_json::Value::Object(map)).context(JsonSnafu)
 }
 
 impl ConnectableStorage for Connection {
@@ -260,21 +958,15 @@ impl ConnectableStorage for Connection {
             let pool = sqlx::PgPool::connect(&connection_args)
                 .await
                 .context(SqlxSnafu)?;
+            let mut conn = pool.acquire().await.context(SqlxSnafu)?;
+            backfill_properties(&mut conn).await?;
             let result = sqlx::query!("SELECT key, value FROM config")
-                .fetch_all(&mut pool.acquire().await.context(SqlxSnafu)?)
+                .fetch_all(&mut conn)
                 .await
                 .context(SqlxSnafu)?;
+            drop(conn);
 
-            // Map them into a `serde_json::Map`
-            let map: serde_json::Map<String, serde_json::Value> = result
-                .into_iter()
-                .map(|o| Ok((o.key, serde_json::from_str(&o.value)?)))
-                .collect::<Result<_, _>>()
-                .context(JsonSnafu)?;
-
-            // Now we can deserialize this from a `serde_json::Value::Object(map)`
-            let config =
-                serde_json::from_value(serde_json::Value::Object(map)).context(JsonSnafu)?;
+            let config = key_values_to_config(result.into_iter().map(|o| (o.key, o.value)))?;
 
             Ok((Self { conn: pool }, config))
         }
@@ -0,0 +1,7 @@
+CREATE TABLE user_config (
+    user_id INTEGER NOT NULL REFERENCES users(user_id),
+    key TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_user_config_user_key ON user_config(user_id, key);
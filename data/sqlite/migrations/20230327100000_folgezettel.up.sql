@@ -0,0 +1,3 @@
+ALTER TABLE zettel ADD COLUMN folgezettel TEXT NULL;
+
+CREATE UNIQUE INDEX idx_zettel_user_folgezettel ON zettel(user_id, folgezettel);
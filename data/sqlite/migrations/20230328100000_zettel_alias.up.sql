@@ -0,0 +1,9 @@
+-- Other paths under which a zettel can be found. An alias is unique per user, and may not be the path of any zettel.
+CREATE TABLE zettel_alias (
+    zettel_id INTEGER NOT NULL REFERENCES zettel(zettel_id),
+    user_id INTEGER NOT NULL REFERENCES users(user_id),
+    alias TEXT NOT NULL COLLATE NOCASE
+);
+
+CREATE UNIQUE INDEX idx_zettel_alias_user_alias ON zettel_alias(user_id, alias);
+CREATE INDEX idx_zettel_alias_zettel ON zettel_alias(zettel_id);
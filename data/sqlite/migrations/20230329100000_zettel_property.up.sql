@@ -0,0 +1,10 @@
+-- The front matter properties of a zettel, updated whenever the zettel is saved
+CREATE TABLE zettel_property (
+    zettel_id INTEGER NOT NULL REFERENCES zettel(zettel_id),
+    user_id INTEGER NOT NULL REFERENCES users(user_id),
+    key TEXT NOT NULL COLLATE NOCASE,
+    value TEXT NOT NULL COLLATE NOCASE
+);
+
+CREATE INDEX idx_zettel_property_zettel ON zettel_property(zettel_id);
+CREATE INDEX idx_zettel_property_user_key_value ON zettel_property(user_id, key, value);
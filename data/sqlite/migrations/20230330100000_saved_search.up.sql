@@ -0,0 +1,10 @@
+-- Searches that a user saved under a name. `mode` and `properties` are JSON.
+CREATE TABLE saved_search (
+    user_id INTEGER NOT NULL REFERENCES users(user_id),
+    name TEXT NOT NULL COLLATE NOCASE,
+    query TEXT NOT NULL,
+    mode TEXT NOT NULL,
+    properties TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX idx_saved_search_user_name ON saved_search(user_id, name);
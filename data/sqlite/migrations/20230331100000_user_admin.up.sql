@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- the first user that registered administers the existing zettelkasten
+UPDATE users SET is_admin = TRUE WHERE user_id = (SELECT MIN(user_id) FROM users);
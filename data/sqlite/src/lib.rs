@@ -1,20 +1,42 @@
 mod regexp;
 
+use async_broadcast::{InactiveReceiver, Sender};
 use snafu::ResultExt;
-use sqlx::{Row, SqliteConnection, SqlitePool};
-use std::str::FromStr;
+use sqlx::{
+    query::Query,
+    sqlite::{SqliteArguments, SqlitePoolOptions},
+    Connection as _, Row, Sqlite, SqliteConnection, SqlitePool,
+};
+use std::{collections::HashMap, str::FromStr};
 use storage::{
-    BcryptSnafu, ConnectableStorage, Error, InvalidRegexSnafu, JsonSnafu, SearchOpts,
-    SqlxMigrateSnafu, SqlxSnafu, Storage, SystemConfig, User, UserId, Zettel, ZettelHeader,
+    BatchOperation, BatchResult, BcryptSnafu, ConnectableStorage, Error, InvalidQuerySnafu,
+    JsonSnafu, PathChild, PropertyFilter, SavedSearch, SearchMode, SearchOpts, SqlxMigrateSnafu,
+    SqlxSnafu, Storage, StorageEvent, SystemConfig, User, UserConfig, UserId, Zettel, ZettelHeader,
     ZettelId,
 };
 use zettelkasten_shared::{
-    futures::{future::LocalBoxFuture, FutureExt},
-    storage,
+    folgezettel, frontmatter,
+    futures::{future::LocalBoxFuture, stream::BoxStream, FutureExt, StreamExt},
+    namespace, query, snippet, storage,
 };
 
+/// The amount of events that are buffered per subscriber
+const EVENT_CAPACITY: usize = 64;
+
 pub struct Connection {
     conn: SqlitePool,
+    events: Sender<StorageEvent>,
+    /// Keeps the `events` channel open while nobody is subscribed
+    events_receiver: InactiveReceiver<StorageEvent>,
+}
+
+impl Connection {
+    /// Send an event to all subscribers. Sqlite has no way to share these between processes, so only subscribers of
+    /// this connection will receive them.
+    fn notify(&self, event: StorageEvent) {
+        // this only fails when nobody is subscribed
+        drop(self.events.try_broadcast(event));
+    }
 }
 
 #[zettelkasten_shared::async_trait]
@@ -33,7 +55,7 @@ impl Storage for Connection {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
         let query = sqlx::query_as!(
             User,
-            "SELECT user_id as id, username as name, password, last_visited_zettel FROM users",
+            "SELECT user_id as id, username as name, password, last_visited_zettel, is_admin FROM users",
         );
         query.fetch_one(&mut conn).await.context(SqlxSnafu)
     }
@@ -42,7 +64,7 @@ impl Storage for Connection {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
         let query = sqlx::query_as!(
             User,
-            "SELECT user_id as id, username as name, password, last_visited_zettel FROM users WHERE username = ?",
+            "SELECT user_id as id, username as name, password, last_visited_zettel, is_admin FROM users WHERE username = ?",
             username,
         );
         let user = query.fetch_optional(&mut conn).await.context(SqlxSnafu)?;
@@ -56,6 +78,16 @@ impl Storage for Connection {
         Ok(None)
     }
 
+    async fn get_user(&self, user: UserId) -> Result<Option<User>, Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let query = sqlx::query_as!(
+            User,
+            "SELECT user_id as id, username as name, password, last_visited_zettel, is_admin FROM users WHERE user_id = ?",
+            user,
+        );
+        query.fetch_optional(&mut conn).await.context(SqlxSnafu)
+    }
+
     async fn register(&self, username: &str, password: &str) -> Result<User, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
         let query = sqlx::query!(
@@ -72,11 +104,18 @@ impl Storage for Connection {
 
         let query = sqlx::query_as!(
             User,
-            r#"INSERT INTO users (username, password) VALUES (?, ?) RETURNING user_id as "id!", username as "name!", password as "password!", last_visited_zettel"#,
+            r#"INSERT INTO users (username, password, is_admin) VALUES (?, ?, NOT EXISTS (SELECT 1 FROM users)) RETURNING user_id as "id!", username as "name!", password as "password!", last_visited_zettel, is_admin as "is_admin!: bool""#,
             username,
             password
         );
-        let user = query.fetch_one(&mut *conn).await.context(SqlxSnafu)?;
+        // `fetch_one` stops before the statement is done, which keeps its write lock until the connection runs the
+        // next statement. Other connections of the pool would time out waiting for it.
+        let user = query
+            .fetch_all(&mut *conn)
+            .await
+            .context(SqlxSnafu)?
+            .pop()
+            .expect("INSERT ... RETURNING returns the new row");
 
         Ok(user)
     }
@@ -87,85 +126,105 @@ impl Storage for Connection {
         search: SearchOpts<'_>,
     ) -> Result<Vec<ZettelHeader>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
-        if search.list_all {
-            let results = sqlx::query(
-                "SELECT zettel_id as id, path FROM zettel WHERE user_id = ? ORDER BY path ASC",
-            )
-            .bind(user)
+        let highlight = search.highlight_regex()?;
+        let has_query = !search.list_all && !search.query.trim().is_empty();
+        if !search.list_all && !has_query && search.properties.is_empty() {
+            return Err(Error::InvalidSearchOpts);
+        }
+
+        // the query uses REGEXP, and sql does not understand that because we need to register it ourselves
+        // therefor we can't use `sqlx::query_as!()` and instead have to do it manually.
+        let mut sql =
+            "SELECT zettel_id as id, path, body, folgezettel FROM zettel WHERE user_id = ?"
+                .to_owned();
+        let mut params = Vec::new();
+        if has_query {
+            sql.push_str(" AND ");
+            search_condition(&search, &mut sql, &mut params)?;
+        }
+        sql.push_str(&property_conditions(search.properties));
+        sql.push_str(" ORDER BY path ASC");
+
+        let mut statement = sqlx::query(&sql).bind(user);
+        for param in params {
+            statement = match param {
+                query::Param::Text(text) => statement.bind(text),
+                query::Param::Date(date) => statement.bind(date),
+            };
+        }
+        let results = bind_properties(statement, search.properties)
             .fetch_all(&mut conn)
             .await
             .context(SqlxSnafu)?;
 
-            Ok(results
-                .into_iter()
-                .map(|row| ZettelHeader {
+        Ok(results
+            .into_iter()
+            .map(|row| {
+                let body: String = row.get(2);
+                let snippets = highlight
+                    .as_ref()
+                    .map(|regex| snippet::build(&body, regex))
+                    .unwrap_or_default();
+                ZettelHeader {
                     id: row.get(0),
                     path: row.get(1),
-                    highlight_text: None,
-                })
-                .collect())
-        } else if !search.query.trim().is_empty() {
-            let regex = regex::Regex::new(search.query).context(InvalidRegexSnafu)?;
-
-            // we're using REGEXP here, and sql does not understand that because we need to register it ourselves
-            // therefor we can't use `sqlx::query_as!()` and instead have to do it manually.
-            let query = sqlx::query(
-                "SELECT zettel_id as id, path, body FROM zettel WHERE user_id = ? AND (body REGEXP ? OR path REGEXP ?)",
-            ).bind(user)
-            .bind(search.query)
-            .bind(search.query);
-            let results = query.fetch_all(&mut conn).await.context(SqlxSnafu)?;
-
-            Ok(results
-                .into_iter()
-                .map(|row| {
-                    let body: String = row.get(2);
-                    let highlight_text = if let Some(m) = regex.find(&body) {
-                        let start = if m.start() < 10 { 0 } else { m.start() - 10 };
-                        let end = if m.end() + 10 >= body.len() {
-                            body.len()
-                        } else {
-                            m.end() + 10
-                        };
-                        Some(body[start..end].to_owned())
-                    } else {
-                        None
-                    };
-                    ZettelHeader {
-                        id: row.get(0),
-                        path: row.get(1),
-                        highlight_text,
-                    }
-                })
-                .collect())
-        } else {
-            Err(Error::InvalidSearchOpts)
-        }
+                    snippets,
+                    folgezettel: row.get(3),
+                }
+            })
+            .collect())
     }
 
-    async fn get_zettel(&self, user: UserId, id: ZettelId) -> Result<Zettel, Error> {
+    async fn get_children(&self, user: UserId, parent: &str) -> Result<Vec<PathChild>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
-        let result = sqlx::query!(
-            "SELECT zettel_id, path, body FROM zettel WHERE user_id = ? AND zettel_id = ?",
-            user,
-            id
+        let prefix = namespace::join(parent, "");
+        let query = sqlx::query(
+            r#"SELECT
+                COALESCE(MAX(CASE WHEN is_leaf THEN name END), MIN(name)),
+                MAX(CASE WHEN is_leaf THEN zettel_id END),
+                SUM(NOT is_leaf)
+            FROM (
+                SELECT
+                    zettel_id,
+                    CASE WHEN instr(rest, '/') = 0 THEN rest ELSE substr(rest, 1, instr(rest, '/') - 1) END AS name,
+                    instr(rest, '/') = 0 AS is_leaf
+                FROM (
+                    SELECT zettel_id, substr(path, length(?) + 1) AS rest
+                    FROM zettel
+                    WHERE user_id = ? AND path LIKE ? ESCAPE '\'
+                )
+            )
+            WHERE name != ''
+            GROUP BY LOWER(name)
+            ORDER BY LOWER(name)"#,
         )
-        .fetch_one(&mut conn)
-        .await
-        .context(SqlxSnafu)?;
+        .bind(&prefix)
+        .bind(user)
+        .bind(namespace::like_pattern(parent));
+        let results = query.fetch_all(&mut conn).await.context(SqlxSnafu)?;
 
-        Ok(Zettel {
-            id: result.zettel_id,
-            path: result.path,
-            body: result.body,
-            attachments: Vec::new(),
-        })
+        Ok(results
+            .into_iter()
+            .map(|row| PathChild {
+                path: namespace::join(parent, row.get(0)),
+                zettel: row.get(1),
+                descendants: row.get::<i64, _>(2).unsigned_abs(),
+            })
+            .collect())
+    }
+
+    async fn get_zettel(&self, user: UserId, id: ZettelId) -> Result<Zettel, Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        get_zettel(&mut conn, user, id).await
     }
 
     async fn get_zettel_by_url(&self, user: UserId, path: &str) -> Result<Option<Zettel>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
         let result = match sqlx::query!(
-            "SELECT zettel_id, path, body FROM zettel WHERE user_id = ? AND path = ?",
+            r#"SELECT zettel_id, path, body, folgezettel FROM zettel
+            WHERE user_id = ? AND (path = ? OR zettel_id IN (SELECT zettel_id FROM zettel_alias WHERE user_id = ? AND alias = ?))"#,
+            user,
+            path,
             user,
             path
         )
@@ -181,53 +240,94 @@ impl Storage for Connection {
             id: result.zettel_id,
             path: result.path,
             body: result.body,
+            folgezettel: result.folgezettel,
+            aliases: get_aliases(&mut conn, result.zettel_id).await?,
             attachments: Vec::new(),
         }))
     }
 
-    async fn update_zettel(&self, user: UserId, zettel: &mut Zettel) -> Result<(), Error> {
+    async fn get_all_zettels(&self, user: UserId) -> Result<Vec<Zettel>, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
-        if zettel.id == 0 {
-            let query = sqlx::query!(
-                r#"INSERT INTO zettel
-                (user_id, path, body, created_on, last_modified_on)
-                VALUES
-                (?, ?, ?, datetime(), datetime())
-                "#,
-                user,
-                zettel.path,
-                zettel.body,
-            );
-            let id = query
-                .execute(&mut conn)
-                .await
-                .context(SqlxSnafu)?
-                .last_insert_rowid();
-            zettel.id = id;
-            sqlx::query!(
-                "UPDATE users SET last_visited_zettel = ? WHERE user_id = ?",
-                zettel.id,
-                user
-            )
-            .execute(&mut conn)
-            .await
-            .context(SqlxSnafu)?;
+        let zettels = sqlx::query!(
+            "SELECT zettel_id, path, body, folgezettel FROM zettel WHERE user_id = ? ORDER BY path",
+            user
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+        let mut aliases: HashMap<ZettelId, Vec<String>> = HashMap::new();
+        for row in sqlx::query!(
+            "SELECT zettel_id, alias FROM zettel_alias WHERE user_id = ? ORDER BY alias",
+            user
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .context(SqlxSnafu)?
+        {
+            aliases.entry(row.zettel_id).or_default().push(row.alias);
+        }
+        Ok(zettels
+            .into_iter()
+            .map(|zettel| Zettel {
+                id: zettel.zettel_id,
+                aliases: aliases.remove(&zettel.zettel_id).unwrap_or_default(),
+                path: zettel.path,
+                body: zettel.body,
+                folgezettel: zettel.folgezettel,
+                attachments: Vec::new(),
+            })
+            .collect())
+    }
 
-            Ok(())
-        } else {
-            sqlx::query!(
-                r#"UPDATE zettel
-                SET path = ?, body = ?, last_modified_on = datetime()
-                WHERE zettel_id = ?"#,
-                zettel.path,
-                zettel.body,
-                zettel.id,
-            )
-            .execute(&mut conn)
-            .await
-            .context(SqlxSnafu)?;
-            Ok(())
+    async fn update_zettel(&self, user: UserId, zettel: &mut Zettel) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        update_zettel(&mut tx, user, zettel).await?;
+        tx.commit().await.context(SqlxSnafu)?;
+        self.notify(StorageEvent::ZettelChanged {
+            user,
+            id: zettel.id,
+        });
+        Ok(())
+    }
+
+    async fn update_zettel_body(
+        &self,
+        user: UserId,
+        id: ZettelId,
+        base: Option<&str>,
+        body: &str,
+    ) -> Result<Zettel, Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        // the first statement takes the write lock, so nobody changes the body between the check and the update
+        let updated = sqlx::query!(
+            r#"UPDATE zettel SET body = ?
+            WHERE zettel_id = ? AND user_id = ? AND (? IS NULL OR body = ?)"#,
+            body,
+            id,
+            user,
+            base,
+            base,
+        )
+        .execute(&mut tx)
+        .await
+        .context(SqlxSnafu)?;
+        let mut zettel = get_zettel(&mut tx, user, id).await?;
+        if updated.rows_affected() == 0 {
+            return Err(Error::EditConflict { id });
         }
+        // the aliases and properties of the new body
+        update_zettel(&mut tx, user, &mut zettel).await?;
+        tx.commit().await.context(SqlxSnafu)?;
+        self.notify(StorageEvent::ZettelChanged { user, id });
+        Ok(zettel)
+    }
+
+    async fn delete_zettel(&self, user: UserId, id: ZettelId) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        delete_zettel(&mut tx, user, id).await?;
+        tx.commit().await.context(SqlxSnafu)?;
+        self.notify(StorageEvent::ZettelDeleted { user, id });
+        Ok(())
     }
 
     async fn set_user_last_visited_zettel(
@@ -246,20 +346,550 @@ impl Storage for Connection {
     }
 
     async fn update_config(&self, config: &SystemConfig) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        update_config(&mut tx, config).await?;
+        tx.commit().await.context(SqlxSnafu)?;
+        self.notify(StorageEvent::ConfigChanged);
+        Ok(())
+    }
+
+    async fn get_user_config(&self, user: UserId) -> Result<UserConfig, Error> {
         let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let result = sqlx::query!("SELECT key, value FROM user_config WHERE user_id = ?", user)
+            .fetch_all(&mut conn)
+            .await
+            .context(SqlxSnafu)?;
 
-        let serde_json::Value::Object(values) = serde_json::to_value(config).context(JsonSnafu)? else {
-            panic!("SystemConfig did not serialize to an object")
-        };
+        key_values_to_config(result.into_iter().map(|o| (o.key, o.value)))
+    }
 
-        for (key, value) in values {
-            sqlx::query!("UPDATE config SET value = ? WHERE key = ?", value, key)
-                .execute(&mut conn)
-                .await
-                .context(SqlxSnafu)?;
-        }
+    async fn update_user_config(&self, user: UserId, config: &UserConfig) -> Result<(), Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        update_user_config(&mut tx, user, config).await?;
+        tx.commit().await.context(SqlxSnafu)?;
+        self.notify(StorageEvent::UserConfigChanged { user });
+        Ok(())
+    }
+
+    async fn get_saved_searches(&self, user: UserId) -> Result<Vec<SavedSearch>, Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let results = sqlx::query!(
+            "SELECT name, query, mode, properties FROM saved_search WHERE user_id = ? ORDER BY name",
+            user
+        )
+        .fetch_all(&mut conn)
+        .await
+        .context(SqlxSnafu)?;
+        results
+            .into_iter()
+            .map(|row| saved_search_from_row(row.name, row.query, &row.mode, &row.properties))
+            .collect()
+    }
+
+    async fn save_search(&self, user: UserId, search: &SavedSearch) -> Result<(), Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        let mode = serde_json::to_string(&search.mode).context(JsonSnafu)?;
+        let properties = serde_json::to_string(&search.properties).context(JsonSnafu)?;
+        sqlx::query!(
+            r#"INSERT INTO saved_search (user_id, name, query, mode, properties) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, name) DO UPDATE
+            SET name = excluded.name, query = excluded.query, mode = excluded.mode, properties = excluded.properties"#,
+            user,
+            search.name,
+            search.query,
+            mode,
+            properties,
+        )
+        .execute(&mut conn)
+        .await
+        .context(SqlxSnafu)?;
+        Ok(())
+    }
+
+    async fn delete_saved_search(&self, user: UserId, name: &str) -> Result<(), Error> {
+        let mut conn = self.conn.acquire().await.context(SqlxSnafu)?;
+        sqlx::query!(
+            "DELETE FROM saved_search WHERE user_id = ? AND name = ?",
+            user,
+            name
+        )
+        .execute(&mut conn)
+        .await
+        .context(SqlxSnafu)?;
         Ok(())
     }
+
+    async fn batch(
+        &self,
+        user: UserId,
+        operations: &mut [BatchOperation],
+    ) -> Result<Vec<BatchResult>, Error> {
+        let mut tx = self.conn.begin().await.context(SqlxSnafu)?;
+        let mut created = Vec::new();
+        let mut outcome = None;
+        for (idx, operation) in operations.iter_mut().enumerate() {
+            let result = match operation {
+                BatchOperation::UpdateZettel(zettel) => {
+                    if zettel.id == 0 {
+                        created.push(idx);
+                    }
+                    update_zettel(&mut tx, user, zettel).await
+                }
+                BatchOperation::DeleteZettel(id) => delete_zettel(&mut tx, user, *id).await,
+                BatchOperation::UpdateConfig(config) => update_config(&mut tx, config).await,
+                BatchOperation::UpdateUserConfig(config) => {
+                    update_user_config(&mut tx, user, config).await
+                }
+            };
+            if let Err(e) = result {
+                outcome = Some((idx, e));
+                break;
+            }
+        }
+
+        if outcome.is_some() {
+            tx.rollback().await.context(SqlxSnafu)?;
+            // the zettels that were created in this batch do not exist anymore
+            for idx in created {
+                if let BatchOperation::UpdateZettel(zettel) = &mut operations[idx] {
+                    zettel.id = 0;
+                }
+            }
+        } else {
+            tx.commit().await.context(SqlxSnafu)?;
+            for operation in operations.iter() {
+                self.notify(operation.event(user));
+            }
+        }
+        Ok(BatchResult::from_outcome(operations.len(), outcome))
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, StorageEvent>, Error> {
+        Ok(self.events_receiver.activate_cloned().boxed())
+    }
+}
+
+async fn get_zettel(
+    conn: &mut SqliteConnection,
+    user: UserId,
+    id: ZettelId,
+) -> Result<Zettel, Error> {
+    let result = sqlx::query!(
+        "SELECT zettel_id, path, body, folgezettel FROM zettel WHERE user_id = ? AND zettel_id = ?",
+        user,
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .context(SqlxSnafu)?
+    .ok_or(Error::ZettelNotFound { id })?;
+
+    Ok(Zettel {
+        id: result.zettel_id,
+        path: result.path,
+        body: result.body,
+        folgezettel: result.folgezettel,
+        aliases: get_aliases(conn, result.zettel_id).await?,
+        attachments: Vec::new(),
+    })
+}
+
+async fn update_zettel(
+    conn: &mut SqliteConnection,
+    user: UserId,
+    zettel: &mut Zettel,
+) -> Result<(), Error> {
+    folgezettel::normalize(&mut zettel.folgezettel)?;
+    zettel.normalize_aliases();
+    // paths of other zettels are caught by the unique index, aliases are not
+    let aliased = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM zettel_alias WHERE user_id = ? AND alias = ? AND zettel_id != ?",
+        user,
+        zettel.path,
+        zettel.id,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    if aliased.count > 0 {
+        return Err(Error::PathInUse {
+            path: zettel.path.clone(),
+        });
+    }
+    if zettel.id == 0 {
+        let query = sqlx::query!(
+            r#"INSERT INTO zettel
+            (user_id, path, body, folgezettel, created_on, last_modified_on)
+            VALUES
+            (?, ?, ?, ?, datetime(), datetime())
+            "#,
+            user,
+            zettel.path,
+            zettel.body,
+            zettel.folgezettel,
+        );
+        let id = query
+            .execute(&mut *conn)
+            .await
+            .context(SqlxSnafu)?
+            .last_insert_rowid();
+        sqlx::query!(
+            "UPDATE users SET last_visited_zettel = ? WHERE user_id = ?",
+            id,
+            user
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+        zettel.id = id;
+    } else {
+        let result = sqlx::query!(
+            r#"UPDATE zettel
+            SET path = ?, body = ?, folgezettel = ?, last_modified_on = datetime()
+            WHERE zettel_id = ? AND user_id = ?"#,
+            zettel.path,
+            zettel.body,
+            zettel.folgezettel,
+            zettel.id,
+            user,
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+        if result.rows_affected() == 0 {
+            return Err(Error::ZettelNotFound { id: zettel.id });
+        }
+    }
+
+    sqlx::query!("DELETE FROM zettel_alias WHERE zettel_id = ?", zettel.id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    for alias in &zettel.aliases {
+        if path_in_use(conn, user, alias, zettel.id).await? {
+            return Err(Error::PathInUse {
+                path: alias.clone(),
+            });
+        }
+        sqlx::query!(
+            "INSERT INTO zettel_alias (zettel_id, user_id, alias) VALUES (?, ?, ?)",
+            zettel.id,
+            user,
+            alias
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    }
+
+    update_properties(conn, user, zettel.id, &zettel.body).await
+}
+
+/// Replace the stored front matter properties of a zettel with those in `body`
+async fn update_properties(
+    conn: &mut SqliteConnection,
+    user: UserId,
+    id: ZettelId,
+    body: &str,
+) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM zettel_property WHERE zettel_id = ?", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    let properties = frontmatter::parse(body).map(|front_matter| front_matter.properties);
+    for (key, value) in properties.into_iter().flatten() {
+        sqlx::query!(
+            "INSERT INTO zettel_property (zettel_id, user_id, key, value) VALUES (?, ?, ?, ?)",
+            id,
+            user,
+            key,
+            value
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    }
+    Ok(())
+}
+
+fn saved_search_from_row(
+    name: String,
+    query: String,
+    mode: &str,
+    properties: &str,
+) -> Result<SavedSearch, Error> {
+    Ok(SavedSearch {
+        name,
+        query,
+        mode: serde_json::from_str(mode).context(JsonSnafu)?,
+        properties: serde_json::from_str(properties).context(JsonSnafu)?,
+    })
+}
+
+/// The condition for [`SearchOpts::query`] in its [`SearchMode`], whose parameters are appended to `params` in order
+fn search_condition(
+    search: &SearchOpts<'_>,
+    sql: &mut String,
+    params: &mut Vec<query::Param>,
+) -> Result<(), Error> {
+    let (function, pattern) = match search.mode {
+        SearchMode::Query => {
+            let query = search.query.parse().context(InvalidQuerySnafu)?;
+            query_condition(&query, sql, params);
+            return Ok(());
+        }
+        SearchMode::Literal => {
+            sql.push_str("(instr(path, ?) > 0 OR instr(body, ?) > 0)");
+            params.extend([
+                query::Param::Text(search.query.to_owned()),
+                query::Param::Text(search.query.to_owned()),
+            ]);
+            return Ok(());
+        }
+        SearchMode::LiteralIgnoreCase => ("iregexp", regex::escape(search.query)),
+        SearchMode::Regex => ("regexp", search.query.to_owned()),
+        SearchMode::RegexIgnoreCase => ("iregexp", search.query.to_owned()),
+    };
+    sql.push_str(&format!("({function}(?, path) OR {function}(?, body))"));
+    params.extend([
+        query::Param::Text(pattern.clone()),
+        query::Param::Text(pattern),
+    ]);
+    Ok(())
+}
+
+/// Compile `query` into a condition on the `zettel` table, whose parameters are appended to `params` in order
+fn query_condition(query: &query::Query, sql: &mut String, params: &mut Vec<query::Param>) {
+    use query::{Param, Query};
+
+    match query {
+        Query::Term(term) => {
+            // LIKE only ignores the case of ASCII letters
+            sql.push_str("(iregexp(?, path) OR iregexp(?, body))");
+            params.extend([
+                Param::Text(regex::escape(term)),
+                Param::Text(regex::escape(term)),
+            ]);
+        }
+        Query::Path(prefix) => {
+            sql.push_str(r"path LIKE ? ESCAPE '\'");
+            params.push(Param::Text(format!("{}%", query::escape_like(prefix))));
+        }
+        Query::Tag(tag) => {
+            sql.push_str("zettel_id IN (SELECT zettel_id FROM zettel_property WHERE key IN (");
+            sql.push_str(&vec!["?"; query::TAG_KEYS.len()].join(", "));
+            sql.push_str(") AND value = ?)");
+            params.extend(query::TAG_KEYS.map(|key| Param::Text(key.to_owned())));
+            params.push(Param::Text(tag.clone()));
+        }
+        Query::Before(date) => {
+            sql.push_str("date(created_on) < ?");
+            params.push(Param::Date(*date));
+        }
+        Query::After(date) => {
+            sql.push_str("date(created_on) > ?");
+            params.push(Param::Date(*date));
+        }
+        Query::LinksTo(target) => {
+            sql.push_str("body REGEXP ?");
+            params.push(Param::Text(format!(
+                "(?i){}",
+                query::links_to_regex(target)
+            )));
+        }
+        Query::Regex(regex) => {
+            sql.push_str("(path REGEXP ? OR body REGEXP ?)");
+            params.extend([Param::Text(regex.clone()), Param::Text(regex.clone())]);
+        }
+        Query::Not(query) => {
+            sql.push_str("NOT (");
+            query_condition(query, sql, params);
+            sql.push(')');
+        }
+        Query::And(queries) | Query::Or(queries) => {
+            let operator = if matches!(query, Query::And(_)) {
+                " AND "
+            } else {
+                " OR "
+            };
+            sql.push('(');
+            for (idx, query) in queries.iter().enumerate() {
+                if idx > 0 {
+                    sql.push_str(operator);
+                }
+                query_condition(query, sql, params);
+            }
+            sql.push(')');
+        }
+    }
+}
+
+/// The conditions for [`SearchOpts::properties`], see [`bind_properties`]
+fn property_conditions(filters: &[PropertyFilter]) -> String {
+    " AND zettel_id IN (SELECT zettel_id FROM zettel_property WHERE key = ? AND value = ?)"
+        .repeat(filters.len())
+}
+
+/// Bind the parameters of [`property_conditions`]
+fn bind_properties<'q>(
+    mut query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    filters: &'q [PropertyFilter],
+) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+    for filter in filters {
+        query = query.bind(&filter.key).bind(&filter.value);
+    }
+    query
+}
+
+/// Whether a zettel other than `except` has `path` as its path or as an alias
+async fn path_in_use(
+    conn: &mut SqliteConnection,
+    user: UserId,
+    path: &str,
+    except: ZettelId,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"SELECT
+            (SELECT COUNT(*) FROM zettel WHERE user_id = ? AND path = ? AND zettel_id != ?)
+            + (SELECT COUNT(*) FROM zettel_alias WHERE user_id = ? AND alias = ? AND zettel_id != ?)
+            AS "count!: i64""#,
+        user,
+        path,
+        except,
+        user,
+        path,
+        except,
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    Ok(result.count > 0)
+}
+
+async fn get_aliases(conn: &mut SqliteConnection, id: ZettelId) -> Result<Vec<String>, Error> {
+    let result = sqlx::query!(
+        "SELECT alias FROM zettel_alias WHERE zettel_id = ? ORDER BY alias",
+        id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    Ok(result.into_iter().map(|row| row.alias).collect())
+}
+
+async fn delete_zettel(
+    conn: &mut SqliteConnection,
+    user: UserId,
+    id: ZettelId,
+) -> Result<(), Error> {
+    // make sure the zettel belongs to this user before we remove everything that references it
+    let exists = sqlx::query!(
+        "SELECT zettel_id FROM zettel WHERE zettel_id = ? AND user_id = ?",
+        id,
+        user
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    if exists.is_none() {
+        return Err(Error::ZettelNotFound { id });
+    }
+
+    sqlx::query!(
+        "UPDATE users SET last_visited_zettel = NULL WHERE last_visited_zettel = ?",
+        id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    sqlx::query!(
+        "DELETE FROM zettel_link WHERE source_zettel_id = ? OR destination_zettel_id = ?",
+        id,
+        id
+    )
+    .execute(&mut *conn)
+    .await
+    .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_alias WHERE zettel_id = ?", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_property WHERE zettel_id = ?", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_attachment WHERE zettel_id = ?", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel_history WHERE zettel_id = ?", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    sqlx::query!("DELETE FROM zettel WHERE zettel_id = ?", id)
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    Ok(())
+}
+
+/// Read the front matter properties of the zettels that were saved before `zettel_property` existed
+async fn backfill_properties(conn: &mut SqliteConnection) -> Result<(), Error> {
+    let mut tx = conn.begin().await.context(SqlxSnafu)?;
+    let zettels = sqlx::query!(
+        r#"SELECT zettel.zettel_id, zettel.user_id, zettel.body
+        FROM zettel_property_backfill
+        JOIN zettel ON zettel.zettel_id = zettel_property_backfill.zettel_id"#
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .context(SqlxSnafu)?;
+    for zettel in zettels {
+        update_properties(&mut tx, zettel.user_id, zettel.zettel_id, &zettel.body).await?;
+    }
+    sqlx::query!("DELETE FROM zettel_property_backfill")
+        .execute(&mut *tx)
+        .await
+        .context(SqlxSnafu)?;
+    tx.commit().await.context(SqlxSnafu)
+}
+
+async fn update_config(conn: &mut SqliteConnection, config: &SystemConfig) -> Result<(), Error> {
+    let serde_json::Value::Object(values) = serde_json::to_value(config).context(JsonSnafu)? else {
+        panic!("SystemConfig did not serialize to an object")
+    };
+
+    for (key, value) in values {
+        sqlx::query!("UPDATE config SET value = ? WHERE key = ?", value, key)
+            .execute(&mut *conn)
+            .await
+            .context(SqlxSnafu)?;
+    }
+    Ok(())
+}
+
+async fn update_user_config(
+    conn: &mut SqliteConnection,
+    user: UserId,
+    config: &UserConfig,
+) -> Result<(), Error> {
+    let serde_json::Value::Object(values) = serde_json::to_value(config).context(JsonSnafu)? else {
+        panic!("UserConfig did not serialize to an object")
+    };
+
+    for (key, value) in values {
+        sqlx::query!(
+            r#"INSERT INTO user_config (user_id, key, value) VALUES (?, ?, ?)
+            ON CONFLICT (user_id, key) DO UPDATE SET value = excluded.value"#,
+            user,
+            key,
+            value
+        )
+        .execute(&mut *conn)
+        .await
+        .context(SqlxSnafu)?;
+    }
+    Ok(())
 }
 
 async fn load_config(conn: &mut SqliteConnection) -> Result<SystemConfig, Error> {
@@ -269,10 +899,17 @@ async fn load_config(conn: &mut SqliteConnection) -> Result<SystemConfig, Error>
         .await
         .context(SqlxSnafu)?;
 
+    key_values_to_config(result.into_iter().map(|o| (o.key, o.value)))
+}
+
+/// Deserialize a config struct from rows of `key` and json encoded `value`.
+fn key_values_to_config<T>(rows: impl Iterator<Item = (String, String)>) -> Result<T, Error>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
     // Map them into a `serde_json::Map`
-    let map: serde_json::Map<String, serde_json::Value> = result
-        .into_iter()
-        .map(|o| Ok((o.key, serde_json::from_str(&o.value)?)))
+    let map: serde_json::Map<String, serde_json::Value> = rows
+        .map(|(key, value)| Ok((key, serde_json::from_str(&value)?)))
         .collect::<Result<_, _>>()
         .context(JsonSnafu)?;
 
@@ -290,7 +927,18 @@ impl ConnectableStorage for Connection {
             let options = sqlx::sqlite::SqliteConnectOptions::from_str(&connection_args)
                 .expect("Invalid SQLite connection string")
                 .create_if_missing(true);
-            let connection = SqlitePool::connect_with(options).await.context(SqlxSnafu)?;
+            // REGEXP is registered per sqlite connection, so every connection in the pool needs it
+            let connection = SqlitePoolOptions::new()
+                .after_connect(|conn, _| {
+                    async move {
+                        regexp::register(conn).await;
+                        Ok(())
+                    }
+                    .boxed()
+                })
+                .connect_with(options)
+                .await
+                .context(SqlxSnafu)?;
 
             let config = {
                 let mut conn = connection.acquire().await.context(SqlxSnafu)?;
@@ -298,13 +946,20 @@ impl ConnectableStorage for Connection {
                     .run(&mut conn)
                     .await
                     .context(SqlxMigrateSnafu)?;
-
-                regexp::register(&mut conn).await;
+                backfill_properties(&mut conn).await?;
 
                 load_config(&mut conn).await?
             };
 
-            let connection = Connection { conn: connection };
+            let (mut events, events_receiver) = async_broadcast::broadcast(EVENT_CAPACITY);
+            // drop the oldest events when a subscriber can't keep up, instead of blocking the storage
+            events.set_overflow(true);
+
+            let connection = Connection {
+                conn: connection,
+                events,
+                events_receiver: events_receiver.deactivate(),
+            };
 
             Ok((connection, config))
         }
@@ -331,10 +986,25 @@ async fn test_db() -> (Connection, User) {
     (conn, user)
 }
 
-#[test]
-fn test_search_in_path() {
-    zettelkasten_shared::block_on(async {
-        let (db, user) = test_db().await;
+/// Set the bodies of the zettels at the paths, creating the zettels that don't exist yet
+#[cfg(test)]
+async fn put(db: &Connection, user: UserId, zettels: &[(&str, &str)]) {
+    for &(path, body) in zettels {
+        let mut zettel = db
+            .get_zettel_by_url(user, path)
+            .await
+            .unwrap()
+            .unwrap_or_default();
+        zettel.path = path.to_owned();
+        zettel.body = body.to_owned();
+        db.update_zettel(user, &mut zettel).await.unwrap();
+    }
+}
+
+#[test]
+fn test_search_in_path() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
         let result = db
             .get_zettels(
                 user.id,
@@ -349,6 +1019,1145 @@ fn test_search_in_path() {
         let home = &result[0];
         assert_eq!(home.id, 1);
         assert_eq!(home.path, "home");
-        assert_eq!(home.highlight_text, None);
+        assert!(home.snippets.is_empty());
+    });
+}
+
+#[test]
+fn test_get_zettel_by_url_ignores_case() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let home = db
+            .get_zettel_by_url(user.id, "HoMe")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(home.id, 1);
+        assert_eq!(home.path, "home");
+    });
+}
+
+#[test]
+fn test_user_config() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let config = db.get_user_config(user.id).await.unwrap();
+        assert_eq!(config.landing_zettel, None);
+
+        db.update_user_config(
+            user.id,
+            &storage::UserConfig {
+                landing_zettel: Some("journal".to_owned()),
+                ..config
+            },
+        )
+        .await
+        .unwrap();
+        let config = db.get_user_config(user.id).await.unwrap();
+        assert_eq!(config.landing_zettel.as_deref(), Some("journal"));
+    });
+}
+
+#[test]
+fn test_batch_rolls_back_on_failure() {
+    use storage::{BatchOperation, BatchResult};
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let mut operations = [
+            BatchOperation::UpdateZettel(Zettel {
+                path: "new".to_owned(),
+                ..Default::default()
+            }),
+            BatchOperation::UpdateZettel(Zettel {
+                path: "HOME".to_owned(),
+                ..Default::default()
+            }),
+            BatchOperation::DeleteZettel(1),
+        ];
+        let results = db.batch(user.id, &mut operations).await.unwrap();
+        assert!(matches!(
+            results.as_slice(),
+            [
+                BatchResult::RolledBack,
+                BatchResult::Failed(Error::Sqlx { .. }),
+                BatchResult::Skipped
+            ]
+        ));
+        let BatchOperation::UpdateZettel(zettel) = &operations[0] else {
+            unreachable!()
+        };
+        assert_eq!(zettel.id, 0);
+        assert!(db
+            .get_zettel_by_url(user.id, "new")
+            .await
+            .unwrap()
+            .is_none());
+
+        let mut operations = [
+            BatchOperation::UpdateZettel(Zettel {
+                path: "new".to_owned(),
+                ..Default::default()
+            }),
+            BatchOperation::DeleteZettel(1),
+        ];
+        let results = db.batch(user.id, &mut operations).await.unwrap();
+        assert!(matches!(
+            results.as_slice(),
+            [BatchResult::Committed, BatchResult::Committed]
+        ));
+        assert!(db
+            .get_zettel_by_url(user.id, "new")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(db
+            .get_zettel_by_url(user.id, "home")
+            .await
+            .unwrap()
+            .is_none());
+    });
+}
+
+#[test]
+fn test_subscribe() {
+    use storage::StorageEvent;
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let mut events = db.subscribe().await.unwrap();
+
+        let mut home = db.get_zettel(user.id, 1).await.unwrap();
+        home.body = "Hello".to_owned();
+        db.update_zettel(user.id, &mut home).await.unwrap();
+        db.delete_zettel(user.id, 1).await.unwrap();
+
+        assert!(matches!(
+            events.next().await,
+            Some(StorageEvent::ZettelChanged { id: 1, .. })
+        ));
+        assert!(matches!(
+            events.next().await,
+            Some(StorageEvent::ZettelDeleted { id: 1, .. })
+        ));
+    });
+}
+
+#[test]
+fn test_cached_storage() {
+    use zettelkasten_shared::cache::{CacheStats, CachedStorage};
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let db = CachedStorage::new(db, 16).await.unwrap();
+
+        let mut home = db
+            .get_zettel_by_url(user.id, "home")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(db.get_zettel(user.id, home.id).await.unwrap().body, "");
+        assert_eq!(db.stats(), CacheStats { hits: 1, misses: 1 });
+
+        home.body = "Hello".to_owned();
+        db.update_zettel(user.id, &mut home).await.unwrap();
+        let home = db
+            .get_zettel_by_url(user.id, "home")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(home.body, "Hello");
+        assert_eq!(db.stats(), CacheStats { hits: 1, misses: 2 });
+    });
+}
+
+#[test]
+fn test_save_edit() {
+    use zettelkasten_shared::edit::{save_edit, Saved};
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let mut editing = db.get_zettel(user.id, 1).await.unwrap();
+        let saved = save_edit(&db, user.id, &mut editing, "Edited", false).await;
+        assert_eq!(saved.unwrap(), Saved::Saved);
+        assert_eq!(editing.body, "Edited");
+
+        // another session renames the zettel and saves its body while the editor is open
+        let mut other = editing.clone();
+        other.path = "start".to_owned();
+        other.body = "Other".to_owned();
+        db.update_zettel(user.id, &mut other).await.unwrap();
+        let saved = save_edit(&db, user.id, &mut editing, "Mine", false).await;
+        assert_eq!(saved.unwrap(), Saved::Changed);
+        assert_eq!(db.get_zettel(user.id, 1).await.unwrap().body, "Other");
+
+        // only the body is overwritten
+        let saved = save_edit(&db, user.id, &mut editing, "Mine", true).await;
+        assert_eq!(saved.unwrap(), Saved::Saved);
+        let stored = db.get_zettel(user.id, 1).await.unwrap();
+        assert_eq!(
+            (stored.path.as_str(), stored.body.as_str()),
+            ("start", "Mine")
+        );
+        assert_eq!(editing.path, "start");
+
+        // a deleted zettel is created again with id 0
+        db.delete_zettel(user.id, 1).await.unwrap();
+        let saved = save_edit(&db, user.id, &mut editing, "Again", true).await;
+        assert_eq!(saved.unwrap(), Saved::Deleted);
+        editing.id = 0;
+        let saved = save_edit(&db, user.id, &mut editing, "Again", false).await;
+        assert_eq!(saved.unwrap(), Saved::Saved);
+        let stored = db
+            .get_zettel_by_url(user.id, "start")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.body, "Again");
+    });
+}
+
+/// A connection whose first [`Storage::get_zettel`] waits at `barrier` after reading the zettel, and again before
+/// returning it, so the test can change the zettel while the read is in flight
+#[cfg(test)]
+struct Slow {
+    inner: std::sync::Arc<Connection>,
+    barrier: std::sync::Arc<async_lock::Barrier>,
+    delay: std::sync::atomic::AtomicBool,
+}
+
+#[cfg(test)]
+#[zettelkasten_shared::async_trait]
+impl Storage for Slow {
+    async fn user_count(&self) -> Result<u64, Error> {
+        self.inner.user_count().await
+    }
+    async fn login_single_user(&self) -> Result<User, Error> {
+        self.inner.login_single_user().await
+    }
+    async fn login(&self, username: &str, password: &str) -> Result<Option<User>, Error> {
+        self.inner.login(username, password).await
+    }
+    async fn register(&self, username: &str, password: &str) -> Result<User, Error> {
+        self.inner.register(username, password).await
+    }
+    async fn get_user(&self, user: UserId) -> Result<Option<User>, Error> {
+        self.inner.get_user(user).await
+    }
+    async fn get_zettels(
+        &self,
+        user: UserId,
+        search: SearchOpts<'_>,
+    ) -> Result<Vec<ZettelHeader>, Error> {
+        self.inner.get_zettels(user, search).await
+    }
+    async fn get_children(&self, user: UserId, parent: &str) -> Result<Vec<PathChild>, Error> {
+        self.inner.get_children(user, parent).await
+    }
+    async fn get_zettel(&self, user: UserId, id: ZettelId) -> Result<Zettel, Error> {
+        let zettel = self.inner.get_zettel(user, id).await;
+        if self.delay.swap(false, std::sync::atomic::Ordering::SeqCst) {
+            self.barrier.wait().await;
+            self.barrier.wait().await;
+        }
+        zettel
+    }
+    async fn get_zettel_by_url(&self, user: UserId, url: &str) -> Result<Option<Zettel>, Error> {
+        self.inner.get_zettel_by_url(user, url).await
+    }
+    async fn get_all_zettels(&self, user: UserId) -> Result<Vec<Zettel>, Error> {
+        self.inner.get_all_zettels(user).await
+    }
+    async fn update_zettel(&self, user: UserId, zettel: &mut Zettel) -> Result<(), Error> {
+        self.inner.update_zettel(user, zettel).await
+    }
+    async fn update_zettel_body(
+        &self,
+        user: UserId,
+        id: ZettelId,
+        base: Option<&str>,
+        body: &str,
+    ) -> Result<Zettel, Error> {
+        self.inner.update_zettel_body(user, id, base, body).await
+    }
+    async fn delete_zettel(&self, user: UserId, id: ZettelId) -> Result<(), Error> {
+        self.inner.delete_zettel(user, id).await
+    }
+    async fn set_user_last_visited_zettel(
+        &self,
+        user: UserId,
+        zettel_id: Option<ZettelId>,
+    ) -> Result<(), Error> {
+        self.inner
+            .set_user_last_visited_zettel(user, zettel_id)
+            .await
+    }
+    async fn update_config(&self, config: &SystemConfig) -> Result<(), Error> {
+        self.inner.update_config(config).await
+    }
+    async fn get_user_config(&self, user: UserId) -> Result<UserConfig, Error> {
+        self.inner.get_user_config(user).await
+    }
+    async fn update_user_config(&self, user: UserId, config: &UserConfig) -> Result<(), Error> {
+        self.inner.update_user_config(user, config).await
+    }
+    async fn get_saved_searches(&self, user: UserId) -> Result<Vec<SavedSearch>, Error> {
+        self.inner.get_saved_searches(user).await
+    }
+    async fn save_search(&self, user: UserId, search: &SavedSearch) -> Result<(), Error> {
+        self.inner.save_search(user, search).await
+    }
+    async fn delete_saved_search(&self, user: UserId, name: &str) -> Result<(), Error> {
+        self.inner.delete_saved_search(user, name).await
+    }
+    async fn batch(
+        &self,
+        user: UserId,
+        operations: &mut [BatchOperation],
+    ) -> Result<Vec<BatchResult>, Error> {
+        self.inner.batch(user, operations).await
+    }
+    async fn subscribe(&self) -> Result<BoxStream<'static, StorageEvent>, Error> {
+        self.inner.subscribe().await
+    }
+}
+
+#[test]
+fn test_cache_change_during_read() {
+    use std::sync::Arc;
+    use zettelkasten_shared::cache::{CacheStats, CachedStorage};
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let db = Arc::new(db);
+        let barrier = Arc::new(async_lock::Barrier::new(2));
+        let slow = Slow {
+            inner: Arc::clone(&db),
+            barrier: Arc::clone(&barrier),
+            delay: true.into(),
+        };
+        let cache = CachedStorage::new(slow, 16).await.unwrap();
+
+        // the zettel changes after it was read, and another read processes the event of that
+        let change = async {
+            barrier.wait().await;
+            let mut home = db.get_zettel(user.id, 1).await.unwrap();
+            home.body = "new".to_owned();
+            db.update_zettel(user.id, &mut home).await.unwrap();
+            cache
+                .get_zettels(
+                    user.id,
+                    SearchOpts {
+                        list_all: true,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+            barrier.wait().await;
+        };
+        let (stale, ()) =
+            zettelkasten_shared::futures::future::join(cache.get_zettel(user.id, 1), change).await;
+        assert_eq!(stale.unwrap().body, "");
+
+        let zettel = cache.get_zettel(user.id, 1).await.unwrap();
+        assert_eq!(zettel.body, "new");
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 3 });
+    });
+}
+
+#[test]
+fn test_cache_paths_ignore_case() {
+    use zettelkasten_shared::cache::{CacheStats, CachedStorage};
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let cache = CachedStorage::new(db, 16).await.unwrap();
+        for url in ["home", "HOME", "Home"] {
+            let zettel = cache.get_zettel_by_url(user.id, url).await.unwrap();
+            assert_eq!(zettel.unwrap().id, 1);
+        }
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 1 });
+    });
+}
+
+#[test]
+fn test_templates() {
+    use zettelkasten_shared::template;
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        db.update_zettel(
+            user.id,
+            &mut Zettel {
+                path: "templates/literature".to_owned(),
+                body: "# {{path}}\nby {{user}}, {{unknown}}".to_owned(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let templates = template::list_templates(&db, &user).await.unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(
+            template::template_name(&templates[0].path),
+            Some("literature")
+        );
+
+        let zettel = template::new_zettel(
+            &db,
+            user.id,
+            &templates[0].path,
+            &template::TemplateVars::new("lit/paper", &user),
+        )
+        .await
+        .unwrap();
+        assert_eq!(zettel.id, 0);
+        assert_eq!(zettel.body, "# lit/paper\nby test, {{unknown}}");
+    });
+}
+
+#[test]
+fn test_daily_note() {
+    use zettelkasten_shared::{chrono::NaiveDate, journal};
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        db.update_zettel(
+            user.id,
+            &mut Zettel {
+                path: "templates/daily".to_owned(),
+                body: "# {{date}}".to_owned(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let config = storage::UserConfig {
+            journal_template: Some("daily".to_owned()),
+            ..Default::default()
+        };
+        let date = NaiveDate::from_ymd_opt(2023, 3, 27).unwrap();
+
+        let unsaved = journal::daily_note(&db, &user, &config, date)
+            .await
+            .unwrap();
+        assert_eq!(unsaved.id, 0);
+        assert_eq!(unsaved.path, "journal/2023-03-27");
+        assert_eq!(journal::journal_date(&unsaved.path), Some(date));
+
+        let created = journal::create_daily_note(&db, &user, &config, date)
+            .await
+            .unwrap();
+        assert_ne!(created.id, 0);
+        assert_eq!(created.body, "# 2023-03-27");
+
+        let resolved = journal::create_daily_note(&db, &user, &config, date)
+            .await
+            .unwrap();
+        assert_eq!(resolved.id, created.id);
+    });
+}
+
+#[test]
+fn test_link_graph() {
+    use zettelkasten_shared::links::LinkGraph;
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        put(
+            &db,
+            user.id,
+            &[
+                ("home", "See [A] and the [config](sys:config)"),
+                ("a", "[missing](nope) and [home]"),
+                ("b", "Nobody links to me"),
+                ("templates/note", "Back to [{{path}}]"),
+            ],
+        )
+        .await;
+
+        let graph = LinkGraph::load(&db, user.id).await.unwrap();
+        let orphans: Vec<&str> = graph.orphans().iter().map(|z| z.path.as_str()).collect();
+        assert_eq!(orphans, ["b"]);
+
+        let broken = graph.broken_links();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].source.path, "a");
+        assert_eq!(broken[0].target, "nope");
+    });
+}
+
+#[test]
+fn test_get_all_zettels() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let other = db.register("other", "other").await.unwrap();
+        for (user, path, aliases) in [
+            (user.id, "b", vec!["z".to_owned(), "y".to_owned()]),
+            (user.id, "A", Vec::new()),
+            (other.id, "c", vec!["x".to_owned()]),
+        ] {
+            let mut zettel = Zettel {
+                path: path.to_owned(),
+                body: format!("About {path}"),
+                aliases,
+                ..Default::default()
+            };
+            db.update_zettel(user, &mut zettel).await.unwrap();
+        }
+
+        let zettels = db.get_all_zettels(user.id).await.unwrap();
+        let listed: Vec<_> = zettels
+            .iter()
+            .map(|zettel| {
+                (
+                    zettel.path.as_str(),
+                    zettel.body.as_str(),
+                    &zettel.aliases[..],
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("A", "About A", &[][..]),
+                ("b", "About b", &["y".to_owned(), "z".to_owned()][..]),
+                ("home", "", &[][..]),
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_export() {
+    use zettelkasten_shared::{
+        export::{ExportGraph, ExportOpts},
+        links::LinkGraph,
+    };
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        // home -> a/1 -> a/2 -> b
+        put(
+            &db,
+            user.id,
+            &[
+                ("home", "[a/1]"),
+                ("a/1", "[A/2]"),
+                ("a/2", "[b] & [missing]"),
+                ("b", "The end"),
+            ],
+        )
+        .await;
+        let graph = LinkGraph::load(&db, user.id).await.unwrap();
+        let all = ExportGraph::new(&graph, &ExportOpts::default()).unwrap();
+        let id = |path: &str| graph.resolve(path).unwrap();
+        let paths: Vec<_> = all.nodes.iter().map(|node| node.path.as_str()).collect();
+        assert_eq!(paths, ["a/1", "a/2", "b", "home"]);
+        assert_eq!(
+            all.edges,
+            [
+                (id("home"), id("a/1")),
+                (id("a/1"), id("a/2")),
+                (id("a/2"), id("b"))
+            ]
+            .into()
+        );
+    });
+}
+
+#[test]
+fn test_folgezettel() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let mut first = Zettel {
+            path: "first".to_owned(),
+            folgezettel: Some(" 1 ".to_owned()),
+            ..Default::default()
+        };
+        db.update_zettel(user.id, &mut first).await.unwrap();
+        assert_eq!(first.folgezettel.as_deref(), Some("1"));
+        let mut duplicate = Zettel {
+            path: "duplicate".to_owned(),
+            folgezettel: Some("1".to_owned()),
+            ..Default::default()
+        };
+        assert!(db.update_zettel(user.id, &mut duplicate).await.is_err());
+        let mut branch = Zettel {
+            path: "branch".to_owned(),
+            folgezettel: Some("1a".to_owned()),
+            ..Default::default()
+        };
+        db.update_zettel(user.id, &mut branch).await.unwrap();
+
+        let zettels = db
+            .get_zettels(
+                user.id,
+                SearchOpts {
+                    list_all: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let listed: Vec<_> = zettels
+            .iter()
+            .map(|zettel| (zettel.path.as_str(), zettel.folgezettel.as_deref()))
+            .collect();
+        assert_eq!(
+            listed,
+            [("branch", Some("1a")), ("first", Some("1")), ("home", None)]
+        );
+        let stored = db.get_zettel(user.id, branch.id).await.unwrap();
+        assert_eq!(stored.folgezettel.as_deref(), Some("1a"));
+    });
+}
+
+#[test]
+fn test_get_children() {
+    use storage::PathChild;
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let mut ids = HashMap::new();
+        for path in [
+            "project",
+            "project/rust",
+            "Project/rust/async",
+            "project/zettel",
+            "people/ada",
+            "100%_done",
+        ] {
+            let mut zettel = Zettel {
+                path: path.to_owned(),
+                ..Default::default()
+            };
+            db.update_zettel(user.id, &mut zettel).await.unwrap();
+            ids.insert(path, zettel.id);
+        }
+
+        let home = db
+            .get_zettel_by_url(user.id, "home")
+            .await
+            .unwrap()
+            .unwrap();
+        let top_level = db.get_children(user.id, "").await.unwrap();
+        assert_eq!(
+            top_level,
+            [
+                PathChild {
+                    path: "100%_done".to_owned(),
+                    zettel: Some(ids["100%_done"]),
+                    descendants: 0,
+                },
+                PathChild {
+                    path: "home".to_owned(),
+                    zettel: Some(home.id),
+                    descendants: 0,
+                },
+                PathChild {
+                    path: "people".to_owned(),
+                    zettel: None,
+                    descendants: 1,
+                },
+                PathChild {
+                    path: "project".to_owned(),
+                    zettel: Some(ids["project"]),
+                    descendants: 3,
+                },
+            ]
+        );
+        let project = db.get_children(user.id, "PROJECT").await.unwrap();
+        let paths: Vec<_> = project.iter().map(|child| child.path.as_str()).collect();
+        assert_eq!(paths, ["PROJECT/rust", "PROJECT/zettel"]);
+        assert_eq!(project[0].descendants, 1);
+        // `%` and `_` are not wildcards
+        assert!(db.get_children(user.id, "100%").await.unwrap().is_empty());
+        assert!(db.get_children(user.id, "p_ople").await.unwrap().is_empty());
+    });
+}
+
+#[test]
+fn test_aliases() {
+    use zettelkasten_shared::links::LinkGraph;
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let mut rust = Zettel {
+            path: "rust".to_owned(),
+            aliases: vec![
+                " Rust language ".to_owned(),
+                String::new(),
+                "RUST".to_owned(),
+                "rust language".to_owned(),
+            ],
+            ..Default::default()
+        };
+        db.update_zettel(user.id, &mut rust).await.unwrap();
+        assert_eq!(rust.aliases, ["Rust language"]);
+
+        let by_alias = db
+            .get_zettel_by_url(user.id, "RUST LANGUAGE")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(by_alias.id, rust.id);
+        assert_eq!(by_alias.aliases, ["Rust language"]);
+
+        // aliases and paths share one namespace
+        let mut clash = Zettel {
+            path: "rust language".to_owned(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            db.update_zettel(user.id, &mut clash).await,
+            Err(Error::PathInUse { .. })
+        ));
+        let mut home = db.get_zettel(user.id, 1).await.unwrap();
+        home.aliases = vec!["Rust".to_owned()];
+        assert!(matches!(
+            db.update_zettel(user.id, &mut home).await,
+            Err(Error::PathInUse { .. })
+        ));
+        home.aliases = vec!["start".to_owned()];
+        home.body = "[rust] and [Rust language] and [start]".to_owned();
+        db.update_zettel(user.id, &mut home).await.unwrap();
+
+        let graph = LinkGraph::load(&db, user.id).await.unwrap();
+        assert!(graph.broken_links().is_empty());
+        assert_eq!(graph.resolve("Start"), Some(home.id));
+
+        db.delete_zettel(user.id, rust.id).await.unwrap();
+        assert!(db
+            .get_zettel_by_url(user.id, "rust language")
+            .await
+            .unwrap()
+            .is_none());
+    });
+}
+
+#[test]
+fn test_property_filters() {
+    use storage::PropertyFilter;
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        for (path, status) in [("a", "draft"), ("b", "Draft"), ("c", "done")] {
+            let mut zettel = Zettel {
+                path: path.to_owned(),
+                body: format!("---\nstatus: {status}\ntags: [rust]\n---\nAbout {path}"),
+                ..Default::default()
+            };
+            db.update_zettel(user.id, &mut zettel).await.unwrap();
+        }
+        let search = |query: &'static str, filters: &'static [&'static str]| {
+            let db = &db;
+            async move {
+                let properties: Vec<PropertyFilter> =
+                    filters.iter().map(|f| f.parse().unwrap()).collect();
+                let results = db
+                    .get_zettels(
+                        user.id,
+                        SearchOpts {
+                            query,
+                            properties: &properties,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .unwrap();
+                let mut paths: Vec<_> = results.into_iter().map(|z| z.path).collect();
+                paths.sort();
+                paths
+            }
+        };
+        assert_eq!(search("", &["status = draft"]).await, ["a", "b"]);
+        assert_eq!(
+            search("", &["STATUS=draft", "tags = rust"]).await,
+            ["a", "b"]
+        );
+        assert_eq!(search("/About [bc]/", &["status = draft"]).await, ["b"]);
+        assert!(search("", &["status = missing"]).await.is_empty());
+
+        // properties follow the body
+        let mut a = db.get_zettel_by_url(user.id, "a").await.unwrap().unwrap();
+        a.body = "No front matter".to_owned();
+        db.update_zettel(user.id, &mut a).await.unwrap();
+        assert_eq!(search("", &["status = draft"]).await, ["b"]);
+    });
+}
+
+#[test]
+fn test_property_backfill() {
+    use storage::PropertyFilter;
+
+    zettelkasten_shared::block_on(async {
+        let path = std::env::temp_dir().join(format!(
+            "zettelkasten-property-backfill-{}.db",
+            std::process::id()
+        ));
+        let url = format!("sqlite://{}", path.display());
+
+        // a database from before zettel_property existed
+        {
+            let options = sqlx::sqlite::SqliteConnectOptions::from_str(&url)
+                .unwrap()
+                .create_if_missing(true);
+            let pool = SqlitePool::connect_with(options).await.unwrap();
+            let mut migrator = sqlx::migrate!();
+            migrator.migrations = migrator
+                .migrations
+                .iter()
+                .filter(|migration| migration.version < 20_230_329_100_000)
+                .cloned()
+                .collect::<Vec<_>>()
+                .into();
+            migrator.run(&pool).await.unwrap();
+            sqlx::query("INSERT INTO users (user_id, username, password) VALUES (1, 'test', '')")
+                .execute(&pool)
+                .await
+                .unwrap();
+            sqlx::query(
+                r#"INSERT INTO zettel (user_id, path, body, created_on, last_modified_on)
+                VALUES (1, 'a', ?, datetime(), datetime())"#,
+            )
+            .bind("---\nstatus: draft\n---\nAbout a")
+            .execute(&pool)
+            .await
+            .unwrap();
+            pool.close().await;
+        }
+
+        let (db, _config) = Connection::connect(url).await.unwrap();
+        let properties: Vec<PropertyFilter> = vec!["status = draft".parse().unwrap()];
+        let results = db
+            .get_zettels(
+                1,
+                SearchOpts {
+                    properties: &properties,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        db.conn.close().await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            results.into_iter().map(|z| z.path).collect::<Vec<_>>(),
+            ["a"]
+        );
+    });
+}
+
+/// [`test_db`] with a few zettels to search with queries
+#[cfg(test)]
+async fn query_db() -> (Connection, User) {
+    let (db, user) = test_db().await;
+    put(
+        &db,
+        user.id,
+        &[
+            (
+                "rust/async",
+                "---\ntags: [rust, async]\n---\nFutures and [rust]",
+            ),
+            ("rust/traits", "Traits, see [the book](Rust)"),
+            ("journal/2023-03-01", "Learned about 100% of [rust/async]"),
+        ],
+    )
+    .await;
+    (db, user)
+}
+
+/// The paths that `query` finds
+#[cfg(test)]
+async fn search_query(db: &Connection, user: &User, query: &str) -> Vec<String> {
+    let results = db
+        .get_zettels(
+            user.id,
+            SearchOpts {
+                query,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    results.into_iter().map(|z| z.path).collect()
+}
+
+#[test]
+fn test_query_terms() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = query_db().await;
+        assert_eq!(search_query(&db, &user, "FUTURES").await, ["rust/async"]);
+        // LIKE wildcards are matched literally
+        assert_eq!(
+            search_query(&db, &user, "\"100%\"").await,
+            ["journal/2023-03-01"]
+        );
+        assert!(search_query(&db, &user, "100_").await.is_empty());
+    });
+}
+
+#[test]
+fn test_query_fields() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = query_db().await;
+        assert_eq!(
+            search_query(&db, &user, "path:rust/ -tag:async").await,
+            ["rust/traits"]
+        );
+        assert_eq!(
+            search_query(&db, &user, "tag:ASYNC OR traits").await,
+            ["rust/async", "rust/traits"]
+        );
+    });
+}
+
+#[test]
+fn test_query_links_to() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = query_db().await;
+        assert_eq!(
+            search_query(&db, &user, "links-to:rust").await,
+            ["rust/async", "rust/traits"]
+        );
+        assert_eq!(
+            search_query(&db, &user, "links-to:rust/async").await,
+            ["journal/2023-03-01"]
+        );
+    });
+}
+
+#[test]
+fn test_query_regex() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = query_db().await;
+        assert_eq!(
+            search_query(&db, &user, r"/^rust\/t/").await,
+            ["rust/traits"]
+        );
+    });
+}
+
+#[test]
+fn test_query_dates() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = query_db().await;
+        assert!(search_query(&db, &user, "before:2000-01-01")
+            .await
+            .is_empty());
+        assert_eq!(
+            search_query(&db, &user, "after:2000-01-01 AND path:journal").await,
+            ["journal/2023-03-01"]
+        );
+    });
+}
+
+#[test]
+fn test_search_snippets() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        let mut zettel = Zettel {
+            path: "rust".to_owned(),
+            body: "Gedanken\nÜber Rust".to_owned(),
+            ..Default::default()
+        };
+        db.update_zettel(user.id, &mut zettel).await.unwrap();
+        let results = db
+            .get_zettels(
+                user.id,
+                SearchOpts {
+                    query: "über",
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let snippets = &results[0].snippets;
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].text, "Gedanken Über Rust");
+        assert_eq!(snippets[0].matches, vec![9..14]);
+    });
+}
+
+#[test]
+fn test_search_modes() {
+    use storage::SearchMode;
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        for (path, body) in [
+            ("cpp", "Learning C++ and foo(bar)"),
+            ("notes", "Über ÄPFEL"),
+        ] {
+            let mut zettel = Zettel {
+                path: path.to_owned(),
+                body: body.to_owned(),
+                ..Default::default()
+            };
+            db.update_zettel(user.id, &mut zettel).await.unwrap();
+        }
+        let search = |query: &'static str, mode: SearchMode| {
+            let db = &db;
+            async move {
+                db.get_zettels(
+                    user.id,
+                    SearchOpts {
+                        query,
+                        mode,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .map(|results| results.into_iter().map(|z| z.path).collect::<Vec<_>>())
+            }
+        };
+        assert_eq!(search("C++", SearchMode::Literal).await.unwrap(), ["cpp"]);
+        assert!(search("c++", SearchMode::Literal).await.unwrap().is_empty());
+        assert_eq!(
+            search("c++", SearchMode::LiteralIgnoreCase).await.unwrap(),
+            ["cpp"]
+        );
+        assert_eq!(
+            search("äpfel", SearchMode::LiteralIgnoreCase)
+                .await
+                .unwrap(),
+            ["notes"]
+        );
+        assert_eq!(search("üBER", SearchMode::Query).await.unwrap(), ["notes"]);
+        assert_eq!(search("foo\\(", SearchMode::Regex).await.unwrap(), ["cpp"]);
+        assert!(search("^über", SearchMode::Regex).await.unwrap().is_empty());
+        assert_eq!(
+            search("^über", SearchMode::RegexIgnoreCase).await.unwrap(),
+            ["notes"]
+        );
+        assert!(matches!(
+            search("foo(", SearchMode::Regex).await,
+            Err(Error::InvalidRegex { .. })
+        ));
+
+        let results = db
+            .get_zettels(
+                user.id,
+                SearchOpts {
+                    query: "c++",
+                    mode: SearchMode::LiteralIgnoreCase,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        let snippet = &results[0].snippets[0];
+        assert_eq!(&snippet.text[snippet.matches[0].clone()], "C++");
+    });
+}
+
+#[test]
+fn test_saved_searches() {
+    use storage::{PropertyFilter, SavedSearch, SearchMode};
+    use zettelkasten_shared::links::{self, LinkGraph};
+
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        put(
+            &db,
+            user.id,
+            &[
+                ("home", "See the [open questions](search:tag:question)"),
+                ("why", "---\ntags: question\n---\nWhy?"),
+                ("how", "---\ntags:\n  - question\n  - done\n---\nHow?"),
+            ],
+        )
+        .await;
+
+        let open = SavedSearch {
+            name: "Open questions".to_owned(),
+            query: "tag:question".to_owned(),
+            mode: SearchMode::Query,
+            properties: Vec::new(),
+        };
+        db.save_search(user.id, &open).await.unwrap();
+        db.save_search(
+            user.id,
+            &SavedSearch {
+                name: "done".to_owned(),
+                query: "How".to_owned(),
+                mode: SearchMode::Literal,
+                properties: vec!["tags = done".parse::<PropertyFilter>().unwrap()],
+            },
+        )
+        .await
+        .unwrap();
+        // saving under the same name, ignoring case, replaces the search
+        let open = SavedSearch {
+            name: "open questions".to_owned(),
+            query: "tag:question -how".to_owned(),
+            ..open
+        };
+        db.save_search(user.id, &open).await.unwrap();
+
+        let saved = db.get_saved_searches(user.id).await.unwrap();
+        let names: Vec<&str> = saved.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["done", "open questions"]);
+        assert_eq!(saved[1], open);
+        assert_eq!(saved[0].properties.len(), 1);
+
+        let search = links::search_for_link(&db, user.id, "OPEN QUESTIONS")
+            .await
+            .unwrap();
+        let paths: Vec<String> = db
+            .get_zettels(user.id, search.opts())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|z| z.path)
+            .collect();
+        assert_eq!(paths, ["why"]);
+
+        // a link without a saved search is a query of its own
+        let search = links::search_for_link(&db, user.id, "tag:question")
+            .await
+            .unwrap();
+        assert_eq!(search.query, "tag:question");
+        assert_eq!(
+            db.get_zettels(user.id, search.opts()).await.unwrap().len(),
+            2
+        );
+
+        // search links are not broken links
+        let graph = LinkGraph::load(&db, user.id).await.unwrap();
+        assert!(graph.broken_links().is_empty());
+
+        db.delete_saved_search(user.id, "DONE").await.unwrap();
+        let saved = db.get_saved_searches(user.id).await.unwrap();
+        assert_eq!(saved, [open]);
+    });
+}
+
+#[test]
+fn test_users() {
+    zettelkasten_shared::block_on(async {
+        let (db, user) = test_db().await;
+        assert!(user.is_admin);
+
+        let other = db.register("other", "other").await.unwrap();
+        assert!(!other.is_admin);
+        assert!(db.login("test", "test").await.unwrap().unwrap().is_admin);
+        assert!(!db.login("other", "other").await.unwrap().unwrap().is_admin);
+
+        // zettels of other users can't be read
+        let home = db
+            .get_zettel_by_url(user.id, "home")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            db.get_zettel(other.id, home.id).await,
+            Err(Error::ZettelNotFound { .. })
+        ));
     });
 }
@@ -13,15 +13,22 @@
 //! - sqlx has a way to safely get a sqlite3 pointer:
 //!   - <https://docs.rs/sqlx/0.6.2/sqlx/sqlite/struct.SqliteConnection.html#method.lock_handle>
 //!   - <https://docs.rs/sqlx/0.6.2/sqlx/sqlite/struct.LockedSqliteHandle.html#method.as_raw_handle>
+//!
+//! Next to `regexp` we register `iregexp(REGEX, FIELD)`, which ignores case. It has no operator syntax.
 
 use libsqlite3_sys as ffi;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 use std::sync::Arc;
 
 /// The function name for sqlite3. This must be "regexp\0"
 static FN_NAME: &[u8] = b"regexp\0";
+/// The function name of the case insensitive version, also 0-terminated
+static FN_NAME_IGNORE_CASE: &[u8] = b"iregexp\0";
+
+/// The user data of the case insensitive function. Only its address matters: the user data of `regexp` is null.
+static IGNORE_CASE: u8 = 1;
 
-/// Register the regex function with sqlite.
+/// Register the regex functions with sqlite.
 ///
 /// # Panic
 ///
@@ -34,18 +41,35 @@ pub async fn register(connection: &mut sqlx::SqliteConnection) {
         .expect("Could not get a lock handle on the connection");
     let sqlite3 = handle.as_raw_handle();
 
+    for (name, user_data) in [
+        (FN_NAME, std::ptr::null_mut()),
+        (
+            FN_NAME_IGNORE_CASE,
+            std::ptr::addr_of!(IGNORE_CASE).cast_mut().cast(),
+        ),
+    ] {
+        register_function(sqlite3.as_ptr(), name, user_data);
+    }
+}
+
+/// Register `sqlite3_regexp_func` as `name`
+fn register_function(
+    sqlite3: *mut ffi::sqlite3,
+    name: &'static [u8],
+    user_data: *mut std::ffi::c_void,
+) {
     let res = unsafe {
         ffi::sqlite3_create_function_v2(
             //  the database connection
-            sqlite3.as_ptr(),
+            sqlite3,
             // the function name. Must be up to 255 bytes, and 0-terminated
-            FN_NAME.as_ptr().cast(),
+            name.as_ptr().cast(),
             // the number of arguments this function accepts. We want 2 arguments: The regex and the field
             2,
             // we want all our strings to be UTF8, and this function will return the same output with the same inputs
             ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC,
-            // pointer to user data. We're not using user data
-            std::ptr::null_mut(),
+            // pointer to user data, which tells whether case is ignored
+            user_data,
             // xFunc to be executed when we are invoked
             Some(sqlite3_regexp_func),
             // xStep, should be NULL for scalar functions
@@ -81,12 +105,13 @@ unsafe extern "C" fn sqlite3_regexp_func(
     }
 
     // arg0: Regex
-    let Some(regex) = get_regex_from_arg(ctx, *args.offset(0), 0) else {
+    let ignore_case = !ffi::sqlite3_user_data(ctx).is_null();
+    let Some(regex) = get_regex_from_arg(ctx, *args, 0, ignore_case) else {
         return;
     };
 
     // arg1: value
-    let Some(value) = get_text_from_arg(ctx, *args.offset(1)) else {
+    let Some(value) = get_text_from_arg(ctx, *args.add(1)) else {
         return;
     };
 
@@ -110,6 +135,7 @@ unsafe fn get_regex_from_arg(
     ctx: *mut ffi::sqlite3_context,
     arg: *mut ffi::sqlite3_value,
     index: i32,
+    ignore_case: bool,
 ) -> Option<Arc<Regex>> {
     // try to get the auxdata for this field
     let ptr = ffi::sqlite3_get_auxdata(ctx, index);
@@ -123,7 +149,10 @@ unsafe fn get_regex_from_arg(
     // get the text for this field
     let value = get_text_from_arg(ctx, arg)?;
     // try to compile it into a regex
-    let regex = match Regex::new(value) {
+    let regex = match RegexBuilder::new(value)
+        .case_insensitive(ignore_case)
+        .build()
+    {
         Ok(regex) => Arc::new(regex),
         Err(e) => {
             eprintln!("Invalid regex {value:?}: {e:?}");
@@ -162,7 +191,7 @@ unsafe fn get_text_from_arg<'a>(
         match std::str::from_utf8(slice) {
             Ok(result) => Some(result),
             Err(e) => {
-                eprintln!("Incoming text is not valid UTF8: {e:?}",);
+                eprintln!("Incoming text is not valid UTF8: {e:?}");
                 ffi::sqlite3_result_error_code(ctx, ffi::SQLITE_CONSTRAINT_FUNCTION);
                 None
             }
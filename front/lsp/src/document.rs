@@ -0,0 +1,139 @@
+//! Zettels as text documents.
+//!
+//! Every zettel is a virtual document at `zettel:///<path>.md`, so editors treat it as markdown without it ever being
+//! a file. Positions in documents count UTF-16 code units, like the protocol does by default.
+
+use lsp_types::{Position, Range, Url};
+use percent_encoding::percent_decode_str;
+use zettelkasten_shared::{links, namespace};
+
+pub const SCHEME: &str = "zettel";
+const EXTENSION: &str = ".md";
+
+/// The URI of the document of the zettel at `path`
+pub fn uri(path: &str) -> Url {
+    let mut uri = Url::parse(&format!("{SCHEME}:///")).expect("The base URI is valid");
+    let mut segments: Vec<&str> = path.split(namespace::SEPARATOR).collect();
+    let last = format!("{}{EXTENSION}", segments.pop().unwrap_or_default());
+    uri.path_segments_mut()
+        .expect("The base URI has a path")
+        .clear()
+        .extend(segments)
+        .push(&last);
+    uri
+}
+
+/// The path of the zettel whose document is at `uri`, or `None` if the URI is not one of [`uri`]
+pub fn path(uri: &Url) -> Option<String> {
+    if uri.scheme() != SCHEME {
+        return None;
+    }
+    let segments = uri
+        .path_segments()?
+        .map(|segment| percent_decode_str(segment).decode_utf8().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let path = segments.join(&namespace::SEPARATOR.to_string());
+    let path = path.strip_suffix(EXTENSION).unwrap_or(&path);
+    (!path.is_empty()).then(|| path.to_owned())
+}
+
+/// The byte offset of `position` in `text`. Positions past the end of a line are at its end.
+pub fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(end) => line_start += end + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// The position of the byte `offset` in `text`
+pub fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: u32::try_from(before.matches('\n').count()).unwrap_or(u32::MAX),
+        character: u32::try_from(before[line_start..].encode_utf16().count()).unwrap_or(u32::MAX),
+    }
+}
+
+/// The range of the bytes `start..end` in `text`
+pub fn range(text: &str, bytes: std::ops::Range<usize>) -> Range {
+    Range {
+        start: position(text, bytes.start),
+        end: position(text, bytes.end),
+    }
+}
+
+/// The lines of `text` with the byte offset at which each starts
+pub fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split('\n').scan(0, |start, line| {
+        let line_start = *start;
+        *start += line.len() + 1;
+        Some((line_start, line))
+    })
+}
+
+/// All links in `text`, with their ranges made relative to the start of `text`
+pub fn links(text: &str) -> impl Iterator<Item = links::Link<'_>> {
+    lines(text).flat_map(|(start, line)| {
+        links::parse(line).map(move |link| links::Link {
+            text: link.text.start + start..link.text.end + start,
+            range: link.range.start + start..link.range.end + start,
+            target: link.target,
+        })
+    })
+}
+
+/// The link that the byte `offset` is on
+pub fn link_at(text: &str, offset: usize) -> Option<links::Link<'_>> {
+    links(text).find(|link| link.range.contains(&offset))
+}
+
+/// A link that is being typed, to complete its target
+#[derive(Debug, PartialEq, Eq)]
+pub struct PartialLink<'a> {
+    /// The bytes of the target that completions replace. This includes the rest of the target if the link is
+    /// already closed.
+    pub target: std::ops::Range<usize>,
+    /// The part of the target before the cursor
+    pub prefix: &'a str,
+    /// What closes the target, `]` for `[path]` and `)` for `[name](path)`
+    pub close: char,
+    /// Whether `close` already follows the target
+    pub closed: bool,
+}
+
+/// The link around the byte `offset` whose target the cursor is in
+pub fn partial_link(text: &str, offset: usize) -> Option<PartialLink<'_>> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+    let before = &text[line_start..offset];
+    let (open, close) = before.char_indices().rev().find_map(|(i, c)| match c {
+        '[' if !before[..i].ends_with('`') => Some(Some((i, ']'))),
+        '(' if before[..i].ends_with(']') => Some(Some((i, ')'))),
+        '[' | '(' | ']' | ')' => Some(None),
+        _ => None,
+    })??;
+    let start = line_start + open + 1;
+    let end = text[offset..line_end]
+        .find(['[', '(', ']', ')'])
+        .filter(|&i| text[offset + i..].starts_with(close))
+        .map(|i| offset + i);
+    Some(PartialLink {
+        target: start..end.unwrap_or(offset),
+        prefix: &text[start..offset],
+        close,
+        closed: end.is_some(),
+    })
+}
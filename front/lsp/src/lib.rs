@@ -0,0 +1,105 @@
+#![deny(clippy::perf)]
+#![warn(clippy::pedantic)]
+
+mod document;
+mod server;
+
+use snafu::{ResultExt, Snafu};
+use std::sync::Arc;
+use zettelkasten_shared::{storage, Front};
+
+/// A language server on stdin and stdout, so editors can edit zettels with completion, go to definition, hover,
+/// diagnostics and references for links.
+///
+/// Zettels are virtual documents at `zettel:///<path>.md`. Editors get their text with the
+/// `zettelkasten/content` request, and saving a document saves the zettel.
+pub struct Lsp;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// How many lines of a linked zettel to show when hovering over a link
+    pub hover_lines: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { hover_lines: 20 }
+    }
+}
+
+impl Front for Lsp {
+    type Config = Config;
+
+    fn run(
+        config: Self::Config,
+        system_config: storage::SystemConfig,
+        storage: Arc<dyn storage::Storage>,
+    ) {
+        let (connection, io_threads) = lsp_server::Connection::stdio();
+        serve(&connection, config, &system_config, storage).expect("The language server failed");
+        // the writer thread only finishes once every sender is gone
+        drop(connection);
+        io_threads
+            .join()
+            .expect("Could not talk to the editor over stdio");
+    }
+}
+
+/// Serve the editor on the other end of `connection` until it shuts the server down
+///
+/// # Errors
+///
+/// When the editor disconnects without shutting down, or the user can't log in
+pub fn serve(
+    connection: &lsp_server::Connection,
+    config: Config,
+    system_config: &storage::SystemConfig,
+    storage: Arc<dyn storage::Storage>,
+) -> Result<(), Error> {
+    let (id, params) = connection.initialize_start().context(ProtocolSnafu)?;
+    let params: lsp_types::InitializeParams =
+        serde_json::from_value(params).context(InvalidParamsSnafu)?;
+    let user = match server::login(system_config, storage.as_ref(), &params) {
+        Ok(user) => user,
+        Err(e) => {
+            let response = lsp_server::Response::new_err(
+                id,
+                lsp_server::ErrorCode::RequestFailed as i32,
+                e.to_string(),
+            );
+            // the error is reported either way
+            let _ = connection.sender.send(response.into());
+            return Err(e);
+        }
+    };
+    connection
+        .initialize_finish(id, server::initialize_result())
+        .context(ProtocolSnafu)?;
+    server::Server::new(connection, config, storage, user).run()
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    #[snafu(display("The connection to the editor failed: {source}"))]
+    Protocol { source: lsp_server::ProtocolError },
+    #[snafu(display("The editor sent invalid parameters: {source}"))]
+    InvalidParams { source: serde_json::Error },
+    #[snafu(display("Database error: {source}"))]
+    Database { source: storage::Error },
+    #[snafu(display(
+        "Could not log in. Pass `username` and `password` in the initialization options."
+    ))]
+    Login,
+    #[snafu(display("{uri} is not a zettel"))]
+    NotAZettel { uri: lsp_types::Url },
+    #[snafu(display(
+        "{path} was changed somewhere else since it was opened, so it was not saved. Reopen it to get that version."
+    ))]
+    EditConflict { path: String },
+    #[snafu(display(
+        "{path} was deleted somewhere else since it was opened, so it was not saved. Save again to create it again."
+    ))]
+    ZettelDeleted { path: String },
+}
@@ -0,0 +1,586 @@
+use crate::{
+    document, Config, DatabaseSnafu, EditConflictSnafu, Error, InvalidParamsSnafu, NotAZettelSnafu,
+    ProtocolSnafu, ZettelDeletedSnafu,
+};
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{self, Notification as _},
+    request::{self, Request as _},
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    CompletionTextEdit, Diagnostic, DiagnosticSeverity, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, InitializeResult, Location, MarkupContent, MarkupKind, MessageType, OneOf,
+    PublishDiagnosticsParams, Range, ReferenceParams, ServerCapabilities, ServerInfo,
+    ShowMessageParams, SymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, TextEdit, Url, WorkspaceSymbol,
+    WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
+use snafu::{OptionExt, ResultExt};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    sync::Arc,
+};
+use zettelkasten_shared::{
+    block_on, edit, fuzzy, links, namespace,
+    storage::{self, SearchOpts, Storage},
+};
+
+type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The text of a zettel document, for editors that can't read `zettel:` URIs themselves
+enum Content {}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ContentParams {
+    uri: Url,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ContentResult {
+    text: String,
+}
+
+impl request::Request for Content {
+    type Params = ContentParams;
+    type Result = ContentResult;
+    const METHOD: &'static str = "zettelkasten/content";
+}
+
+#[derive(Default, serde::Deserialize)]
+struct Credentials {
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// Log in with the `username` and `password` of the initialization options, or as the single user if they are
+/// logged in automatically
+pub(crate) fn login(
+    system_config: &storage::SystemConfig,
+    storage: &dyn Storage,
+    params: &InitializeParams,
+) -> Result<storage::User> {
+    let credentials: Credentials = match &params.initialization_options {
+        Some(options) => serde_json::from_value(options.clone()).context(InvalidParamsSnafu)?,
+        None => Credentials::default(),
+    };
+    match (credentials, system_config.user_mode) {
+        (
+            Credentials {
+                username: Some(username),
+                password: Some(password),
+            },
+            _,
+        ) => block_on(storage.login(&username, &password))
+            .context(DatabaseSnafu)?
+            .ok_or(Error::Login),
+        (_, storage::UserMode::SingleUserAutoLogin) => {
+            block_on(storage.login_single_user()).context(DatabaseSnafu)
+        }
+        _ => Err(Error::Login),
+    }
+}
+
+pub(crate) fn initialize_result() -> serde_json::Value {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
+                save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                ..Default::default()
+            },
+        )),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["[".to_owned(), "(".to_owned()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    serde_json::to_value(InitializeResult {
+        capabilities,
+        server_info: Some(ServerInfo {
+            name: "zettelkasten".to_owned(),
+            version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+        }),
+    })
+    .expect("The initialize result is valid JSON")
+}
+
+pub(crate) struct Server<'a> {
+    connection: &'a Connection,
+    config: Config,
+    storage: Arc<dyn Storage>,
+    user: storage::User,
+    /// The text of the open documents, which may not be saved yet
+    documents: HashMap<Url, String>,
+    /// The zettels of the open documents as they were stored when they were opened or last saved, to notice when they
+    /// are changed somewhere else in the meantime
+    stored: HashMap<Url, storage::Zettel>,
+}
+
+impl<'a> Server<'a> {
+    pub(crate) fn new(
+        connection: &'a Connection,
+        config: Config,
+        storage: Arc<dyn Storage>,
+        user: storage::User,
+    ) -> Self {
+        Self {
+            connection,
+            config,
+            storage,
+            user,
+            documents: HashMap::new(),
+            stored: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn run(mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self
+                        .connection
+                        .handle_shutdown(&request)
+                        .context(ProtocolSnafu)?
+                    {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.send(response);
+                }
+                Message::Notification(notification) => {
+                    if notification.method == notification::Exit::METHOD {
+                        return Ok(());
+                    }
+                    if let Err(e) = self.handle_notification(notification) {
+                        self.show_error(&e);
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, message: impl Into<Message>) {
+        // if the editor is gone, the receiver is closed as well and `run` stops
+        let _ = self.connection.sender.send(message.into());
+    }
+
+    fn notify<N: notification::Notification>(&self, params: N::Params) {
+        self.send(Notification::new(N::METHOD.to_owned(), params));
+    }
+
+    fn show_error(&self, error: &Error) {
+        self.notify::<notification::ShowMessage>(ShowMessageParams {
+            typ: MessageType::ERROR,
+            message: error.to_string(),
+        });
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            request::Completion::METHOD => {
+                self.respond::<request::Completion>(request, Self::completion)
+            }
+            request::GotoDefinition::METHOD => {
+                self.respond::<request::GotoDefinition>(request, Self::definition)
+            }
+            request::HoverRequest::METHOD => {
+                self.respond::<request::HoverRequest>(request, Self::hover)
+            }
+            request::References::METHOD => {
+                self.respond::<request::References>(request, Self::references)
+            }
+            request::WorkspaceSymbolRequest::METHOD => {
+                self.respond::<request::WorkspaceSymbolRequest>(request, Self::workspace_symbol)
+            }
+            Content::METHOD => self.respond::<Content>(request, Self::content),
+            method => {
+                let message = format!("Unknown method {method}");
+                return error_response(id, lsp_server::ErrorCode::MethodNotFound, &message);
+            }
+        };
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(e @ Error::InvalidParams { .. }) => {
+                error_response(id, lsp_server::ErrorCode::InvalidParams, &e.to_string())
+            }
+            Err(e) => error_response(id, lsp_server::ErrorCode::RequestFailed, &e.to_string()),
+        }
+    }
+
+    fn respond<R: request::Request>(
+        &self,
+        request: Request,
+        handler: fn(&Self, &R::Params) -> Result<R::Result>,
+    ) -> Result<serde_json::Value> {
+        let params = serde_json::from_value(request.params).context(InvalidParamsSnafu)?;
+        let result = handler(self, &params)?;
+        Ok(serde_json::to_value(result).expect("Results are valid JSON"))
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            notification::DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = parse_params(notification)?;
+                let uri = params.text_document.uri;
+                if let Some(path) = document::path(&uri) {
+                    let zettel = block_on(self.storage.get_zettel_by_url(self.user.id, &path))
+                        .context(DatabaseSnafu)?
+                        .unwrap_or_else(|| storage::Zettel {
+                            path,
+                            ..Default::default()
+                        });
+                    self.stored.insert(uri.clone(), zettel);
+                }
+                self.documents
+                    .insert(uri.clone(), params.text_document.text);
+                self.publish_diagnostics(&uri)
+            }
+            notification::DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = parse_params(notification)?;
+                let uri = params.text_document.uri;
+                // the sync is `FULL`, so the last change is the whole text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(uri.clone(), change.text);
+                }
+                self.publish_diagnostics(&uri)
+            }
+            notification::DidSaveTextDocument::METHOD => {
+                let params: lsp_types::DidSaveTextDocumentParams = parse_params(notification)?;
+                self.save(&params.text_document.uri)?;
+                // the zettel might be the target of links that were broken so far
+                let uris: Vec<Url> = self.documents.keys().cloned().collect();
+                for uri in uris {
+                    self.publish_diagnostics(&uri)?;
+                }
+                Ok(())
+            }
+            notification::DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = parse_params(notification)?;
+                self.documents.remove(&params.text_document.uri);
+                self.stored.remove(&params.text_document.uri);
+                self.notify::<notification::PublishDiagnostics>(PublishDiagnosticsParams {
+                    uri: params.text_document.uri,
+                    diagnostics: Vec::new(),
+                    version: None,
+                });
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The text of the document at `uri`. Documents that are not open are read from the storage, and zettels that
+    /// don't exist yet are empty.
+    fn text(&self, uri: &Url) -> Result<String> {
+        if let Some(text) = self.documents.get(uri) {
+            return Ok(text.clone());
+        }
+        let path = document::path(uri).context(NotAZettelSnafu { uri: uri.clone() })?;
+        let zettel =
+            block_on(self.storage.get_zettel_by_url(self.user.id, &path)).context(DatabaseSnafu)?;
+        Ok(zettel.map(|zettel| zettel.body).unwrap_or_default())
+    }
+
+    /// The text of the zettel `id`, preferring the open document if there is one
+    fn zettel_text(&self, id: storage::ZettelId, path: &str) -> Result<String> {
+        if let Some(text) = self.documents.get(&document::uri(path)) {
+            return Ok(text.clone());
+        }
+        let zettel = block_on(self.storage.get_zettel(self.user.id, id)).context(DatabaseSnafu)?;
+        Ok(zettel.body)
+    }
+
+    fn all_zettels(&self) -> Result<Vec<storage::ZettelHeader>> {
+        block_on(self.storage.get_zettels(
+            self.user.id,
+            SearchOpts {
+                list_all: true,
+                ..Default::default()
+            },
+        ))
+        .context(DatabaseSnafu)
+    }
+
+    /// Store the open document at `uri` as the body of its zettel, which is created if it does not exist yet. Nothing
+    /// is stored if the zettel was changed or deleted somewhere else since the document was opened.
+    fn save(&mut self, uri: &Url) -> Result<()> {
+        let (Some(zettel), Some(text)) = (self.stored.get_mut(uri), self.documents.get(uri)) else {
+            return Ok(());
+        };
+        if zettel.body == *text && zettel.id != 0 {
+            return Ok(());
+        }
+        let saved = block_on(edit::save_edit(
+            self.storage.as_ref(),
+            self.user.id,
+            zettel,
+            text,
+            false,
+        ))
+        .context(DatabaseSnafu)?;
+        match saved {
+            edit::Saved::Saved => Ok(()),
+            edit::Saved::Changed => EditConflictSnafu {
+                path: zettel.path.clone(),
+            }
+            .fail(),
+            edit::Saved::Deleted => {
+                // like a new document, saving again creates the zettel
+                zettel.id = 0;
+                ZettelDeletedSnafu {
+                    path: zettel.path.clone(),
+                }
+                .fail()
+            }
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: &Url) -> Result<()> {
+        let text = self.text(uri)?;
+        let mut exists = HashMap::new();
+        let mut diagnostics = Vec::new();
+        for link in document::links(&text) {
+            if links::is_sys_page(link.target) || links::search_target(link.target).is_some() {
+                continue;
+            }
+            let key = link.target.to_lowercase();
+            let found = if let Some(&found) = exists.get(&key) {
+                found
+            } else {
+                let found = block_on(self.storage.get_zettel_by_url(self.user.id, link.target))
+                    .context(DatabaseSnafu)?
+                    .is_some();
+                exists.insert(key, found);
+                found
+            };
+            if !found {
+                diagnostics.push(Diagnostic {
+                    range: document::range(&text, link.range),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    source: Some("zettelkasten".to_owned()),
+                    message: format!("No zettel at `{}`", link.target),
+                    ..Default::default()
+                });
+            }
+        }
+        self.notify::<notification::PublishDiagnostics>(PublishDiagnosticsParams {
+            uri: uri.clone(),
+            diagnostics,
+            version: None,
+        });
+        Ok(())
+    }
+
+    fn completion(&self, params: &CompletionParams) -> Result<Option<CompletionResponse>> {
+        let position = &params.text_document_position;
+        let text = self.text(&position.text_document.uri)?;
+        let offset = document::offset(&text, position.position);
+        let Some(partial) = document::partial_link(&text, offset) else {
+            return Ok(None);
+        };
+        let range = document::range(&text, partial.target.clone());
+        let close = if partial.closed {
+            String::new()
+        } else {
+            partial.close.to_string()
+        };
+        let zettels = self.all_zettels()?;
+        let items = fuzzy::filter(partial.prefix, zettels, |zettel| &zettel.path)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (zettel, _))| CompletionItem {
+                label: zettel.path.clone(),
+                kind: Some(CompletionItemKind::FILE),
+                detail: zettel.folgezettel.clone(),
+                // keep the order of the fuzzy matches
+                sort_text: Some(format!("{i:08}")),
+                filter_text: Some(zettel.path.clone()),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text: format!("{}{close}", zettel.path),
+                })),
+                ..Default::default()
+            })
+            .collect();
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    fn definition(&self, params: &GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
+        let position = &params.text_document_position_params;
+        let text = self.text(&position.text_document.uri)?;
+        let Some(link) = document::link_at(&text, document::offset(&text, position.position))
+        else {
+            return Ok(None);
+        };
+        if links::is_sys_page(link.target) || links::search_target(link.target).is_some() {
+            return Ok(None);
+        }
+        let zettel = block_on(self.storage.get_zettel_by_url(self.user.id, link.target))
+            .context(DatabaseSnafu)?;
+        Ok(zettel.map(|zettel| {
+            GotoDefinitionResponse::Scalar(Location {
+                uri: document::uri(&zettel.path),
+                range: Range::default(),
+            })
+        }))
+    }
+
+    fn hover(&self, params: &HoverParams) -> Result<Option<Hover>> {
+        let position = &params.text_document_position_params;
+        let text = self.text(&position.text_document.uri)?;
+        let Some(link) = document::link_at(&text, document::offset(&text, position.position))
+        else {
+            return Ok(None);
+        };
+        let value = if links::is_sys_page(link.target) {
+            format!("System page `{}`", link.target)
+        } else if let Some(search) = links::search_target(link.target) {
+            self.search_preview(search)?
+        } else {
+            match block_on(self.storage.get_zettel_by_url(self.user.id, link.target))
+                .context(DatabaseSnafu)?
+            {
+                Some(zettel) => {
+                    let lines: Vec<&str> =
+                        zettel.body.lines().take(self.config.hover_lines).collect();
+                    lines.join("\n")
+                }
+                None => format!("No zettel at `{}`", link.target),
+            }
+        };
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(document::range(&text, link.range)),
+        }))
+    }
+
+    /// The paths of the results of a `search:` link
+    fn search_preview(&self, search: &str) -> Result<String> {
+        let saved = block_on(links::search_for_link(
+            self.storage.as_ref(),
+            self.user.id,
+            search,
+        ))
+        .context(DatabaseSnafu)?;
+        let zettels = match block_on(self.storage.get_zettels(self.user.id, saved.opts())) {
+            Ok(zettels) => zettels,
+            Err(e) => return Ok(format!("Invalid search `{}`: {e}", saved.query)),
+        };
+        let mut preview = format!("Search `{}`, {} results\n", saved.query, zettels.len());
+        for zettel in zettels.iter().take(self.config.hover_lines) {
+            writeln!(preview, "- {}", zettel.path).unwrap();
+        }
+        Ok(preview)
+    }
+
+    /// The links to the zettel that the cursor is on a link to, or else to the zettel of the document
+    fn references(&self, params: &ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let position = &params.text_document_position;
+        let uri = &position.text_document.uri;
+        let text = self.text(uri)?;
+        let target = match document::link_at(&text, document::offset(&text, position.position)) {
+            Some(link) if links::is_sys_page(link.target) => return Ok(None),
+            Some(link) if links::search_target(link.target).is_some() => return Ok(None),
+            Some(link) => link.target.to_owned(),
+            None => document::path(uri).context(NotAZettelSnafu { uri: uri.clone() })?,
+        };
+        let zettel = block_on(self.storage.get_zettel_by_url(self.user.id, &target))
+            .context(DatabaseSnafu)?;
+        let names: Vec<String> = match &zettel {
+            Some(zettel) => std::iter::once(zettel.path.clone())
+                .chain(zettel.aliases.iter().cloned())
+                .collect(),
+            None => vec![target],
+        };
+
+        // the query language can't quote a `"`, such paths can't be searched for
+        let mut sources = BTreeMap::new();
+        for name in names.iter().filter(|name| !name.contains('"')) {
+            let query = format!("links-to:\"{name}\"");
+            let found = block_on(self.storage.get_zettels(
+                self.user.id,
+                SearchOpts {
+                    query: &query,
+                    ..Default::default()
+                },
+            ))
+            .context(DatabaseSnafu)?;
+            sources.extend(found.into_iter().map(|header| (header.path, header.id)));
+        }
+
+        let mut locations = Vec::new();
+        if let (Some(zettel), true) = (&zettel, params.context.include_declaration) {
+            locations.push(Location {
+                uri: document::uri(&zettel.path),
+                range: Range::default(),
+            });
+        }
+        for (path, id) in sources {
+            let source_uri = document::uri(&path);
+            let text = self.zettel_text(id, &path)?;
+            locations.extend(
+                document::links(&text)
+                    .filter(|link| {
+                        names
+                            .iter()
+                            .any(|name| name.eq_ignore_ascii_case(link.target))
+                    })
+                    .map(|link| Location {
+                        uri: source_uri.clone(),
+                        range: document::range(&text, link.range),
+                    }),
+            );
+        }
+        Ok(Some(locations))
+    }
+
+    /// Every zettel whose path matches the query, to open zettels from the editor
+    fn workspace_symbol(
+        &self,
+        params: &WorkspaceSymbolParams,
+    ) -> Result<Option<WorkspaceSymbolResponse>> {
+        let zettels = self.all_zettels()?;
+        let symbols = fuzzy::filter(&params.query, zettels, |zettel| &zettel.path)
+            .into_iter()
+            .map(|(zettel, _)| WorkspaceSymbol {
+                name: zettel.path.clone(),
+                kind: SymbolKind::FILE,
+                tags: None,
+                container_name: namespace::parent(&zettel.path).map(str::to_owned),
+                location: OneOf::Left(Location {
+                    uri: document::uri(&zettel.path),
+                    range: Range::default(),
+                }),
+                data: None,
+            })
+            .collect();
+        Ok(Some(WorkspaceSymbolResponse::Nested(symbols)))
+    }
+
+    fn content(&self, params: &ContentParams) -> Result<ContentResult> {
+        Ok(ContentResult {
+            text: self.text(&params.uri)?,
+        })
+    }
+}
+
+fn parse_params<P: serde::de::DeserializeOwned>(notification: Notification) -> Result<P> {
+    serde_json::from_value(notification.params).context(InvalidParamsSnafu)
+}
+
+fn error_response(id: RequestId, code: lsp_server::ErrorCode, message: &str) -> Response {
+    Response::new_err(id, code as i32, message.to_owned())
+}
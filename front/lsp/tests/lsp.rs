@@ -0,0 +1,367 @@
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use serde_json::{json, Value};
+use std::{sync::Arc, thread::JoinHandle, time::Duration};
+use zettelkasten_shared::{
+    block_on,
+    storage::{ConnectableStorage, Storage, SystemConfig, UserMode, Zettel},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<Result<(), zettelkasten_lsp::Error>>>,
+    next_id: i32,
+    /// The notifications received while waiting for responses
+    notifications: Vec<Notification>,
+    storage: Arc<dyn Storage>,
+    _dir: tempfile::TempDir,
+}
+
+impl Client {
+    /// Start a server on a database with the user `test` and the zettels `home` and `rust` with the alias `rs`
+    fn start(user_mode: UserMode) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let url = dir.path().join("zettelkasten.db").display().to_string();
+        let storage: Arc<dyn Storage> = block_on(async {
+            let (storage, _) = zettelkasten_sqlite::Connection::connect(url).await.unwrap();
+            let user = storage.register("test", "test").await.unwrap();
+            for (path, body, aliases) in [
+                ("home", "# Home\nAbout [rust] and [missing]", vec![]),
+                (
+                    "rust",
+                    "# Rust\nA language\nSee [home](home)",
+                    vec!["rs".to_owned()],
+                ),
+                (
+                    "notes/c",
+                    "Unlike [rs], see [search](search:language)",
+                    vec![],
+                ),
+            ] {
+                storage
+                    .update_zettel(
+                        user.id,
+                        &mut Zettel {
+                            path: path.to_owned(),
+                            body: body.to_owned(),
+                            aliases,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .unwrap();
+            }
+            Arc::new(storage)
+        });
+        let (server, connection) = Connection::memory();
+        let server = {
+            let storage = Arc::clone(&storage);
+            std::thread::spawn(move || {
+                zettelkasten_lsp::serve(
+                    &server,
+                    zettelkasten_lsp::Config { hover_lines: 2 },
+                    &SystemConfig {
+                        user_mode,
+                        ..SystemConfig::default()
+                    },
+                    storage,
+                )
+            })
+        };
+        Self {
+            connection,
+            server: Some(server),
+            next_id: 0,
+            notifications: Vec::new(),
+            storage,
+            _dir: dir,
+        }
+    }
+
+    fn send_request(&mut self, method: &str, params: Value) -> Response {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        self.connection
+            .sender
+            .send(Request::new(id.clone(), method.to_owned(), params).into())
+            .unwrap();
+        loop {
+            match self.connection.receiver.recv_timeout(TIMEOUT).unwrap() {
+                Message::Response(response) if response.id == id => return response,
+                Message::Notification(notification) => self.notifications.push(notification),
+                message => panic!("Unexpected message {message:?}"),
+            }
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let response = self.send_request(method, params);
+        assert!(response.error.is_none(), "{method} failed: {response:?}");
+        response.result.unwrap_or_default()
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.connection
+            .sender
+            .send(Notification::new(method.to_owned(), params).into())
+            .unwrap();
+    }
+
+    fn initialize(&mut self, options: Value) -> Response {
+        let response = self.send_request(
+            "initialize",
+            json!({ "capabilities": {}, "initializationOptions": options }),
+        );
+        if response.error.is_none() {
+            self.notify("initialized", json!({}));
+        }
+        response
+    }
+
+    fn open(&self, uri: &str, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": uri, "languageId": "markdown", "version": 1, "text": text }
+            }),
+        );
+    }
+
+    /// The diagnostics that were published last for `uri`
+    fn diagnostics(&mut self, uri: &str) -> Value {
+        // a request is answered after all notifications that were sent before it
+        self.request("workspace/symbol", json!({ "query": "" }));
+        self.notifications
+            .iter()
+            .rev()
+            .filter(|notification| notification.method == "textDocument/publishDiagnostics")
+            .find(|notification| notification.params["uri"] == uri)
+            .map(|notification| notification.params["diagnostics"].clone())
+            .expect("No diagnostics were published")
+    }
+
+    /// The messages that were shown so far
+    fn messages(&mut self) -> Vec<String> {
+        self.request("workspace/symbol", json!({ "query": "" }));
+        self.notifications
+            .iter()
+            .filter(|notification| notification.method == "window/showMessage")
+            .map(|notification| notification.params["message"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        self.server.take().unwrap().join().unwrap().unwrap();
+    }
+}
+
+fn position(uri: &str, line: u32, character: u32) -> Value {
+    json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+}
+
+#[test]
+fn test_login() {
+    let mut client = Client::start(UserMode::MultiUser);
+    let response = client.initialize(Value::Null);
+    assert!(response.error.unwrap().message.contains("username"));
+    assert!(client.server.take().unwrap().join().unwrap().is_err());
+
+    let mut client = Client::start(UserMode::MultiUser);
+    let response = client.initialize(json!({ "username": "test", "password": "wrong" }));
+    assert!(response.error.is_some());
+
+    let mut client = Client::start(UserMode::MultiUser);
+    let response = client.initialize(json!({ "username": "test", "password": "test" }));
+    let capabilities = &response.result.unwrap()["capabilities"];
+    assert_eq!(
+        capabilities["completionProvider"]["triggerCharacters"],
+        json!(["[", "("])
+    );
+    client.shutdown();
+
+    let mut client = Client::start(UserMode::SingleUserAutoLogin);
+    assert!(client.initialize(Value::Null).error.is_none());
+    client.shutdown();
+}
+
+#[test]
+fn test_navigation() {
+    let mut client = Client::start(UserMode::SingleUserAutoLogin);
+    client.initialize(Value::Null);
+    let home = "zettel:///home.md";
+    let text = client.request("zettelkasten/content", json!({ "uri": home }));
+    assert_eq!(text["text"], "# Home\nAbout [rust] and [missing]");
+    client.open(home, "# Home\nAbout [rust] and [missing]");
+    let diagnostics = client.diagnostics(home);
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["message"], "No zettel at `missing`");
+    assert_eq!(
+        diagnostics[0]["range"]["start"],
+        json!({ "line": 1, "character": 17 })
+    );
+
+    let definition = client.request("textDocument/definition", position(home, 1, 8));
+    assert_eq!(definition["uri"], "zettel:///rust.md");
+    let definition = client.request("textDocument/definition", position(home, 1, 20));
+    assert_eq!(definition, Value::Null);
+    let definition = client.request("textDocument/definition", position(home, 0, 2));
+    assert_eq!(definition, Value::Null);
+
+    let hover = client.request("textDocument/hover", position(home, 1, 8));
+    assert_eq!(hover["contents"]["value"], "# Rust\nA language");
+    let hover = client.request("textDocument/hover", position(home, 1, 20));
+    assert_eq!(hover["contents"]["value"], "No zettel at `missing`");
+    let hover = client.request(
+        "textDocument/hover",
+        position("zettel:///notes/c.md", 0, 20),
+    );
+    assert!(hover["contents"]["value"]
+        .as_str()
+        .unwrap()
+        .contains("- rust"));
+
+    // links to the path and the alias of `rust`
+    let mut references = position("zettel:///rust.md", 0, 0);
+    references["context"] = json!({ "includeDeclaration": false });
+    let references = client.request("textDocument/references", references);
+    let mut found: Vec<_> = references
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|location| {
+            (
+                location["uri"].as_str().unwrap(),
+                &location["range"]["start"],
+            )
+        })
+        .collect();
+    found.sort_by_key(|(uri, _)| *uri);
+    assert_eq!(
+        found,
+        [
+            ("zettel:///home.md", &json!({ "line": 1, "character": 6 })),
+            (
+                "zettel:///notes/c.md",
+                &json!({ "line": 0, "character": 7 })
+            ),
+        ]
+    );
+
+    let symbols = client.request("workspace/symbol", json!({ "query": "nc" }));
+    assert_eq!(symbols[0]["name"], "notes/c");
+    assert_eq!(symbols[0]["location"]["uri"], "zettel:///notes/c.md");
+    client.shutdown();
+}
+
+#[test]
+fn test_editing() {
+    let mut client = Client::start(UserMode::SingleUserAutoLogin);
+    client.initialize(Value::Null);
+    let uri = "zettel:///notes/new%20idea.md";
+    assert_eq!(
+        client.request("zettelkasten/content", json!({ "uri": uri }))["text"],
+        ""
+    );
+    client.open(uri, "See [ru");
+    let completion = client.request("textDocument/completion", position(uri, 0, 7));
+    let first = &completion[0];
+    assert_eq!(first["label"], "rust");
+    assert_eq!(first["textEdit"]["newText"], "rust]");
+    assert_eq!(
+        first["textEdit"]["range"],
+        json!({ "start": { "line": 0, "character": 5 }, "end": { "line": 0, "character": 7 } })
+    );
+    let completion = client.request("textDocument/completion", position(uri, 0, 3));
+    assert_eq!(completion, Value::Null);
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": "See [idea](notes/c) and [later]" }]
+        }),
+    );
+    let completion = client.request("textDocument/completion", position(uri, 0, 11));
+    assert_eq!(completion.as_array().unwrap().len(), 3);
+    assert_eq!(completion[0]["textEdit"]["newText"], "home");
+    assert_eq!(client.diagnostics(uri).as_array().unwrap().len(), 1);
+
+    // saving creates the zettel, which fixes links to it in other documents
+    let other = "zettel:///later.md";
+    client.open(other, "Back to [notes/new idea]");
+    assert_eq!(client.diagnostics(other).as_array().unwrap().len(), 1);
+    client.notify(
+        "textDocument/didSave",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    assert_eq!(client.diagnostics(other), json!([]));
+    let user = block_on(client.storage.login_single_user()).unwrap();
+    let zettel = block_on(client.storage.get_zettel_by_url(user.id, "notes/new idea"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(zettel.body, "See [idea](notes/c) and [later]");
+
+    let unknown = client.send_request("zettelkasten/unknown", Value::Null);
+    assert_eq!(
+        unknown.error.unwrap().code,
+        lsp_server::ErrorCode::MethodNotFound as i32
+    );
+    client.shutdown();
+}
+
+#[test]
+fn test_save_conflict() {
+    let mut client = Client::start(UserMode::SingleUserAutoLogin);
+    client.initialize(Value::Null);
+    let uri = "zettel:///rust.md";
+    client.open(uri, "# Rust\nA language\nSee [home](home)");
+    assert!(client.messages().is_empty());
+
+    // another session saves the zettel while it is open
+    let user = block_on(client.storage.login_single_user()).unwrap();
+    let storage = Arc::clone(&client.storage);
+    let stored = || {
+        block_on(storage.get_zettel_by_url(user.id, "rust"))
+            .unwrap()
+            .unwrap()
+    };
+    let mut rust = stored();
+    rust.body = "Theirs".to_owned();
+    block_on(storage.update_zettel(user.id, &mut rust)).unwrap();
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": "Mine" }]
+        }),
+    );
+    client.notify(
+        "textDocument/didSave",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    let messages = client.messages();
+    assert_eq!(messages.len(), 1);
+    assert!(
+        messages[0].contains("changed somewhere else"),
+        "{messages:?}"
+    );
+    assert_eq!(stored().body, "Theirs");
+
+    // after reopening, the other version is the one that is edited
+    client.notify(
+        "textDocument/didClose",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    client.open(uri, "Theirs and mine");
+    client.notify(
+        "textDocument/didSave",
+        json!({ "textDocument": { "uri": uri } }),
+    );
+    assert_eq!(client.messages().len(), 1);
+    assert_eq!(stored().body, "Theirs and mine");
+    client.shutdown();
+}
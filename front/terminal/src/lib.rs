@@ -10,16 +10,26 @@ use crossterm::{
     ExecutableCommand,
 };
 use snafu::ResultExt;
-use std::{io::Stdout, sync::Arc};
+use std::{
+    io::Stdout,
+    sync::{Arc, Mutex},
+};
 use tui::backend::CrosstermBackend;
-use zettelkasten_shared::{storage, Front};
+use zettelkasten_shared::{
+    futures::{stream::BoxStream, FutureExt, StreamExt},
+    storage, Front,
+};
 
 pub(crate) type Terminal = tui::Terminal<CrosstermBackend<Stdout>>;
 
 pub struct Tui<'a> {
     pub(crate) terminal: &'a mut Terminal,
     pub(crate) system_config: &'a mut storage::SystemConfig,
+    /// The config of the currently logged in user, or the default config if nobody is logged in.
+    pub(crate) user_config: storage::UserConfig,
     pub(crate) storage: &'a Arc<dyn storage::Storage>,
+    /// Wrapped in a mutex because [`Front`] needs to be `Sync`
+    pub(crate) storage_events: Mutex<BoxStream<'static, storage::StorageEvent>>,
     pub(crate) running: bool,
 }
 
@@ -33,32 +43,52 @@ impl Tui<'_> {
             Ok(user_count == 0)
         }
     }
+
+    /// Get all the storage events that were received since the last call, without blocking.
+    pub(crate) fn storage_events(&mut self) -> Vec<storage::StorageEvent> {
+        let mut events = Vec::new();
+        let storage_events = self
+            .storage_events
+            .get_mut()
+            .expect("Storage events mutex is poisoned");
+        while let Some(Some(event)) = storage_events.next().now_or_never() {
+            events.push(event);
+        }
+        events
+    }
 }
 
 impl Front for Tui<'_> {
     type Config = ();
 
     fn run(
-        _: Self::Config,
+        (): Self::Config,
         mut system_config: storage::SystemConfig,
         storage: Arc<dyn storage::Storage>,
     ) {
+        let storage_events = zettelkasten_shared::block_on(storage.subscribe())
+            .expect("Could not subscribe to storage events");
+
         enable_raw_mode().expect("Could not enable raw mode");
         let mut stdout = std::io::stdout();
         execute!(stdout, EnterAlternateScreen).expect("Could not enable alternate screen");
 
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend).expect("Could not instantiate the terminal");
-        let mut view = view::View::new(&system_config, &storage);
         let mut tui = Tui {
             terminal: &mut terminal,
             system_config: &mut system_config,
+            user_config: storage::UserConfig::default(),
             storage: &storage,
+            storage_events: Mutex::new(storage_events),
             running: true,
         };
+        let mut view = view::View::new(&mut tui);
 
         while tui.running {
-            let Err(e) = view.render(&mut tui) else { continue };
+            let Err(e) = view.render(&mut tui) else {
+                continue;
+            };
             let keycode = view::alert(tui.terminal, |f| {
                 f.title("Could not render page")
                     .text(e.to_string())
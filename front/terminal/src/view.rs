@@ -1,8 +1,16 @@
+mod aliases;
 mod config;
+mod export;
+mod form;
 mod list;
 mod login;
 mod register;
+mod report;
+mod saved_searches;
 mod search;
+mod sequence;
+mod template;
+mod tree;
 mod utils;
 mod zettel;
 
@@ -13,19 +21,26 @@ use tui::{
     text::{Spans, Text},
     widgets::{Block, Borders, Paragraph},
 };
-use zettelkasten_shared::storage;
+use zettelkasten_shared::{edit, storage};
 
 pub struct View {
     layers: Vec<ViewLayer>,
 }
 
 enum ViewLayer {
+    Aliases(aliases::Aliases),
     Config(config::Config),
+    Export(export::Export),
     Login(login::Login),
     Register(register::Register),
     Zettel(zettel::Zettel),
     Search(search::Search),
     List(list::List),
+    TemplatePicker(template::TemplatePicker),
+    Report(report::Report),
+    SavedSearches(saved_searches::SavedSearches),
+    Sequence(sequence::Sequence),
+    Tree(tree::Tree),
 }
 
 enum ViewReplace {
@@ -40,11 +55,21 @@ impl From<ViewLayer> for View {
     }
 }
 
+impl From<aliases::Aliases> for ViewLayer {
+    fn from(v: aliases::Aliases) -> Self {
+        ViewLayer::Aliases(v)
+    }
+}
 impl From<config::Config> for ViewLayer {
     fn from(v: config::Config) -> Self {
         ViewLayer::Config(v)
     }
 }
+impl From<export::Export> for ViewLayer {
+    fn from(v: export::Export) -> Self {
+        ViewLayer::Export(v)
+    }
+}
 impl From<login::Login> for ViewLayer {
     fn from(v: login::Login) -> Self {
         ViewLayer::Login(v)
@@ -72,17 +97,42 @@ impl From<list::List> for ViewLayer {
         ViewLayer::List(v)
     }
 }
+impl From<report::Report> for ViewLayer {
+    fn from(v: report::Report) -> Self {
+        ViewLayer::Report(v)
+    }
+}
+impl From<saved_searches::SavedSearches> for ViewLayer {
+    fn from(v: saved_searches::SavedSearches) -> Self {
+        ViewLayer::SavedSearches(v)
+    }
+}
+impl From<sequence::Sequence> for ViewLayer {
+    fn from(v: sequence::Sequence) -> Self {
+        ViewLayer::Sequence(v)
+    }
+}
+impl From<tree::Tree> for ViewLayer {
+    fn from(v: tree::Tree) -> Self {
+        ViewLayer::Tree(v)
+    }
+}
+impl From<template::TemplatePicker> for ViewLayer {
+    fn from(v: template::TemplatePicker) -> Self {
+        ViewLayer::TemplatePicker(v)
+    }
+}
 
 impl View {
-    pub fn new(system_config: &storage::SystemConfig, storage: &Arc<dyn storage::Storage>) -> Self {
-        let layer = if let Ok(0) = zettelkasten_shared::block_on(storage.user_count()) {
+    pub(crate) fn new(tui: &mut crate::Tui) -> Self {
+        let layer = if let Ok(0) = zettelkasten_shared::block_on(tui.storage.user_count()) {
             register::Register::default().into()
         } else {
-            match system_config.user_mode {
+            match tui.system_config.user_mode {
                 storage::UserMode::SingleUserAutoLogin => {
-                    match zettelkasten_shared::block_on(storage.login_single_user()) {
+                    match zettelkasten_shared::block_on(tui.storage.login_single_user()) {
                         // Successfully logged in
-                        Ok(user) => zettel::Zettel::new_with_user(storage, Arc::new(user)).into(),
+                        Ok(user) => zettel::Zettel::new_with_user(tui, Arc::new(user)).into(),
                         // Failed to log in, show the login view and the error
                         Err(source) => login::Login {
                             error: Some(login::LoginError::Storage { source }),
@@ -108,42 +158,9 @@ impl View {
         let layer: &mut ViewLayer = self.layers.last_mut().unwrap();
 
         let next: ViewReplace = match layer {
-            ViewLayer::Zettel(zettel) => match zettel.render(tui)? {
-                Some(zettel::Transition::Edit) => {
-                    if let Some(str) = utils::edit(&zettel.zettel, tui)? {
-                        zettel.zettel.body = str;
-                        zettelkasten_shared::block_on(
-                            tui.storage
-                                .update_zettel(zettel.user.id, &mut zettel.zettel),
-                        )
-                        .context(DatabaseSnafu)?;
-                    }
-                    return Ok(());
-                }
-                Some(zettel::Transition::Exit) => {
-                    tui.running = false;
-                    return Ok(());
-                }
-                Some(zettel::Transition::Logout) => Replace(login::Login::default().into()),
-                Some(zettel::Transition::OpenConfig) => Push(config::Config::new(tui).into()),
-                Some(zettel::Transition::Search) => {
-                    Push(search::Search::new(Arc::clone(&zettel.user)).into())
-                }
-                Some(zettel::Transition::ZettelList) => {
-                    Push(list::List::new(Arc::clone(&zettel.user), tui)?.into())
-                }
-                Some(zettel::Transition::NavigateTo(new_zettel)) => Replace(
-                    zettel::Zettel::new_with_zettel(
-                        Arc::clone(&zettel.user),
-                        new_zettel,
-                        tui.storage,
-                    )
-                    .into(),
-                ),
-                Some(zettel::Transition::SysPage(page)) => Push(page),
-                None => {
-                    return Ok(());
-                }
+            ViewLayer::Zettel(zettel) => match Self::render_zettel(zettel, tui)? {
+                Some(next) => next,
+                None => return Ok(()),
             },
             ViewLayer::Login(login) => match login.render(tui)? {
                 Some(login::Transition::Exit) => {
@@ -152,7 +169,7 @@ impl View {
                 }
                 Some(login::Transition::Register) => Replace(register::Register::default().into()),
                 Some(login::Transition::Login { user }) => {
-                    Replace(zettel::Zettel::new_with_user(tui.storage, Arc::new(user)).into())
+                    Replace(zettel::Zettel::new_with_user(tui, Arc::new(user)).into())
                 }
                 None => return Ok(()),
             },
@@ -162,7 +179,7 @@ impl View {
                     return Ok(());
                 }
                 Some(register::Transition::Registered { user }) => {
-                    Replace(zettel::Zettel::new_with_user(tui.storage, Arc::new(user)).into())
+                    Replace(zettel::Zettel::new_with_user(tui, Arc::new(user)).into())
                 }
                 Some(register::Transition::Login) => Replace(login::Login::default().into()),
                 None => return Ok(()),
@@ -171,6 +188,15 @@ impl View {
                 Some(config::Transition::Pop) => Pop,
                 None => return Ok(()),
             },
+            ViewLayer::Aliases(aliases) => match aliases.render(tui)? {
+                Some(aliases::Transition::Saved(zettel)) => Replace(zettel.into()),
+                Some(aliases::Transition::Pop) => Pop,
+                None => return Ok(()),
+            },
+            ViewLayer::Export(export) => match export.render(tui)? {
+                Some(export::Transition::Pop) => Pop,
+                None => return Ok(()),
+            },
             ViewLayer::Search(search) => match search.render(tui)? {
                 Some(search::Transition::NewZettel(zettel)) => Replace(zettel.into()),
                 Some(search::Transition::Pop) => Pop,
@@ -181,6 +207,35 @@ impl View {
                 Some(list::Transition::Pop) => Pop,
                 None => return Ok(()),
             },
+            ViewLayer::Report(report) => match report.render(tui)? {
+                Some(report::Transition::NewZettel(zettel)) => Replace(zettel.into()),
+                Some(report::Transition::Pop) => Pop,
+                None => return Ok(()),
+            },
+            ViewLayer::SavedSearches(saved) => match saved.render(tui)? {
+                Some(saved_searches::Transition::Results(report)) => Push(report.into()),
+                Some(saved_searches::Transition::Pop) => Pop,
+                None => return Ok(()),
+            },
+            ViewLayer::Sequence(sequence) => match sequence.render(tui)? {
+                Some(sequence::Transition::NewZettel(zettel)) => Replace(zettel.into()),
+                Some(sequence::Transition::Pop) => Pop,
+                None => return Ok(()),
+            },
+            ViewLayer::Tree(tree) => match tree.render(tui)? {
+                Some(tree::Transition::NewZettel(zettel)) => Replace(zettel.into()),
+                Some(tree::Transition::Create(path)) => {
+                    let user = Arc::clone(&tree.user);
+                    Self::create_zettel(tui, user, path)?
+                }
+                Some(tree::Transition::Pop) => Pop,
+                None => return Ok(()),
+            },
+            ViewLayer::TemplatePicker(picker) => match picker.render(tui)? {
+                Some(template::Transition::NewZettel(zettel)) => Replace(zettel.into()),
+                Some(template::Transition::Pop) => Pop,
+                None => return Ok(()),
+            },
         };
 
         match next {
@@ -198,6 +253,133 @@ impl View {
 
         Ok(())
     }
+
+    /// Render a [`zettel::Zettel`] layer, returns `None` if the layers stay the same
+    fn render_zettel(
+        zettel: &mut zettel::Zettel,
+        tui: &mut crate::Tui,
+    ) -> Result<Option<ViewReplace>> {
+        use ViewReplace::{Push, Replace};
+
+        let next = match zettel.render(tui)? {
+            Some(zettel::Transition::Edit) => {
+                if let Some(body) = utils::edit(&zettel.zettel, tui)? {
+                    Self::save_edit(zettel, tui, &body)?;
+                }
+                return Ok(None);
+            }
+            Some(zettel::Transition::Exit) => {
+                tui.running = false;
+                return Ok(None);
+            }
+            Some(zettel::Transition::Logout) => {
+                tui.user_config = storage::UserConfig::default();
+                Replace(login::Login::default().into())
+            }
+            Some(zettel::Transition::OpenConfig) => {
+                Push(config::Config::new(tui, Arc::clone(&zettel.user)).into())
+            }
+            Some(zettel::Transition::Search) => Push(
+                search::Search::new(Arc::clone(&zettel.user), tui.user_config.search_mode).into(),
+            ),
+            Some(zettel::Transition::ZettelList) => {
+                Push(list::List::new(Arc::clone(&zettel.user), tui)?.into())
+            }
+            Some(zettel::Transition::EditAliases) => {
+                Push(aliases::Aliases::new(Arc::clone(&zettel.user), zettel.zettel.clone()).into())
+            }
+            Some(zettel::Transition::Tree) => {
+                Push(tree::Tree::new(tui, Arc::clone(&zettel.user), &zettel.zettel.path)?.into())
+            }
+            Some(zettel::Transition::Sequence) => {
+                Push(sequence::Sequence::new(tui, Arc::clone(&zettel.user), &zettel.zettel)?.into())
+            }
+            Some(zettel::Transition::NavigateTo(new_zettel)) => Replace(
+                zettel::Zettel::new_with_zettel(Arc::clone(&zettel.user), new_zettel, tui.storage)
+                    .into(),
+            ),
+            Some(zettel::Transition::Create(path)) => {
+                Self::create_zettel(tui, Arc::clone(&zettel.user), path)?
+            }
+            Some(zettel::Transition::Reload(new_zettel)) => {
+                zettel.zettel = new_zettel;
+                return Ok(None);
+            }
+            Some(zettel::Transition::SysPage(page)) => Push(page),
+            None => return Ok(None),
+        };
+        Ok(Some(next))
+    }
+
+    /// Save the edited `body`. If the zettel was changed or deleted while the editor was open, ask whether to
+    /// overwrite or recreate it with `body`, or to discard `body`.
+    fn save_edit(zettel: &mut zettel::Zettel, tui: &mut crate::Tui, body: &str) -> Result<()> {
+        let user = zettel.user.id;
+        let mut overwrite = false;
+        loop {
+            let saved = zettelkasten_shared::block_on(edit::save_edit(
+                tui.storage.as_ref(),
+                user,
+                &mut zettel.zettel,
+                body,
+                overwrite,
+            ))
+            .context(DatabaseSnafu)?;
+            match saved {
+                edit::Saved::Saved => return Ok(()),
+                edit::Saved::Changed => {
+                    let keycode = alert(tui.terminal, |f| {
+                        f.title("Zettel changed while editing")
+                            .text("The zettel was saved somewhere else while you were editing it.")
+                            .action(KeyCode::Char('o'), "Overwrite its body with your version")
+                            .action(KeyCode::Enter, "Discard your version")
+                    })?;
+                    if keycode != KeyCode::Char('o') {
+                        let stored = zettelkasten_shared::block_on(
+                            tui.storage.get_zettel(user, zettel.zettel.id),
+                        );
+                        match stored {
+                            Ok(stored) => zettel.zettel = stored,
+                            // deleted in the meantime, which the storage events will show
+                            Err(storage::Error::ZettelNotFound { .. }) => {}
+                            Err(source) => return Err(Error::Database { source }),
+                        }
+                        return Ok(());
+                    }
+                    overwrite = true;
+                }
+                edit::Saved::Deleted => {
+                    let keycode = alert(tui.terminal, |f| {
+                        f.title("Zettel deleted while editing")
+                            .text(
+                                "The zettel was deleted somewhere else while you were editing it.",
+                            )
+                            .action(KeyCode::Char('r'), "Recreate it with your version")
+                            .action(KeyCode::Enter, "Discard your version")
+                    })?;
+                    // like a deleted zettel that is shown, editing it again creates it
+                    zettel.zettel.id = 0;
+                    if keycode != KeyCode::Char('r') {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create a new zettel at `path`, letting the user pick a template if there are any
+    fn create_zettel(
+        tui: &mut crate::Tui,
+        user: Arc<storage::User>,
+        path: String,
+    ) -> Result<ViewReplace> {
+        let picker = template::TemplatePicker::new(tui, user, path)?;
+        Ok(if picker.has_templates() {
+            ViewReplace::Push(picker.into())
+        } else {
+            ViewReplace::Replace(picker.create(tui)?.into())
+        })
+    }
 }
 
 pub type Result<T = ()> = std::result::Result<T, Error>;
@@ -221,10 +403,6 @@ pub enum Error {
     Database {
         source: storage::Error,
     },
-    #[snafu(display("Zettel ID {id} not found"))]
-    ZettelIdNotFound {
-        id: i64,
-    },
     Io {
         source: std::io::Error,
     },
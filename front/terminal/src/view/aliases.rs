@@ -0,0 +1,110 @@
+use crossterm::event::{Event, KeyCode};
+use snafu::ResultExt;
+use std::sync::Arc;
+use tui::{
+    layout::Rect,
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+use zettelkasten_shared::storage;
+
+/// Edit the aliases of a zettel, the other paths under which it can be found
+pub struct Aliases {
+    user: Arc<storage::User>,
+    zettel: storage::Zettel,
+    /// The aliases, separated by commas
+    input: String,
+}
+
+impl Aliases {
+    pub fn new(user: Arc<storage::User>, zettel: storage::Zettel) -> Self {
+        let input = zettel.aliases.join(", ");
+        Self {
+            user,
+            zettel,
+            input,
+        }
+    }
+
+    pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        loop {
+            self.draw(tui)?;
+            let event = crossterm::event::read().context(super::EventSnafu)?;
+            let Event::Key(key_event) = event else {
+                continue;
+            };
+            match key_event.code {
+                KeyCode::Char(c) => self.input.push(c),
+                KeyCode::Backspace => drop(self.input.pop()),
+                KeyCode::Esc => return Ok(Some(Transition::Pop)),
+                KeyCode::Enter => {
+                    self.zettel.aliases = self.input.split(',').map(str::to_owned).collect();
+                    let result = zettelkasten_shared::block_on(
+                        tui.storage.update_zettel(self.user.id, &mut self.zettel),
+                    );
+                    match result {
+                        Ok(()) => {
+                            return Ok(Some(Transition::Saved(
+                                super::zettel::Zettel::new_with_zettel(
+                                    Arc::clone(&self.user),
+                                    self.zettel.clone(),
+                                    tui.storage,
+                                ),
+                            )))
+                        }
+                        Err(storage::Error::PathInUse { path }) => {
+                            super::alert(tui.terminal, |f| {
+                                f.title("Could not save aliases")
+                                    .text(format!("{path:?} is already used by another zettel"))
+                                    .action(KeyCode::Enter, "Continue")
+                            })?;
+                        }
+                        Err(source) => return Err(super::Error::Database { source }),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, tui: &mut crate::Tui) -> super::Result<()> {
+        tui.terminal
+            .draw(|f| {
+                let size = f.size();
+                let body = Paragraph::new(vec![
+                    Spans::from(format!(
+                        "Other paths under which {} can be found, separated by commas:",
+                        self.zettel.path
+                    )),
+                    Spans::default(),
+                    Spans(vec![Span::raw(self.input.as_str()), Span::raw("_")]),
+                ])
+                .block(Block::default().borders(Borders::ALL).title("Aliases"));
+                let actions = Paragraph::new("Enter: save, Esc: go back");
+
+                f.render_widget(
+                    body,
+                    Rect {
+                        height: size.height - 1,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    actions,
+                    Rect {
+                        height: 1,
+                        y: size.height - 1,
+                        ..size
+                    },
+                );
+            })
+            .context(super::RenderFrameSnafu)?;
+        Ok(())
+    }
+}
+
+pub enum Transition {
+    /// The aliases were saved, show the zettel with its new aliases
+    Saved(super::zettel::Zettel),
+    Pop,
+}
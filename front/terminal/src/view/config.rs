@@ -1,66 +1,132 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
 
 use crossterm::event::{Event, KeyCode};
 use snafu::ResultExt;
 use tui::{
     layout::Rect,
-    style::{Color, Style},
     text::{Span, Spans, Text},
     widgets::{Block, Borders, Paragraph},
 };
 use zettelkasten_shared::storage;
 
+use super::form::{Field, FieldTy, Form};
+
+/// The section with settings shared by every user of this zettelkasten
+const SYSTEM: &str = "System";
+/// The section with settings of the user that is currently logged in
+const PERSONAL: &str = "Personal";
+
 pub struct Config {
+    user: Arc<storage::User>,
     form: Form,
 }
 
 impl Config {
-    pub(crate) fn new(tui: &mut crate::Tui) -> Self {
-        let mut form = Form {
-            active: 0,
-            fields: Vec::new(),
-        };
-        form.fields.push(Field {
-            label: "User mode",
-            ty: FieldTy::MultipleOption {
-                options: vec![
-                    "single user with auto login",
-                    "single user with manual login",
-                    "multiple users",
-                ],
-                selected: match tui.system_config.user_mode {
-                    storage::UserMode::SingleUserAutoLogin => 0,
-                    storage::UserMode::SingleUserManualLogin => 1,
-                    storage::UserMode::MultiUser => 2,
+    /// Only admins can change the [`SYSTEM`] section, as it applies to every user
+    pub(crate) fn new(tui: &mut crate::Tui, user: Arc<storage::User>) -> Self {
+        let fields = vec![
+            Field {
+                section: SYSTEM,
+                label: "User mode",
+                ty: FieldTy::MultipleOption {
+                    options: vec![
+                        "single user with auto login",
+                        "single user with manual login",
+                        "multiple users",
+                    ],
+                    selected: match tui.system_config.user_mode {
+                        storage::UserMode::SingleUserAutoLogin => 0,
+                        storage::UserMode::SingleUserManualLogin => 1,
+                        storage::UserMode::MultiUser => 2,
+                    },
                 },
+                validator: None,
+                read_only: !user.is_admin,
             },
-            validator: None,
-        });
-        form.fields.push(Field {
-            label: "Terminal editor (path)",
-            ty: FieldTy::Text {
-                value: if let Some(path) = &tui.system_config.terminal_editor {
-                    path.display().to_string()
-                } else {
-                    String::new()
+            Field {
+                section: SYSTEM,
+                label: "Terminal editor (path)",
+                ty: FieldTy::Text {
+                    value: path_to_string(tui.system_config.terminal_editor.as_ref()),
                 },
+                validator: Some(validate_editor_path),
+                read_only: !user.is_admin,
             },
-            validator: Some(validate_editor_path),
-        });
-        Self { form }
+            Field {
+                section: PERSONAL,
+                label: "Editor (path)",
+                ty: FieldTy::Text {
+                    value: path_to_string(tui.user_config.editor.as_ref()),
+                },
+                validator: Some(validate_editor_path),
+                read_only: false,
+            },
+            Field {
+                section: PERSONAL,
+                label: "Theme",
+                ty: FieldTy::MultipleOption {
+                    options: vec!["dark", "light"],
+                    selected: match tui.user_config.theme {
+                        storage::Theme::Dark => 0,
+                        storage::Theme::Light => 1,
+                    },
+                },
+                validator: None,
+                read_only: false,
+            },
+            Field {
+                section: PERSONAL,
+                label: "Landing zettel (path)",
+                ty: FieldTy::Text {
+                    value: tui.user_config.landing_zettel.clone().unwrap_or_default(),
+                },
+                validator: None,
+                read_only: false,
+            },
+            Field {
+                section: PERSONAL,
+                label: "Search mode",
+                ty: FieldTy::MultipleOption {
+                    options: storage::SearchMode::ALL
+                        .iter()
+                        .map(|mode| mode.name())
+                        .collect(),
+                    selected: storage::SearchMode::ALL
+                        .iter()
+                        .position(|&mode| mode == tui.user_config.search_mode)
+                        .unwrap_or_default(),
+                },
+                validator: None,
+                read_only: false,
+            },
+            Field {
+                section: PERSONAL,
+                label: "Default templates (prefix=template, ...)",
+                ty: FieldTy::Text {
+                    value: templates_to_string(&tui.user_config.default_templates),
+                },
+                validator: Some(validate_templates),
+                read_only: false,
+            },
+            Field {
+                section: PERSONAL,
+                label: "Journal template",
+                ty: FieldTy::Text {
+                    value: tui.user_config.journal_template.clone().unwrap_or_default(),
+                },
+                validator: None,
+                read_only: false,
+            },
+        ];
+        Self {
+            user,
+            form: Form::new(fields),
+        }
     }
 
     fn save(&self, tui: &mut crate::Tui) -> bool {
-        let terminal_editor = self
-            .form
-            .get_value_by_label("Terminal editor (path)")
-            .to_string();
-        let config = storage::SystemConfig {
-            terminal_editor: if terminal_editor.is_empty() {
-                None
-            } else {
-                Some(PathBuf::from(terminal_editor))
-            },
+        let system_config = storage::SystemConfig {
+            terminal_editor: string_to_path(self.form.get_value_by_label("Terminal editor (path)")),
             user_mode: match self.form.get_idx_by_label("User mode") {
                 0 => storage::UserMode::SingleUserAutoLogin,
                 1 => storage::UserMode::SingleUserManualLogin,
@@ -68,7 +134,49 @@ impl Config {
                 _ => unreachable!(),
             },
         };
-        if let Err(e) = zettelkasten_shared::block_on(tui.storage.update_config(&config)) {
+        let landing_zettel = self.form.get_value_by_label("Landing zettel (path)").trim();
+        let journal_template = self.form.get_value_by_label("Journal template").trim();
+        let user_config = storage::UserConfig {
+            editor: string_to_path(self.form.get_value_by_label("Editor (path)")),
+            theme: match self.form.get_idx_by_label("Theme") {
+                0 => storage::Theme::Dark,
+                1 => storage::Theme::Light,
+                _ => unreachable!(),
+            },
+            landing_zettel: if landing_zettel.is_empty() {
+                None
+            } else {
+                Some(landing_zettel.to_string())
+            },
+            search_mode: storage::SearchMode::ALL[self.form.get_idx_by_label("Search mode")],
+            default_templates: string_to_templates(
+                self.form
+                    .get_value_by_label("Default templates (prefix=template, ...)"),
+            ),
+            journal_template: if journal_template.is_empty() {
+                None
+            } else {
+                Some(journal_template.to_string())
+            },
+        };
+        let mut operations = vec![storage::BatchOperation::UpdateUserConfig(
+            user_config.clone(),
+        )];
+        if self.user.is_admin {
+            operations.push(storage::BatchOperation::UpdateConfig(system_config.clone()));
+        }
+        let result =
+            zettelkasten_shared::block_on(tui.storage.batch(self.user.id, &mut operations))
+                .and_then(|results| {
+                    results
+                        .into_iter()
+                        .find_map(|result| match result {
+                            storage::BatchResult::Failed(e) => Some(e),
+                            _ => None,
+                        })
+                        .map_or(Ok(()), Err)
+                });
+        if let Err(e) = result {
             drop(super::alert(tui.terminal, |f| {
                 f.title("Could not save config")
                     .text(e.to_string())
@@ -76,26 +184,16 @@ impl Config {
             }));
             return false;
         }
-        *tui.system_config = config;
+        if self.user.is_admin {
+            *tui.system_config = system_config;
+        }
+        tui.user_config = user_config;
         true
     }
 
     pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
         loop {
-            let mut lines = Vec::new();
-            for (idx, field) in self.form.fields.iter().enumerate() {
-                let mut spans = Vec::new();
-                spans.push(Span::raw(if idx == self.form.active { "> " } else { "  " }));
-                spans.push(Span::raw(field.label));
-                spans.push(Span::raw(" = "));
-                field.ty.render(&mut spans, idx == self.form.active);
-
-                if let Some(msg) = field.validator.as_ref().and_then(|v| v(field.ty.value())) {
-                    spans.push(Span::raw("  "));
-                    spans.push(Span::styled(msg, Style::default().fg(Color::Red)));
-                }
-                lines.push(Spans(spans));
-            }
+            let lines = self.form.lines();
             let p = Paragraph::new(Text { lines })
                 .block(Block::default().borders(Borders::ALL).title("Config"));
 
@@ -129,35 +227,12 @@ impl Config {
 
             let event = crossterm::event::read().context(super::EventSnafu)?;
             if let Event::Key(key_event) = event {
+                if self.form.handle_key(key_event.code) {
+                    continue;
+                }
                 match key_event.code {
-                    KeyCode::Up => {
-                        if self.form.active > 0 {
-                            self.form.active -= 1;
-                        }
-                    }
-                    KeyCode::Down => {
-                        if self.form.active + 1 < self.form.fields.len() {
-                            self.form.active += 1;
-                        }
-                    }
-                    KeyCode::Left => {
-                        self.form.fields[self.form.active].ty.left();
-                    }
-                    KeyCode::Right => {
-                        self.form.fields[self.form.active].ty.right();
-                    }
-                    KeyCode::Char(c) => {
-                        self.form.fields[self.form.active].ty.input(c);
-                    }
-                    KeyCode::Backspace => {
-                        self.form.fields[self.form.active].ty.backspace();
-                    }
                     KeyCode::Esc => return Ok(Some(Transition::Pop)),
-                    KeyCode::Enter => {
-                        if self.save(tui) {
-                            return Ok(Some(Transition::Pop));
-                        }
-                    }
+                    KeyCode::Enter if self.save(tui) => return Ok(Some(Transition::Pop)),
                     _ => {}
                 }
             }
@@ -169,84 +244,49 @@ pub enum Transition {
     Pop,
 }
 
-struct Form {
-    active: usize,
-    fields: Vec<Field>,
+fn path_to_string(path: Option<&PathBuf>) -> String {
+    path.map(|path| path.display().to_string())
+        .unwrap_or_default()
 }
 
-struct Field {
-    label: &'static str,
-    ty: FieldTy,
-    validator: Option<fn(&str) -> Option<&'static str>>,
+fn string_to_path(value: &str) -> Option<PathBuf> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(value))
+    }
 }
 
-enum FieldTy {
-    MultipleOption {
-        options: Vec<&'static str>,
-        selected: usize,
-    },
-    Text {
-        value: String,
-    },
+fn templates_to_string(templates: &BTreeMap<String, String>) -> String {
+    templates
+        .iter()
+        .map(|(prefix, template)| format!("{prefix}={template}"))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-impl FieldTy {
-    fn render(&self, spans: &mut Vec<Span>, active: bool) {
-        match self {
-            Self::MultipleOption { options, selected } => {
-                spans.push(Span::styled(
-                    if active && *selected > 0 { "< " } else { "  " },
-                    Style::default().fg(Color::Yellow),
-                ));
-                spans.push(Span::raw(options[*selected]));
-                spans.push(Span::styled(
-                    if active && selected + 1 < options.len() {
-                        " >"
-                    } else {
-                        "  "
-                    },
-                    Style::default().fg(Color::Yellow),
-                ));
-            }
-            Self::Text { value } => {
-                spans.push(Span::raw(value.clone()));
-                if active {
-                    spans.push(Span::raw("_"));
-                }
-            }
-        }
-    }
-
-    fn input(&mut self, key: char) {
-        if let Self::Text { value } = self {
-            value.push(key);
-        }
-    }
-    fn backspace(&mut self) {
-        if let Self::Text { value } = self {
-            value.pop();
-        }
-    }
-    fn left(&mut self) {
-        if let Self::MultipleOption { selected, .. } = self {
-            if *selected > 0 {
-                *selected -= 1;
-            }
-        }
-    }
-    fn right(&mut self) {
-        if let Self::MultipleOption { options, selected } = self {
-            if *selected + 1 < options.len() {
-                *selected += 1;
-            }
-        }
-    }
+fn string_to_templates(value: &str) -> BTreeMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(prefix, template)| (prefix.trim().to_string(), template.trim().to_string()))
+        .filter(|(prefix, template)| !prefix.is_empty() && !template.is_empty())
+        .collect()
+}
 
-    fn value(&self) -> &str {
-        match self {
-            FieldTy::MultipleOption { options, selected } => options[*selected],
-            FieldTy::Text { value } => value,
-        }
+fn validate_templates(value: &str) -> Option<&'static str> {
+    let invalid = value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .any(|entry| {
+            entry.split_once('=').is_none_or(|(prefix, template)| {
+                prefix.trim().is_empty() || template.trim().is_empty()
+            })
+        });
+    if invalid {
+        Some("Expected entries like `literature/=literature`")
+    } else {
+        None
     }
 }
 
@@ -263,25 +303,3 @@ fn validate_editor_path(path: &str) -> Option<&'static str> {
         Some("Executable not found")
     }
 }
-
-impl Form {
-    fn get_value_by_label(&self, arg: &str) -> &str {
-        for field in &self.fields {
-            if field.label == arg {
-                return field.ty.value();
-            }
-        }
-        panic!("Form field with label {arg:?} not found")
-    }
-    fn get_idx_by_label(&self, arg: &str) -> usize {
-        for field in &self.fields {
-            if field.label == arg {
-                if let FieldTy::MultipleOption { selected, .. } = &field.ty {
-                    return *selected;
-                }
-                panic!("Field {arg:?} is not a multiple choice");
-            }
-        }
-        panic!("Form field with label {arg:?} not found")
-    }
-}
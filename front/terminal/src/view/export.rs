@@ -0,0 +1,182 @@
+use crossterm::event::{Event, KeyCode};
+use snafu::ResultExt;
+use std::sync::Arc;
+use tui::{
+    layout::Rect,
+    text::Text,
+    widgets::{Block, Borders, Paragraph},
+};
+use zettelkasten_shared::{
+    export::{ExportGraph, ExportOpts, Format},
+    links::LinkGraph,
+    storage,
+};
+
+use super::form::{Field, FieldTy, Form};
+
+const SECTION: &str = "Export the link graph";
+
+/// The `sys:export` page, which writes the link graph to a file
+pub struct Export {
+    user: Arc<storage::User>,
+    form: Form,
+}
+
+impl Export {
+    pub fn new(user: Arc<storage::User>) -> Self {
+        let fields = vec![
+            Field {
+                section: SECTION,
+                label: "Format",
+                ty: FieldTy::MultipleOption {
+                    options: Format::ALL.iter().map(|format| format.name()).collect(),
+                    selected: 0,
+                },
+                validator: None,
+                read_only: false,
+            },
+            Field {
+                section: SECTION,
+                label: "Path prefix",
+                ty: FieldTy::Text {
+                    value: String::new(),
+                },
+                validator: None,
+                read_only: false,
+            },
+            Field {
+                section: SECTION,
+                label: "Around zettel (path)",
+                ty: FieldTy::Text {
+                    value: String::new(),
+                },
+                validator: None,
+                read_only: false,
+            },
+            Field {
+                section: SECTION,
+                label: "Hops",
+                ty: FieldTy::Text {
+                    value: "2".to_owned(),
+                },
+                validator: Some(validate_hops),
+                read_only: false,
+            },
+            Field {
+                section: SECTION,
+                label: "Output file",
+                ty: FieldTy::Text {
+                    value: String::new(),
+                },
+                validator: None,
+                read_only: false,
+            },
+        ];
+        Self {
+            user,
+            form: Form::new(fields),
+        }
+    }
+
+    /// Write the export, returns a message for the user
+    fn export(&self, tui: &crate::Tui) -> super::Result<String> {
+        let format = Format::ALL[self.form.get_idx_by_label("Format")];
+        let prefix = self.form.get_value_by_label("Path prefix").trim();
+        let around = self.form.get_value_by_label("Around zettel (path)").trim();
+        let Ok(hops) = self.form.get_value_by_label("Hops").trim().parse() else {
+            return Ok("Hops needs to be a number".to_owned());
+        };
+        let output = match self.form.get_value_by_label("Output file").trim() {
+            "" => format!("zettelkasten.{}", format.extension()),
+            output => output.to_owned(),
+        };
+
+        let opts = ExportOpts {
+            prefix: Some(prefix).filter(|prefix| !prefix.is_empty()),
+            around: Some(around)
+                .filter(|around| !around.is_empty())
+                .map(|around| (around, hops)),
+        };
+        let graph =
+            zettelkasten_shared::block_on(LinkGraph::load(tui.storage.as_ref(), self.user.id))
+                .context(super::DatabaseSnafu)?;
+        let Some(graph) = ExportGraph::new(&graph, &opts) else {
+            return Ok(format!("Zettel {around:?} not found"));
+        };
+        if let Err(e) = std::fs::write(&output, graph.render(format)) {
+            return Ok(format!("Could not write {output}: {e}"));
+        }
+        Ok(format!(
+            "Exported {} zettels and {} links to {output}",
+            graph.nodes.len(),
+            graph.edges.len()
+        ))
+    }
+
+    pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        loop {
+            let body = Paragraph::new(Text {
+                lines: self.form.lines(),
+            })
+            .block(Block::default().borders(Borders::ALL).title("Export"));
+            let action = Paragraph::new(
+                "Up/Down: select field, Left/Right: change option, Enter: Export, Esc: go back",
+            );
+            let size = tui.terminal.size().context(super::TerminalSizeSnafu)?;
+
+            tui.terminal
+                .draw(|f| {
+                    f.render_widget(
+                        body,
+                        Rect {
+                            height: size.height - 1,
+                            ..size
+                        },
+                    );
+                    f.render_widget(
+                        action,
+                        Rect {
+                            y: size.height - 1,
+                            height: 1,
+                            ..size
+                        },
+                    );
+                })
+                .context(super::RenderFrameSnafu)?;
+
+            let event = crossterm::event::read().context(super::EventSnafu)?;
+            if let Event::Key(key_event) = event {
+                if self.form.handle_key(key_event.code) {
+                    continue;
+                }
+                match key_event.code {
+                    KeyCode::Esc => return Ok(Some(Transition::Pop)),
+                    KeyCode::Enter => {
+                        let message = match self.export(tui) {
+                            Ok(message) => message,
+                            Err(e) => e.to_string(),
+                        };
+                        super::alert(tui.terminal, |f| {
+                            f.title("Export")
+                                .text(message.clone())
+                                .action(KeyCode::Enter, "Continue")
+                        })?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+pub enum Transition {
+    Pop,
+}
+
+fn validate_hops(hops: &str) -> Option<&'static str> {
+    if hops.trim().parse::<usize>().is_ok() {
+        None
+    } else {
+        Some("Not a number")
+    }
+}
@@ -0,0 +1,184 @@
+use crossterm::event::KeyCode;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// A list of input fields, grouped in sections
+pub struct Form {
+    pub active: usize,
+    pub fields: Vec<Field>,
+}
+
+pub struct Field {
+    /// The title of the section this field is shown in
+    pub section: &'static str,
+    pub label: &'static str,
+    pub ty: FieldTy,
+    pub validator: Option<fn(&str) -> Option<&'static str>>,
+    /// Shown, but can't be changed
+    pub read_only: bool,
+}
+
+pub enum FieldTy {
+    MultipleOption {
+        options: Vec<&'static str>,
+        selected: usize,
+    },
+    Text {
+        value: String,
+    },
+}
+
+impl Form {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self { active: 0, fields }
+    }
+
+    /// The rendered fields, with a bold header above every section
+    pub fn lines(&self) -> Vec<Spans<'_>> {
+        let mut lines = Vec::new();
+        let mut section = None;
+        for (idx, field) in self.fields.iter().enumerate() {
+            if section != Some(field.section) {
+                if section.is_some() {
+                    lines.push(Spans::default());
+                }
+                lines.push(Spans(vec![Span::styled(
+                    field.section,
+                    Style::default().add_modifier(Modifier::BOLD),
+                )]));
+                section = Some(field.section);
+            }
+            let mut spans = Vec::new();
+            spans.push(Span::raw(if idx == self.active { "> " } else { "  " }));
+            spans.push(Span::raw(field.label));
+            spans.push(Span::raw(" = "));
+            if field.read_only {
+                spans.push(Span::styled(
+                    field.ty.value(),
+                    Style::default().fg(Color::DarkGray),
+                ));
+                lines.push(Spans(spans));
+                continue;
+            }
+            field.ty.render(&mut spans, idx == self.active);
+
+            if let Some(msg) = field.validator.as_ref().and_then(|v| v(field.ty.value())) {
+                spans.push(Span::raw("  "));
+                spans.push(Span::styled(msg, Style::default().fg(Color::Red)));
+            }
+            lines.push(Spans(spans));
+        }
+        lines
+    }
+
+    /// Move between fields and edit the active field. Returns `false` if the key is not used by the form.
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Up if self.active > 0 => {
+                self.active -= 1;
+            }
+            KeyCode::Down if self.active + 1 < self.fields.len() => {
+                self.active += 1;
+            }
+            KeyCode::Left | KeyCode::Right | KeyCode::Char(_) | KeyCode::Backspace
+                if self.fields[self.active].read_only => {}
+            KeyCode::Left => {
+                self.fields[self.active].ty.left();
+            }
+            KeyCode::Right => {
+                self.fields[self.active].ty.right();
+            }
+            KeyCode::Char(c) => {
+                self.fields[self.active].ty.input(c);
+            }
+            KeyCode::Backspace => {
+                self.fields[self.active].ty.backspace();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn get_value_by_label(&self, arg: &str) -> &str {
+        for field in &self.fields {
+            if field.label == arg {
+                return field.ty.value();
+            }
+        }
+        panic!("Form field with label {arg:?} not found")
+    }
+
+    pub fn get_idx_by_label(&self, arg: &str) -> usize {
+        for field in &self.fields {
+            if field.label == arg {
+                if let FieldTy::MultipleOption { selected, .. } = &field.ty {
+                    return *selected;
+                }
+                panic!("Field {arg:?} is not a multiple choice");
+            }
+        }
+        panic!("Form field with label {arg:?} not found")
+    }
+}
+
+impl FieldTy {
+    fn render(&self, spans: &mut Vec<Span>, active: bool) {
+        match self {
+            Self::MultipleOption { options, selected } => {
+                spans.push(Span::styled(
+                    if active && *selected > 0 { "< " } else { "  " },
+                    Style::default().fg(Color::Yellow),
+                ));
+                spans.push(Span::raw(options[*selected]));
+                spans.push(Span::styled(
+                    if active && selected + 1 < options.len() {
+                        " >"
+                    } else {
+                        "  "
+                    },
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            Self::Text { value } => {
+                spans.push(Span::raw(value.clone()));
+                if active {
+                    spans.push(Span::raw("_"));
+                }
+            }
+        }
+    }
+
+    fn input(&mut self, key: char) {
+        if let Self::Text { value } = self {
+            value.push(key);
+        }
+    }
+    fn backspace(&mut self) {
+        if let Self::Text { value } = self {
+            value.pop();
+        }
+    }
+    fn left(&mut self) {
+        if let Self::MultipleOption { selected, .. } = self {
+            if *selected > 0 {
+                *selected -= 1;
+            }
+        }
+    }
+    fn right(&mut self) {
+        if let Self::MultipleOption { options, selected } = self {
+            if *selected + 1 < options.len() {
+                *selected += 1;
+            }
+        }
+    }
+
+    fn value(&self) -> &str {
+        match self {
+            FieldTy::MultipleOption { options, selected } => options[*selected],
+            FieldTy::Text { value } => value,
+        }
+    }
+}
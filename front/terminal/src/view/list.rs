@@ -1,3 +1,4 @@
+use super::utils::RenderStyle;
 use crossterm::event::{Event, KeyCode};
 use snafu::ResultExt;
 use std::sync::Arc;
@@ -6,7 +7,7 @@ use tui::{
     text::{Span, Spans},
     widgets::{Block, Borders, Paragraph},
 };
-use zettelkasten_shared::storage;
+use zettelkasten_shared::{fuzzy, storage};
 
 pub struct List {
     user: Arc<storage::User>,
@@ -35,16 +36,14 @@ impl List {
 
     pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
         loop {
-            let links: Vec<&storage::ZettelHeader> = if self.search.trim().is_empty() {
-                self.links.iter().collect()
+            // the matched positions in the path of every link, best matches first
+            let links: Vec<(&storage::ZettelHeader, Vec<usize>)> = if self.search.trim().is_empty()
+            {
+                self.links.iter().map(|l| (l, Vec::new())).collect()
             } else {
-                self.links
-                    .iter()
-                    .filter(|l| {
-                        l.path
-                            .to_ascii_lowercase()
-                            .contains(&self.search.to_ascii_lowercase())
-                    })
+                fuzzy::filter(&self.search, &self.links, |l| l.path.as_str())
+                    .into_iter()
+                    .map(|(l, found)| (l, found.positions))
                     .collect()
             };
             if links.is_empty() {
@@ -62,7 +61,9 @@ impl List {
                     }
                     KeyCode::Esc => return Ok(Some(Transition::Pop)),
                     KeyCode::Enter => {
-                        let Some(zettel_header) = links.get(self.selected) else { continue };
+                        let Some((zettel_header, _)) = links.get(self.selected) else {
+                            continue;
+                        };
                         match zettelkasten_shared::block_on(
                             tui.storage.get_zettel(self.user.id, zettel_header.id),
                         ) {
@@ -85,15 +86,11 @@ impl List {
                             }
                         }
                     }
-                    KeyCode::Up => {
-                        if self.selected >= 1 {
-                            self.selected -= 1;
-                        }
+                    KeyCode::Up if self.selected >= 1 => {
+                        self.selected -= 1;
                     }
-                    KeyCode::Down => {
-                        if self.selected + 1 < links.len() {
-                            self.selected += 1;
-                        }
+                    KeyCode::Down if self.selected + 1 < links.len() => {
+                        self.selected += 1;
                     }
                     _ => {}
                 }
@@ -104,8 +101,9 @@ impl List {
     fn draw(
         &self,
         tui: &mut crate::Tui,
-        links: &[&storage::ZettelHeader],
+        links: &[(&storage::ZettelHeader, Vec<usize>)],
     ) -> Result<(), super::Error> {
+        let match_style = RenderStyle::new(tui.user_config.theme).match_style();
         tui.terminal
             .draw(|f| {
                 let size = f.size();
@@ -114,12 +112,22 @@ impl List {
 
                 let mut entries = Vec::new();
                 let mut previous_parts = None;
-                for (idx, zettel) in links.iter().enumerate() {
+                for (idx, (zettel, positions)) in links.iter().enumerate() {
                     let mut spans = Spans::default();
 
                     spans
                         .0
                         .push(Span::raw(if self.selected == idx { "> " } else { "  " }));
+                    // matches are sorted by score, so they are shown as full paths instead of a tree
+                    if !self.search.trim().is_empty() {
+                        spans.0.extend(super::utils::highlight_positions(
+                            &zettel.path,
+                            positions,
+                            match_style,
+                        ));
+                        entries.push(spans);
+                        continue;
+                    }
                     let parts = zettel.path.split('/').collect::<Vec<_>>();
                     let common_parts =
                         CommonParts::get(&parts, previous_parts.as_deref().unwrap_or_default());
@@ -174,7 +182,7 @@ impl<'a, 'b> CommonParts<'a, 'b> {
         let common_length = current
             .iter()
             .zip(previous.iter())
-            .take_while(|(left, right)| left.to_ascii_lowercase() == right.to_ascii_lowercase())
+            .take_while(|(left, right)| left.eq_ignore_ascii_case(right))
             .count();
         Self {
             common_length,
@@ -0,0 +1,204 @@
+use crossterm::event::{Event, KeyCode};
+use snafu::ResultExt;
+use std::sync::Arc;
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+use zettelkasten_shared::{links::LinkGraph, storage};
+
+/// A system page that lists zettels which need attention, like `sys:orphans`
+pub struct Report {
+    user: Arc<storage::User>,
+    title: String,
+    selected: usize,
+    entries: Vec<Entry>,
+    /// Shown instead of the entries, like an invalid search query
+    error: Option<String>,
+}
+
+struct Entry {
+    label: String,
+    /// The zettel that is opened when this entry is selected
+    id: storage::ZettelId,
+}
+
+impl Report {
+    /// The zettels that no other zettel links to
+    pub fn orphans(tui: &crate::Tui, user: Arc<storage::User>) -> super::Result<Self> {
+        let graph = zettelkasten_shared::block_on(LinkGraph::load(tui.storage.as_ref(), user.id))
+            .context(super::DatabaseSnafu)?;
+        let entries = graph
+            .orphans()
+            .into_iter()
+            .map(|zettel| Entry {
+                label: zettel.path.clone(),
+                id: zettel.id,
+            })
+            .collect();
+        Ok(Self {
+            user,
+            title: "Orphans".to_owned(),
+            selected: 0,
+            entries,
+            error: None,
+        })
+    }
+
+    /// The links that point to a zettel that does not exist
+    pub fn broken_links(tui: &crate::Tui, user: Arc<storage::User>) -> super::Result<Self> {
+        let graph = zettelkasten_shared::block_on(LinkGraph::load(tui.storage.as_ref(), user.id))
+            .context(super::DatabaseSnafu)?;
+        let entries = graph
+            .broken_links()
+            .into_iter()
+            .map(|link| Entry {
+                label: format!("{} -> {}", link.source.path, link.target),
+                id: link.source.id,
+            })
+            .collect();
+        Ok(Self {
+            user,
+            title: "Broken links".to_owned(),
+            selected: 0,
+            entries,
+            error: None,
+        })
+    }
+
+    /// The current results of a saved search, or of a `search:` link
+    pub fn search(
+        tui: &crate::Tui,
+        user: Arc<storage::User>,
+        saved: &storage::SavedSearch,
+    ) -> super::Result<Self> {
+        let (entries, error) =
+            match zettelkasten_shared::block_on(tui.storage.get_zettels(user.id, saved.opts())) {
+                Ok(zettels) => (
+                    zettels
+                        .into_iter()
+                        .map(|zettel| Entry {
+                            label: match zettel.snippets.first() {
+                                Some(snippet) => format!("{}: {}", zettel.path, snippet.text),
+                                None => zettel.path,
+                            },
+                            id: zettel.id,
+                        })
+                        .collect(),
+                    None,
+                ),
+                Err(
+                    e @ (storage::Error::InvalidQuery { .. } | storage::Error::InvalidRegex { .. }),
+                ) => (Vec::new(), Some(e.to_string())),
+                Err(source) => return Err(super::Error::Database { source }),
+            };
+        Ok(Self {
+            user,
+            title: saved.name.clone(),
+            selected: 0,
+            entries,
+            error,
+        })
+    }
+
+    pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        loop {
+            self.draw(tui)?;
+            let event = crossterm::event::read().context(super::EventSnafu)?;
+            if let Event::Key(key_event) = event {
+                match key_event.code {
+                    KeyCode::Esc => return Ok(Some(Transition::Pop)),
+                    KeyCode::Enter => {
+                        let Some(entry) = self.entries.get(self.selected) else {
+                            continue;
+                        };
+                        match zettelkasten_shared::block_on(
+                            tui.storage.get_zettel(self.user.id, entry.id),
+                        ) {
+                            Ok(zettel) => {
+                                return Ok(Some(Transition::NewZettel(
+                                    super::zettel::Zettel::new_with_zettel(
+                                        Arc::clone(&self.user),
+                                        zettel,
+                                        tui.storage,
+                                    ),
+                                )))
+                            }
+                            Err(e) => {
+                                super::alert(tui.terminal, |f| {
+                                    f.title("Error")
+                                        .text("Zettel not found")
+                                        .text(e.to_string())
+                                        .action(KeyCode::Enter, "Continue")
+                                })?;
+                            }
+                        }
+                    }
+                    KeyCode::Up if self.selected >= 1 => {
+                        self.selected -= 1;
+                    }
+                    KeyCode::Down if self.selected + 1 < self.entries.len() => {
+                        self.selected += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn draw(&self, tui: &mut crate::Tui) -> super::Result<()> {
+        tui.terminal
+            .draw(|f| {
+                let size = f.size();
+                let lines: Vec<Spans> = if let Some(error) = &self.error {
+                    vec![Spans::from(Span::styled(
+                        error.as_str(),
+                        Style::default().fg(Color::Red),
+                    ))]
+                } else if self.entries.is_empty() {
+                    vec![Spans::from("Nothing to report")]
+                } else {
+                    self.entries
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, entry)| {
+                            Spans(vec![
+                                Span::raw(if self.selected == idx { "> " } else { "  " }),
+                                Span::raw(entry.label.as_str()),
+                            ])
+                        })
+                        .collect()
+                };
+                let title = format!("{} ({})", self.title, self.entries.len());
+                let body = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title(title));
+                let actions =
+                    Paragraph::new("Up/Down: Select entry, Enter: Go to zettel, Esc: go back");
+
+                f.render_widget(
+                    body,
+                    Rect {
+                        height: size.height - 1,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    actions,
+                    Rect {
+                        height: 1,
+                        y: size.height - 1,
+                        ..size
+                    },
+                );
+            })
+            .context(super::IoSnafu)?;
+        Ok(())
+    }
+}
+
+pub enum Transition {
+    NewZettel(super::zettel::Zettel),
+    Pop,
+}
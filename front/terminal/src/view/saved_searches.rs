@@ -0,0 +1,123 @@
+use crossterm::event::{Event, KeyCode};
+use snafu::ResultExt;
+use std::sync::Arc;
+use tui::{
+    layout::Rect,
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+use zettelkasten_shared::storage;
+
+/// The searches a user saved from the search page, at `sys:saved-searches`
+pub struct SavedSearches {
+    user: Arc<storage::User>,
+    selected: usize,
+    searches: Vec<storage::SavedSearch>,
+}
+
+impl SavedSearches {
+    pub fn new(tui: &crate::Tui, user: Arc<storage::User>) -> super::Result<Self> {
+        let searches = zettelkasten_shared::block_on(tui.storage.get_saved_searches(user.id))
+            .context(super::DatabaseSnafu)?;
+        Ok(Self {
+            user,
+            selected: 0,
+            searches,
+        })
+    }
+
+    pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        loop {
+            self.draw(tui)?;
+            let event = crossterm::event::read().context(super::EventSnafu)?;
+            let Event::Key(key_event) = event else {
+                continue;
+            };
+            match key_event.code {
+                KeyCode::Esc => return Ok(Some(Transition::Pop)),
+                KeyCode::Enter => {
+                    let Some(saved) = self.searches.get(self.selected) else {
+                        continue;
+                    };
+                    let report = super::report::Report::search(tui, Arc::clone(&self.user), saved)?;
+                    return Ok(Some(Transition::Results(report)));
+                }
+                KeyCode::Char('d') => {
+                    if self.selected >= self.searches.len() {
+                        continue;
+                    }
+                    let saved = self.searches.remove(self.selected);
+                    zettelkasten_shared::block_on(
+                        tui.storage.delete_saved_search(self.user.id, &saved.name),
+                    )
+                    .context(super::DatabaseSnafu)?;
+                    self.selected = self.selected.min(self.searches.len().saturating_sub(1));
+                }
+                KeyCode::Up if self.selected >= 1 => {
+                    self.selected -= 1;
+                }
+                KeyCode::Down if self.selected + 1 < self.searches.len() => {
+                    self.selected += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, tui: &mut crate::Tui) -> super::Result<()> {
+        tui.terminal
+            .draw(|f| {
+                let size = f.size();
+                let lines: Vec<Spans> = if self.searches.is_empty() {
+                    vec![Spans::from(
+                        "No saved searches yet, press F3 on the search page to save one",
+                    )]
+                } else {
+                    self.searches
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, saved)| {
+                            Spans(vec![
+                                Span::raw(if self.selected == idx { "> " } else { "  " }),
+                                Span::raw(format!(
+                                    "{}: {} ({})",
+                                    saved.name,
+                                    saved.query,
+                                    saved.mode.name()
+                                )),
+                            ])
+                        })
+                        .collect()
+                };
+                let title = format!("Saved searches ({})", self.searches.len());
+                let body = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title(title));
+                let actions = Paragraph::new(
+                    "Up/Down: Select search, Enter: Show results, D: Delete search, Esc: go back",
+                );
+
+                f.render_widget(
+                    body,
+                    Rect {
+                        height: size.height - 1,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    actions,
+                    Rect {
+                        height: 1,
+                        y: size.height - 1,
+                        ..size
+                    },
+                );
+            })
+            .context(super::IoSnafu)?;
+        Ok(())
+    }
+}
+
+pub enum Transition {
+    Results(super::report::Report),
+    Pop,
+}
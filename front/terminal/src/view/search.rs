@@ -1,3 +1,4 @@
+use super::utils::RenderStyle;
 use crossterm::event::{Event, KeyCode};
 use snafu::ResultExt;
 use std::sync::Arc;
@@ -12,15 +13,29 @@ use zettelkasten_shared::storage;
 pub struct Search {
     user: Arc<storage::User>,
     input: String,
+    /// Front matter properties the results must have, as `key = value` separated by commas
+    filter: String,
+    /// Whether keys go to `filter` instead of `input`
+    editing_filter: bool,
+    mode: storage::SearchMode,
+    /// Why `input` is not a valid search in `mode`. The previous results are kept until it is.
+    error: Option<String>,
+    /// The name under which the search is saved, while it is being typed
+    naming: Option<String>,
     selected: usize,
     results: Vec<storage::ZettelHeader>,
 }
 
 impl Search {
-    pub(crate) fn new(user: Arc<storage::User>) -> Self {
+    pub(crate) fn new(user: Arc<storage::User>, mode: storage::SearchMode) -> Self {
         Self {
             user,
             input: String::new(),
+            filter: String::new(),
+            editing_filter: false,
+            mode,
+            error: None,
+            naming: None,
             selected: 0,
             results: Vec::new(),
         }
@@ -28,70 +43,42 @@ impl Search {
 
     pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
         loop {
-            tui.terminal
-                .draw(|f| {
-                    let size = f.size();
-                    let search = Paragraph::new(self.input.as_str())
-                        .block(Block::default().borders(Borders::all()).title("Search"));
-
-                    let mut entries = Vec::new();
-                    for (idx, zettel) in self.results.iter().enumerate() {
-                        let mut spans = Spans::default();
-
-                        spans
-                            .0
-                            .push(Span::raw(if self.selected == idx { "> " } else { "  " }));
-                        spans.0.push(Span::styled(
-                            zettel.path.as_str(),
-                            Style::default().fg(Color::Yellow),
-                        ));
-                        if let Some(text) = &zettel.highlight_text {
-                            spans.0.push(Span::raw(" "));
-                            spans.0.push(Span::raw(text.replace(['\n', '\r'], "")));
-                        }
-
-                        entries.push(spans);
-                    }
-                    let body = Paragraph::new(entries).block(
-                        Block::default().borders(Borders::RIGHT | Borders::LEFT | Borders::BOTTOM),
-                    );
-                    let actions =
-                        Paragraph::new("Up/Down: Select entry, Enter: Go to zettel, Esc: go back");
-
-                    f.render_widget(search, Rect { height: 3, ..size });
-                    f.render_widget(
-                        body,
-                        Rect {
-                            height: size.height - 4,
-                            y: 3,
-                            ..size
-                        },
-                    );
-                    f.render_widget(
-                        actions,
-                        Rect {
-                            height: 1,
-                            y: size.height - 1,
-                            ..size
-                        },
-                    );
-                })
-                .context(super::IoSnafu)?;
+            self.draw(tui)?;
 
             let event = crossterm::event::read().context(super::EventSnafu)?;
             if let Event::Key(key_event) = event {
+                if let Some(name) = &mut self.naming {
+                    match key_event.code {
+                        KeyCode::Char(c) => name.push(c),
+                        KeyCode::Backspace => drop(name.pop()),
+                        KeyCode::Esc => self.naming = None,
+                        KeyCode::Enter => self.save(tui)?,
+                        _ => {}
+                    }
+                    continue;
+                }
                 match key_event.code {
+                    KeyCode::F(3) if !self.input.is_empty() || !self.filter.is_empty() => {
+                        self.naming = Some(String::new());
+                    }
                     KeyCode::Char(c) => {
-                        self.input.push(c);
+                        self.active_input().push(c);
                         self.update_search(tui)?;
                     }
                     KeyCode::Backspace => {
-                        self.input.pop();
+                        self.active_input().pop();
+                        self.update_search(tui)?;
+                    }
+                    KeyCode::Tab => self.editing_filter = !self.editing_filter,
+                    KeyCode::F(2) => {
+                        self.mode = self.mode.next();
                         self.update_search(tui)?;
                     }
                     KeyCode::Esc => return Ok(Some(Transition::Pop)),
                     KeyCode::Enter => {
-                        let Some(zettel_header) = self.results.get(self.selected) else { continue };
+                        let Some(zettel_header) = self.results.get(self.selected) else {
+                            continue;
+                        };
                         match zettelkasten_shared::block_on(
                             tui.storage.get_zettel(self.user.id, zettel_header.id),
                         ) {
@@ -114,15 +101,11 @@ impl Search {
                             }
                         }
                     }
-                    KeyCode::Up => {
-                        if self.selected >= 1 {
-                            self.selected -= 1;
-                        }
+                    KeyCode::Up if self.selected >= 1 => {
+                        self.selected -= 1;
                     }
-                    KeyCode::Down => {
-                        if self.selected + 1 < self.results.len() {
-                            self.selected += 1;
-                        }
+                    KeyCode::Down if self.selected + 1 < self.results.len() => {
+                        self.selected += 1;
                     }
                     _ => {}
                 }
@@ -130,17 +113,158 @@ impl Search {
         }
     }
 
+    /// Save the current search under the name that was typed
+    fn save(&mut self, tui: &mut crate::Tui) -> super::Result {
+        let Some(name) = self.naming.take() else {
+            return Ok(());
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Ok(());
+        }
+        let saved = storage::SavedSearch {
+            name: name.to_owned(),
+            query: self.input.clone(),
+            mode: self.mode,
+            properties: self.properties(),
+        };
+        zettelkasten_shared::block_on(tui.storage.save_search(self.user.id, &saved))
+            .context(super::DatabaseSnafu)?;
+        super::alert(tui.terminal, |f| {
+            f.title("Search saved")
+                .text(format!("Link to it with [{name}](search:{name})"))
+                .action(KeyCode::Enter, "Continue")
+        })?;
+        Ok(())
+    }
+
+    /// The complete filters, incomplete ones are ignored while they are being typed
+    fn properties(&self) -> Vec<storage::PropertyFilter> {
+        self.filter
+            .split(',')
+            .filter_map(|filter| filter.parse().ok())
+            .collect()
+    }
+
+    fn active_input(&mut self) -> &mut String {
+        if self.editing_filter {
+            &mut self.filter
+        } else {
+            &mut self.input
+        }
+    }
+
+    fn draw(&self, tui: &mut crate::Tui) -> super::Result {
+        let style = RenderStyle::new(tui.user_config.theme);
+        let (link_style, match_style) = (style.link_style, style.match_style());
+        tui.terminal
+            .draw(|f| {
+                let size = f.size();
+                let cursor = |active: bool| if active { "_" } else { "" };
+                let search = Paragraph::new(format!(
+                    "{}{}",
+                    self.input,
+                    cursor(!self.editing_filter)
+                ))
+                .block(
+                    Block::default()
+                        .borders(Borders::all())
+                        .title(format!("Search ({})", self.mode.name())),
+                );
+                let filter =
+                    Paragraph::new(format!("{}{}", self.filter, cursor(self.editing_filter)))
+                        .block(
+                            Block::default()
+                                .borders(Borders::RIGHT | Borders::LEFT | Borders::BOTTOM)
+                                .title("Filter (key = value, ...)"),
+                        );
+
+                let mut entries = Vec::new();
+                if let Some(error) = &self.error {
+                    entries.push(Spans::from(Span::styled(
+                        error.as_str(),
+                        Style::default().fg(Color::Red),
+                    )));
+                }
+                for (idx, zettel) in self.results.iter().enumerate() {
+                    let mut spans = Spans::default();
+
+                    spans
+                        .0
+                        .push(Span::raw(if self.selected == idx { "> " } else { "  " }));
+                    spans.0.push(Span::styled(zettel.path.as_str(), link_style));
+                    for snippet in &zettel.snippets {
+                        spans.0.push(Span::raw(" "));
+                        spans.0.extend(super::utils::highlight_ranges(
+                            &snippet.text,
+                            &snippet.matches,
+                            match_style,
+                        ));
+                    }
+
+                    entries.push(spans);
+                }
+                let body = Paragraph::new(entries).block(
+                    Block::default().borders(Borders::RIGHT | Borders::LEFT | Borders::BOTTOM),
+                );
+                let actions = Paragraph::new(match &self.naming {
+                    Some(name) => format!("Save search as: {name}_ (Enter: save, Esc: cancel)"),
+                    None => "Up/Down: Select entry, Enter: Go to zettel, Tab: switch between search and filter, F2: search mode, F3: save search, Esc: go back".to_owned(),
+                });
+
+                f.render_widget(search, Rect { height: 3, ..size });
+                f.render_widget(
+                    filter,
+                    Rect {
+                        height: 3,
+                        y: 3,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    body,
+                    Rect {
+                        height: size.height - 7,
+                        y: 6,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    actions,
+                    Rect {
+                        height: 1,
+                        y: size.height - 1,
+                        ..size
+                    },
+                );
+            })
+            .context(super::IoSnafu)?;
+        Ok(())
+    }
+
     fn update_search(&mut self, tui: &mut crate::Tui) -> super::Result {
-        if self.input.is_empty() {
+        let properties = self.properties();
+        self.error = None;
+        if self.input.is_empty() && properties.is_empty() {
             self.results.clear();
         } else {
             let query = storage::SearchOpts {
                 query: &self.input,
+                mode: self.mode,
+                properties: &properties,
                 ..Default::default()
             };
-            self.results =
-                zettelkasten_shared::block_on(tui.storage.get_zettels(self.user.id, query))
-                    .context(super::DatabaseSnafu)?;
+            match zettelkasten_shared::block_on(tui.storage.get_zettels(self.user.id, query)) {
+                Ok(results) => self.results = results,
+                // incomplete searches keep the previous results while they are being typed
+                Err(storage::Error::InvalidQuery { source }) => {
+                    self.error = Some(source.to_string());
+                }
+                Err(storage::Error::InvalidRegex { source }) => {
+                    self.error = Some(source.to_string());
+                }
+                Err(source) => return Err(super::Error::Database { source }),
+            }
         }
         if self.results.is_empty() {
             self.selected = 0;
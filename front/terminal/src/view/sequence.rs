@@ -0,0 +1,202 @@
+use crossterm::event::{Event, KeyCode};
+use snafu::ResultExt;
+use std::{collections::HashSet, sync::Arc};
+use tui::{
+    layout::Rect,
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+use zettelkasten_shared::{folgezettel::Folgezettel, storage};
+
+/// Shows all zettels with a Folgezettel id in reading order, and creates continuations and branches
+pub struct Sequence {
+    user: Arc<storage::User>,
+    entries: Vec<(Folgezettel, storage::ZettelHeader)>,
+    taken: HashSet<Folgezettel>,
+    selected: usize,
+    /// The id and path of the zettel that is about to be created
+    new_entry: Option<(Folgezettel, String)>,
+}
+
+impl Sequence {
+    pub fn new(
+        tui: &crate::Tui,
+        user: Arc<storage::User>,
+        current: &storage::Zettel,
+    ) -> super::Result<Self> {
+        let zettels = zettelkasten_shared::block_on(tui.storage.get_zettels(
+            user.id,
+            storage::SearchOpts {
+                list_all: true,
+                ..Default::default()
+            },
+        ))
+        .context(super::DatabaseSnafu)?;
+        let entries: Vec<_> = zettelkasten_shared::folgezettel::sequence(&zettels)
+            .into_iter()
+            .map(|(id, zettel)| (id, zettel.clone()))
+            .collect();
+        let taken = entries.iter().map(|(id, _)| id.clone()).collect();
+        let selected = entries
+            .iter()
+            .position(|(_, zettel)| zettel.id == current.id)
+            .unwrap_or_default();
+        Ok(Self {
+            user,
+            entries,
+            taken,
+            selected,
+            new_entry: None,
+        })
+    }
+
+    pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        loop {
+            self.draw(tui)?;
+            let event = crossterm::event::read().context(super::EventSnafu)?;
+            let Event::Key(key_event) = event else {
+                continue;
+            };
+            if let Some((_, path)) = &mut self.new_entry {
+                match key_event.code {
+                    KeyCode::Char(c) => path.push(c),
+                    KeyCode::Backspace => drop(path.pop()),
+                    KeyCode::Esc => self.new_entry = None,
+                    KeyCode::Enter => {
+                        if let Some(zettel) = self.create(tui)? {
+                            return Ok(Some(Transition::NewZettel(zettel)));
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            let selected = self.entries.get(self.selected);
+            match key_event.code {
+                KeyCode::Esc => return Ok(Some(Transition::Pop)),
+                KeyCode::Enter => {
+                    let Some((_, zettel)) = selected else {
+                        continue;
+                    };
+                    let zettel = zettelkasten_shared::block_on(
+                        tui.storage.get_zettel(self.user.id, zettel.id),
+                    )
+                    .context(super::DatabaseSnafu)?;
+                    return Ok(Some(Transition::NewZettel(
+                        super::zettel::Zettel::new_with_zettel(
+                            Arc::clone(&self.user),
+                            zettel,
+                            tui.storage,
+                        ),
+                    )));
+                }
+                KeyCode::Char('c') => {
+                    let id = Folgezettel::continuation(selected.map(|(id, _)| id), &self.taken);
+                    self.new_entry = Some((id, String::new()));
+                }
+                KeyCode::Char('b') => {
+                    if let Some((id, _)) = selected {
+                        let id = Folgezettel::branch(id, &self.taken);
+                        self.new_entry = Some((id, String::new()));
+                    }
+                }
+                KeyCode::Up if self.selected >= 1 => {
+                    self.selected -= 1;
+                }
+                KeyCode::Down if self.selected + 1 < self.entries.len() => {
+                    self.selected += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Give the zettel at the entered path the new id. The zettel is created if it does not exist yet.
+    fn create(&mut self, tui: &mut crate::Tui) -> super::Result<Option<super::zettel::Zettel>> {
+        let Some((id, path)) = &self.new_entry else {
+            return Ok(None);
+        };
+        let id = id.to_string();
+        let path = match path.trim() {
+            "" => id.clone(),
+            path => path.to_owned(),
+        };
+        let existing =
+            zettelkasten_shared::block_on(tui.storage.get_zettel_by_url(self.user.id, &path))
+                .context(super::DatabaseSnafu)?;
+        let mut zettel = existing.unwrap_or_else(|| storage::Zettel {
+            path: path.clone(),
+            ..Default::default()
+        });
+        if let Some(existing_id) = &zettel.folgezettel {
+            let message = format!("{path} already has the Folgezettel id {existing_id}");
+            super::alert(tui.terminal, |f| {
+                f.title("Could not create zettel")
+                    .text(message.clone())
+                    .action(KeyCode::Enter, "Continue")
+            })?;
+            return Ok(None);
+        }
+        zettel.folgezettel = Some(id);
+        zettelkasten_shared::block_on(tui.storage.update_zettel(self.user.id, &mut zettel))
+            .context(super::DatabaseSnafu)?;
+        Ok(Some(super::zettel::Zettel::new_with_zettel(
+            Arc::clone(&self.user),
+            zettel,
+            tui.storage,
+        )))
+    }
+
+    fn draw(&self, tui: &mut crate::Tui) -> super::Result<()> {
+        tui.terminal
+            .draw(|f| {
+                let size = f.size();
+                let mut lines: Vec<Spans> = self
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, (id, zettel))| {
+                        Spans(vec![
+                            Span::raw(if self.selected == idx { "> " } else { "  " }),
+                            Span::raw("  ".repeat(id.depth())),
+                            Span::raw(format!("{id} {}", zettel.path)),
+                        ])
+                    })
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(Spans::from("No zettel has a Folgezettel id yet"));
+                }
+                let body = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title("Folgezettel"));
+                let actions = match &self.new_entry {
+                    Some((id, path)) => format!(
+                        "Path of {id} (empty for {id}, an existing zettel keeps its content): {path}_"
+                    ),
+                    None => "Up/Down: Select zettel, Enter: Go to zettel, C: continue, B: branch off, Esc: go back".to_owned(),
+                };
+
+                f.render_widget(
+                    body,
+                    Rect {
+                        height: size.height - 1,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    Paragraph::new(actions),
+                    Rect {
+                        height: 1,
+                        y: size.height - 1,
+                        ..size
+                    },
+                );
+            })
+            .context(super::RenderFrameSnafu)?;
+        Ok(())
+    }
+}
+
+pub enum Transition {
+    NewZettel(super::zettel::Zettel),
+    Pop,
+}
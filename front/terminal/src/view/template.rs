@@ -0,0 +1,141 @@
+use crossterm::event::{Event, KeyCode};
+use snafu::ResultExt;
+use std::sync::Arc;
+use tui::{
+    layout::Rect,
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+use zettelkasten_shared::{storage, template};
+
+/// Lets the user pick the template for a zettel that does not exist yet
+pub struct TemplatePicker {
+    user: Arc<storage::User>,
+    path: String,
+    selected: usize,
+    /// The paths of the available templates
+    templates: Vec<String>,
+}
+
+impl TemplatePicker {
+    pub fn new(tui: &crate::Tui, user: Arc<storage::User>, path: String) -> super::Result<Self> {
+        let templates: Vec<String> =
+            zettelkasten_shared::block_on(template::list_templates(tui.storage.as_ref(), &user))
+                .context(super::DatabaseSnafu)?
+                .into_iter()
+                .map(|header| header.path)
+                .collect();
+        // the first entry is the empty zettel, so the templates start at 1
+        let selected = template::default_template(&tui.user_config, &path)
+            .map(template::template_path)
+            .and_then(|default| {
+                templates
+                    .iter()
+                    .position(|path| path.eq_ignore_ascii_case(&default))
+            })
+            .map_or(0, |idx| idx + 1);
+        Ok(Self {
+            user,
+            path,
+            selected,
+            templates,
+        })
+    }
+
+    pub fn has_templates(&self) -> bool {
+        !self.templates.is_empty()
+    }
+
+    /// Create the zettel from the selected template
+    pub fn create(&self, tui: &crate::Tui) -> super::Result<super::zettel::Zettel> {
+        let zettel = match self.selected.checked_sub(1) {
+            Some(idx) => zettelkasten_shared::block_on(template::new_zettel(
+                tui.storage.as_ref(),
+                self.user.id,
+                &self.templates[idx],
+                &template::TemplateVars::new(&self.path, &self.user),
+            ))
+            .context(super::DatabaseSnafu)?,
+            None => storage::Zettel {
+                path: self.path.clone(),
+                ..Default::default()
+            },
+        };
+        Ok(super::zettel::Zettel::new_with_zettel(
+            Arc::clone(&self.user),
+            zettel,
+            tui.storage,
+        ))
+    }
+
+    pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        loop {
+            self.draw(tui)?;
+            let event = crossterm::event::read().context(super::EventSnafu)?;
+            if let Event::Key(key_event) = event {
+                match key_event.code {
+                    KeyCode::Esc => return Ok(Some(Transition::Pop)),
+                    KeyCode::Enter => return Ok(Some(Transition::NewZettel(self.create(tui)?))),
+                    KeyCode::Up if self.selected >= 1 => {
+                        self.selected -= 1;
+                    }
+                    KeyCode::Down if self.selected < self.templates.len() => {
+                        self.selected += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn draw(&self, tui: &mut crate::Tui) -> super::Result<()> {
+        tui.terminal
+            .draw(|f| {
+                let size = f.size();
+                let names = std::iter::once("(empty)").chain(
+                    self.templates
+                        .iter()
+                        .map(|path| template::template_name(path).unwrap_or(path)),
+                );
+                let entries: Vec<Spans> = names
+                    .enumerate()
+                    .map(|(idx, name)| {
+                        Spans(vec![
+                            Span::raw(if self.selected == idx { "> " } else { "  " }),
+                            Span::raw(name),
+                        ])
+                    })
+                    .collect();
+                let body = Paragraph::new(entries).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("New zettel {}: pick a template", self.path)),
+                );
+                let actions =
+                    Paragraph::new("Up/Down: Select template, Enter: Create zettel, Esc: go back");
+
+                f.render_widget(
+                    body,
+                    Rect {
+                        height: size.height - 1,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    actions,
+                    Rect {
+                        height: 1,
+                        y: size.height - 1,
+                        ..size
+                    },
+                );
+            })
+            .context(super::IoSnafu)?;
+        Ok(())
+    }
+}
+
+pub enum Transition {
+    NewZettel(super::zettel::Zettel),
+    Pop,
+}
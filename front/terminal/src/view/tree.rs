@@ -0,0 +1,246 @@
+use crossterm::event::{Event, KeyCode};
+use snafu::ResultExt;
+use std::sync::Arc;
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+use zettelkasten_shared::{namespace, storage};
+
+/// Shows the paths of all zettels as a collapsible tree of namespaces, see [`namespace`]
+pub struct Tree {
+    pub(super) user: Arc<storage::User>,
+    /// The visible nodes, every node is followed by its children if it is expanded
+    nodes: Vec<Node>,
+    selected: usize,
+    /// The name of the zettel that is about to be created below the selected node
+    new_child: Option<String>,
+}
+
+struct Node {
+    child: storage::PathChild,
+    depth: usize,
+    expanded: bool,
+}
+
+impl Tree {
+    /// Show the top level, with the namespaces of `current` expanded and `current` selected
+    pub fn new(tui: &crate::Tui, user: Arc<storage::User>, current: &str) -> super::Result<Self> {
+        let mut tree = Self {
+            user,
+            nodes: Vec::new(),
+            selected: 0,
+            new_child: None,
+        };
+        tree.nodes = tree.children(tui, "", 0)?;
+        let mut start = 0;
+        for (end, _) in current
+            .match_indices(namespace::SEPARATOR)
+            .chain([(current.len(), "")])
+        {
+            let path = &current[..end];
+            let Some(idx) = tree.nodes[start..]
+                .iter()
+                .position(|node| node.child.path.eq_ignore_ascii_case(path))
+            else {
+                break;
+            };
+            tree.selected = start + idx;
+            if end < current.len() {
+                tree.expand(tui)?;
+            }
+            start = tree.selected + 1;
+        }
+        Ok(tree)
+    }
+
+    fn children(&self, tui: &crate::Tui, parent: &str, depth: usize) -> super::Result<Vec<Node>> {
+        let children =
+            zettelkasten_shared::block_on(tui.storage.get_children(self.user.id, parent))
+                .context(super::DatabaseSnafu)?;
+        Ok(children
+            .into_iter()
+            .map(|child| Node {
+                child,
+                depth,
+                expanded: false,
+            })
+            .collect())
+    }
+
+    /// Show the children of the selected node
+    fn expand(&mut self, tui: &crate::Tui) -> super::Result<()> {
+        let Some(node) = self.nodes.get(self.selected) else {
+            return Ok(());
+        };
+        if node.expanded || node.child.descendants == 0 {
+            return Ok(());
+        }
+        let children = self.children(tui, &node.child.path, node.depth + 1)?;
+        self.nodes[self.selected].expanded = true;
+        let at = self.selected + 1;
+        drop(self.nodes.splice(at..at, children));
+        Ok(())
+    }
+
+    /// Hide the children of the selected node, or select its parent if it is not expanded
+    fn collapse(&mut self) {
+        let Some(node) = self.nodes.get(self.selected) else {
+            return;
+        };
+        let depth = node.depth;
+        if node.expanded {
+            let end = self.nodes[self.selected + 1..]
+                .iter()
+                .position(|node| node.depth <= depth)
+                .map_or(self.nodes.len(), |idx| self.selected + 1 + idx);
+            drop(self.nodes.drain(self.selected + 1..end));
+            self.nodes[self.selected].expanded = false;
+        } else if let Some(parent) = self.nodes[..self.selected]
+            .iter()
+            .rposition(|node| node.depth < depth)
+        {
+            self.selected = parent;
+        }
+    }
+
+    pub(crate) fn render(&mut self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        loop {
+            self.draw(tui)?;
+            let event = crossterm::event::read().context(super::EventSnafu)?;
+            let Event::Key(key_event) = event else {
+                continue;
+            };
+            if let Some(name) = &mut self.new_child {
+                match key_event.code {
+                    KeyCode::Char(c) => name.push(c),
+                    KeyCode::Backspace => drop(name.pop()),
+                    KeyCode::Esc => self.new_child = None,
+                    KeyCode::Enter if !name.trim().is_empty() => {
+                        let parent = self
+                            .nodes
+                            .get(self.selected)
+                            .map_or("", |node| node.child.path.as_str());
+                        return Ok(Some(Transition::Create(namespace::join(
+                            parent,
+                            name.trim(),
+                        ))));
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match key_event.code {
+                KeyCode::Esc => return Ok(Some(Transition::Pop)),
+                KeyCode::Enter => {
+                    let Some(node) = self.nodes.get(self.selected) else {
+                        continue;
+                    };
+                    if let Some(id) = node.child.zettel {
+                        let zettel =
+                            zettelkasten_shared::block_on(tui.storage.get_zettel(self.user.id, id))
+                                .context(super::DatabaseSnafu)?;
+                        return Ok(Some(Transition::NewZettel(
+                            super::zettel::Zettel::new_with_zettel(
+                                Arc::clone(&self.user),
+                                zettel,
+                                tui.storage,
+                            ),
+                        )));
+                    } else if node.expanded {
+                        self.collapse();
+                    } else {
+                        self.expand(tui)?;
+                    }
+                }
+                KeyCode::Char('n') => self.new_child = Some(String::new()),
+                KeyCode::Right => self.expand(tui)?,
+                KeyCode::Left => self.collapse(),
+                KeyCode::Up if self.selected >= 1 => {
+                    self.selected -= 1;
+                }
+                KeyCode::Down if self.selected + 1 < self.nodes.len() => {
+                    self.selected += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, tui: &mut crate::Tui) -> super::Result<()> {
+        tui.terminal
+            .draw(|f| {
+                let size = f.size();
+                let mut lines: Vec<Spans> = self
+                    .nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, node)| {
+                        let marker = match (node.child.descendants, node.expanded) {
+                            (0, _) => "  ",
+                            (_, true) => "▾ ",
+                            (_, false) => "▸ ",
+                        };
+                        // namespaces without a zettel of their own can't be opened
+                        let style = if node.child.zettel.is_some() {
+                            Style::default()
+                        } else {
+                            Style::default().fg(Color::DarkGray)
+                        };
+                        let mut spans = vec![
+                            Span::raw(if self.selected == idx { "> " } else { "  " }),
+                            Span::raw("  ".repeat(node.depth)),
+                            Span::raw(marker),
+                            Span::styled(namespace::name(&node.child.path).to_owned(), style),
+                        ];
+                        if node.child.descendants > 0 {
+                            spans.push(Span::raw(format!(" ({})", node.child.descendants)));
+                        }
+                        Spans(spans)
+                    })
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(Spans::from("There are no zettels yet"));
+                }
+                let body = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title("Namespaces"));
+                let actions = match &self.new_child {
+                    Some(name) => {
+                        let parent = self
+                            .nodes
+                            .get(self.selected)
+                            .map_or("", |node| node.child.path.as_str());
+                        format!("New zettel: {}_", namespace::join(parent, name))
+                    }
+                    None => "Up/Down: Select entry, Left/Right: collapse/expand, Enter: Go to zettel, N: create child here, Esc: go back".to_owned(),
+                };
+
+                f.render_widget(
+                    body,
+                    Rect {
+                        height: size.height - 1,
+                        ..size
+                    },
+                );
+                f.render_widget(
+                    Paragraph::new(actions),
+                    Rect {
+                        height: 1,
+                        y: size.height - 1,
+                        ..size
+                    },
+                );
+            })
+            .context(super::RenderFrameSnafu)?;
+        Ok(())
+    }
+}
+
+pub enum Transition {
+    NewZettel(super::zettel::Zettel),
+    /// Create a zettel at this path
+    Create(String),
+    Pop,
+}
@@ -3,36 +3,66 @@ use snafu::ResultExt;
 use std::{
     collections::HashMap,
     io::{Read, Seek, Write},
+    ops::Range,
+    sync::Arc,
 };
 use tui::{
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Span, Spans},
 };
-use zettelkasten_shared::storage;
-
-lazy_static::lazy_static! {
-    /// https://regex101.com/r/wOR3xF/1
-    /// matches:
-    /// - [asd]
-    /// - [asd](dsa)
-    /// but not:
-    /// - `[asd]
-    /// - `[asd](dsa)
-    static ref LINK_REGEX: regex::Regex = regex::Regex::new(r#"(?:^|[^`])(\[[^\]]+\])(\([^)]+\))?"#).unwrap();
-}
+use zettelkasten_shared::{frontmatter, links, storage};
 
 pub struct RenderStyle {
     pub link_style: Style,
     pub link_highlight_style: Style,
 }
 
-impl Default for RenderStyle {
-    fn default() -> Self {
+impl RenderStyle {
+    pub fn new(theme: storage::Theme) -> Self {
+        let link_color = match theme {
+            storage::Theme::Dark => Color::Yellow,
+            storage::Theme::Light => Color::Blue,
+        };
         Self {
-            link_style: Style::default().fg(Color::Yellow),
-            link_highlight_style: Style::default().bg(Color::Yellow),
+            link_style: Style::default().fg(link_color),
+            link_highlight_style: Style::default().bg(link_color),
         }
     }
+
+    /// The style of characters that matched a search
+    pub fn match_style(&self) -> Style {
+        self.link_style.add_modifier(Modifier::BOLD)
+    }
+}
+
+/// Split `text` into spans, with the characters at the byte offsets `positions` in `style`. `positions` must be
+/// sorted, as in [`zettelkasten_shared::fuzzy::Match::positions`].
+pub fn highlight_positions<'a>(text: &'a str, positions: &[usize], style: Style) -> Vec<Span<'a>> {
+    let ranges: Vec<Range<usize>> = positions
+        .iter()
+        .map(|&at| at..at + text[at..].chars().next().map_or(0, char::len_utf8))
+        .collect();
+    highlight_ranges(text, &ranges, style)
+}
+
+/// Split `text` into spans, with the byte `ranges` in `style`. `ranges` must be sorted and must not overlap, as in
+/// [`zettelkasten_shared::snippet::Snippet::matches`].
+pub fn highlight_ranges<'a>(text: &'a str, ranges: &[Range<usize>], style: Style) -> Vec<Span<'a>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for range in ranges {
+        if range.start > start {
+            spans.push(Span::raw(&text[start..range.start]));
+        }
+        if !range.is_empty() {
+            spans.push(Span::styled(&text[range.clone()], style));
+        }
+        start = range.end;
+    }
+    if start < text.len() {
+        spans.push(Span::raw(&text[start..]));
+    }
+    spans
 }
 
 pub struct ParsedZettel<'a> {
@@ -52,31 +82,29 @@ impl<'a> ParsedZettel<'a> {
         let mut lines = Vec::new();
         let mut links = HashMap::new();
 
+        let front_matter = frontmatter::parse(&zettel.body);
+        let content = front_matter
+            .as_ref()
+            .map_or(zettel.body.as_str(), |f| f.content);
+        if let Some(front_matter) = &front_matter {
+            lines.extend(front_matter_header(&front_matter.properties));
+        }
+
         let allowed_chars = ('a'..='z')
             .filter(|c| !disallowed.contains(c))
             .collect::<Vec<_>>();
-        let matches = LINK_REGEX.find_iter(&zettel.body).count();
-
-        let mut naming_scheme = if matches <= allowed_chars.len() {
-            NamingScheme::single(allowed_chars)
-        } else if matches <= allowed_chars.len().pow(2) {
-            NamingScheme::double(allowed_chars)
-        } else {
-            panic!(
-            "Too many links in document: {matches}, there are only enough characters for {} links",
-            allowed_chars.len().pow(2)
-        );
-        };
+        let matches = links::parse(content).count();
+
+        let mut naming_scheme = NamingScheme::new(allowed_chars, matches);
 
-        for mut line in zettel.body.lines() {
+        for line in content.lines() {
             let mut parts = Vec::new();
 
-            for link in LINK_REGEX.captures_iter(line) {
-                let text = link.get(1).unwrap();
-                let maybe_link = link.get(2);
+            let mut start = 0;
+            for link in links::parse(line) {
                 let char = naming_scheme.next().expect("Ran out of characters");
-                if text.start() > 0 {
-                    parts.push(Span::raw(&line[..text.start()]));
+                if link.text.start > start {
+                    parts.push(Span::raw(&line[start..link.text.start]));
                 }
                 if render_links {
                     // if we're rendering links, show the next CHAR as a highlight on top of the link
@@ -85,22 +113,15 @@ impl<'a> ParsedZettel<'a> {
                         style.link_highlight_style,
                     ));
                     // Render the rest of the link
-                    let range = text.range();
-                    let range = (range.start + 2 + char.len())..range.end;
+                    let range = (link.text.start + 2 + char.len())..link.text.end;
                     parts.push(Span::styled(&line[range], style.link_style));
                 } else {
-                    parts.push(Span::styled(&line[text.range()], style.link_style));
+                    parts.push(Span::styled(&line[link.text], style.link_style));
                 }
-                let end = maybe_link.map_or_else(|| text.end(), |l| l.end());
-                line = &line[end..];
-
-                let url = if let Some(url) = maybe_link {
-                    url.as_str().trim_start_matches('(').trim_end_matches(')')
-                } else {
-                    text.as_str().trim_start_matches('[').trim_end_matches(']')
-                };
-                links.insert(char, url);
+                start = link.range.end;
+                links.insert(char, link.target);
             }
+            let line = &line[start..];
             if !line.is_empty() {
                 parts.push(line.into());
             }
@@ -115,35 +136,59 @@ impl<'a> ParsedZettel<'a> {
     }
 }
 
-enum NamingScheme {
-    Single {
-        idx: usize,
-        chars: Vec<char>,
-    },
-    Double {
-        first_idx: usize,
-        second_idx: usize,
-        chars: Vec<char>,
-    },
+/// The front matter as one line per key, followed by an empty line
+fn front_matter_header<'a>(properties: &[(&'a str, &'a str)]) -> Vec<Spans<'a>> {
+    let mut keys: Vec<(&str, Vec<&str>)> = Vec::new();
+    for &(key, value) in properties {
+        match keys.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            Some((_, values)) => values.push(value),
+            None => keys.push((key, vec![value])),
+        }
+    }
+    let key_style = Style::default()
+        .fg(Color::DarkGray)
+        .add_modifier(Modifier::BOLD);
+    let value_style = Style::default().fg(Color::DarkGray);
+    let mut lines: Vec<Spans> = keys
+        .into_iter()
+        .map(|(key, values)| {
+            Spans(vec![
+                Span::styled(format!("{key}: "), key_style),
+                Span::styled(values.join(", "), value_style),
+            ])
+        })
+        .collect();
+    lines.push(Spans::default());
+    lines
+}
+
+/// Names links with strings of `chars` that all have the same length, which is as short as possible for the number of
+/// links
+struct NamingScheme {
+    chars: Vec<char>,
+    len: u32,
+    next: usize,
 }
 
 impl NamingScheme {
-    fn single(chars: Vec<char>) -> Self {
-        Self::Single { idx: 0, chars }
-    }
-    fn double(chars: Vec<char>) -> Self {
-        Self::Double {
-            first_idx: 0,
-            second_idx: 0,
+    fn new(chars: Vec<char>, links: usize) -> Self {
+        assert!(
+            chars.len() > 1,
+            "Links can't be named with fewer than two characters"
+        );
+        let mut len = 1;
+        while chars.len().pow(len) < links {
+            len += 1;
+        }
+        Self {
             chars,
+            len,
+            next: 0,
         }
     }
 
     fn link_char_size(&self) -> usize {
-        match self {
-            Self::Single { .. } => 1,
-            Self::Double { .. } => 2,
-        }
+        self.len as usize
     }
 }
 
@@ -151,32 +196,27 @@ impl Iterator for NamingScheme {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self {
-            Self::Single { idx, chars } => {
-                let result = chars.get(*idx)?.to_string();
-                *idx += 1;
-                Some(result)
-            }
-            Self::Double {
-                first_idx,
-                second_idx,
-                chars,
-            } => {
-                let first = chars.get(*first_idx)?;
-                let second = chars.get(*second_idx)?;
-                *second_idx += 1;
-                if *second_idx >= chars.len() {
-                    *second_idx = 0;
-                    *first_idx += 1;
-                }
-                Some(format!("{first}{second}"))
-            }
+        if self.next >= self.chars.len().pow(self.len) {
+            return None;
         }
+        let mut name = vec![self.chars[0]; self.link_char_size()];
+        let mut rest = self.next;
+        for c in name.iter_mut().rev() {
+            *c = self.chars[rest % self.chars.len()];
+            rest /= self.chars.len();
+        }
+        self.next += 1;
+        Some(name.into_iter().collect())
     }
 }
 
 pub fn edit(zettel: &storage::Zettel, tui: &mut crate::Tui) -> super::Result<Option<String>> {
-    let Some(editor) = &tui.system_config.terminal_editor else {
+    let Some(editor) = tui
+        .user_config
+        .editor
+        .as_ref()
+        .or(tui.system_config.terminal_editor.as_ref())
+    else {
         super::alert(tui.terminal, |cb| {
             cb.title("Could not edit zettel")
                 .text("No terminal editor configured")
@@ -208,11 +248,24 @@ pub fn edit(zettel: &storage::Zettel, tui: &mut crate::Tui) -> super::Result<Opt
 
 pub(super) fn try_get_sys_page(
     tui: &mut crate::Tui,
+    user: &Arc<storage::User>,
     link: &str,
 ) -> super::Result<Option<super::ViewLayer>> {
     if let Some(page) = link.strip_prefix("sys:") {
         match page {
-            "config" => Ok(Some(super::config::Config::new(tui).into())),
+            "config" => Ok(Some(
+                super::config::Config::new(tui, Arc::clone(user)).into(),
+            )),
+            "export" => Ok(Some(super::export::Export::new(Arc::clone(user)).into())),
+            "orphans" => Ok(Some(
+                super::report::Report::orphans(tui, Arc::clone(user))?.into(),
+            )),
+            "broken-links" => Ok(Some(
+                super::report::Report::broken_links(tui, Arc::clone(user))?.into(),
+            )),
+            "saved-searches" => Ok(Some(
+                super::saved_searches::SavedSearches::new(tui, Arc::clone(user))?.into(),
+            )),
             _ => Err(super::Error::UnknownSysPage {
                 page: page.to_string(),
             }),
@@ -221,3 +274,25 @@ pub(super) fn try_get_sys_page(
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_naming_scheme() {
+        let names: Vec<String> = NamingScheme::new(vec!['a', 'b'], 2).collect();
+        assert_eq!(names, ["a", "b"]);
+        let names: Vec<String> = NamingScheme::new(vec!['a', 'b'], 3).collect();
+        assert_eq!(names, ["aa", "ab", "ba", "bb"]);
+
+        let chars: Vec<char> = ('a'..='o').collect();
+        let scheme = NamingScheme::new(chars.clone(), chars.len().pow(2) + 1);
+        assert_eq!(scheme.link_char_size(), 3);
+        let names: Vec<String> = scheme.collect();
+        assert_eq!(names.len(), chars.len().pow(3));
+        assert_eq!(names[1], "aab");
+        assert_eq!(names[chars.len()], "aba");
+        assert_eq!(names.last().unwrap(), "ooo");
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use super::utils::{ParsedZettel, RenderStyle};
 use crossterm::event::{Event, KeyCode};
@@ -8,9 +8,9 @@ use tui::{
     text::Text,
     widgets::{Block, Borders, Paragraph},
 };
-use zettelkasten_shared::storage;
+use zettelkasten_shared::{chrono::NaiveDate, journal, links, storage};
 
-const ENTRY_TEXT: &str = r#"Welcome to Zettelkasten
+const ENTRY_TEXT: &str = r"Welcome to Zettelkasten
 
 You can see the available controls at the bottom of the page. If you are an admin, make sure to check out the config page (`C`).
 
@@ -19,12 +19,26 @@ You can see the available controls at the bottom of the page. If you are an admi
 - E: Edit the current page
 - F: Follow a link on the current page
   - Links are marked by `[name]` or `[name](path)` (only the `name` will be rendered)
+- J: Open today's daily note
+  - [ and ]: Go to the daily note of the previous or next day
 - L: Log out
+- P: Edit the other paths (aliases) under which the current page can be found
 - Q: Exit zettelkasten
 - S: Search in all zettels
-"#;
+- T: Browse all paths as a tree, where `/` separates namespaces
+- Z: Show the Folgezettel sequence, and continue or branch off from the current zettel
 
-const DISALLOWED_CHARS: &[char] = &['a', 'c', 'e', 'f', 'l', 'q', 's'];
+To keep your zettelkasten connected, check the [orphans](sys:orphans) and [broken links](sys:broken-links) every now and then.
+The link graph can be [exported](sys:export) to Graphviz, GraphML or JSON.
+
+Searches can be saved with F3 on the search page and are listed under [saved searches](sys:saved-searches).
+A link like `[open questions](search:tag:question)` shows the live results of a saved search with that name, or else of the query itself.
+";
+
+/// How often we check if the zettel was changed while waiting for input
+const STORAGE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const DISALLOWED_CHARS: &[char] = &['a', 'c', 'e', 'f', 'j', 'l', 'p', 'q', 's', 't', 'z'];
 
 #[derive(Clone)]
 pub struct Zettel {
@@ -33,19 +47,33 @@ pub struct Zettel {
 }
 
 impl Zettel {
-    pub(crate) fn new_with_user(
-        storage: &Arc<dyn storage::Storage>,
-        user: Arc<storage::User>,
-    ) -> Self {
-        let zettel: Option<storage::Zettel> = user.last_visited_zettel.and_then(|zettel_id| {
-            zettelkasten_shared::block_on(storage.get_zettel(user.id, zettel_id)).ok()
-        });
+    pub(crate) fn new_with_user(tui: &mut crate::Tui, user: Arc<storage::User>) -> Self {
+        let storage = tui.storage;
+        tui.user_config =
+            zettelkasten_shared::block_on(storage.get_user_config(user.id)).unwrap_or_default();
+
+        let zettel: Option<storage::Zettel> = if let Some(landing_zettel) =
+            &tui.user_config.landing_zettel
+        {
+            let zettel =
+                zettelkasten_shared::block_on(storage.get_zettel_by_url(user.id, landing_zettel));
+            Some(zettel.ok().flatten().unwrap_or_else(|| storage::Zettel {
+                path: landing_zettel.clone(),
+                ..Default::default()
+            }))
+        } else {
+            user.last_visited_zettel.and_then(|zettel_id| {
+                zettelkasten_shared::block_on(storage.get_zettel(user.id, zettel_id)).ok()
+            })
+        };
         Self::new_with_zettel(
             user,
             zettel.unwrap_or_else(|| storage::Zettel {
                 id: 0,
                 path: "home".into(),
                 body: ENTRY_TEXT.into(),
+                folgezettel: None,
+                aliases: Vec::new(),
                 attachments: Vec::new(),
             }),
             storage,
@@ -69,26 +97,32 @@ impl Zettel {
         let mut render_link_input: Option<String> = None;
         let mut rendered_zettel = None;
         loop {
-            let title = &self.zettel.path;
+            let mut title = match &self.zettel.folgezettel {
+                Some(folgezettel) => format!("{folgezettel} {}", self.zettel.path),
+                None => self.zettel.path.clone(),
+            };
+            if !self.zettel.aliases.is_empty() {
+                title = format!("{title} (also {})", self.zettel.aliases.join(", "));
+            }
             let zettel = rendered_zettel.get_or_insert_with(|| {
                 ParsedZettel::parse(
                     &self.zettel,
                     DISALLOWED_CHARS,
                     render_link_input.is_some(),
-                    RenderStyle::default(),
+                    RenderStyle::new(tui.user_config.theme),
                 )
             });
 
             let body = Paragraph::new(Text {
                 lines: zettel.lines.clone(),
             })
-            .block(Block::default().borders(Borders::ALL).title(title.as_str()));
+            .block(Block::default().borders(Borders::ALL).title(title));
 
             let terminal_size = tui.terminal.size().context(super::TerminalSizeSnafu)?;
 
             let action = Paragraph::new(Text {
                 lines: vec![
-                    "A: All zettels, C: config, E: edit, F: follow link, L: log out, Q: exit, S: search".into(),
+                    "A: All zettels, C: config, E: edit, F: follow link, J: journal, [/]: previous/next day, L: log out, P: aliases, Q: exit, S: search, T: tree, Z: sequence".into(),
                 ],
             });
             tui.terminal
@@ -111,6 +145,12 @@ impl Zettel {
                 })
                 .context(super::RenderFrameSnafu)?;
 
+            // wait for input, but reload the zettel if it gets changed somewhere else in the meantime
+            while !crossterm::event::poll(STORAGE_POLL_INTERVAL).context(super::EventSnafu)? {
+                if let Some(transition) = self.check_storage_events(tui)? {
+                    return Ok(Some(transition));
+                }
+            }
             let event = crossterm::event::read().context(super::EventSnafu)?;
             if let Event::Key(key_event) = event {
                 match key_event.code {
@@ -126,9 +166,17 @@ impl Zettel {
                         rendered_zettel.take();
                         continue;
                     }
+                    KeyCode::Char('j') => {
+                        return self.open_daily_note(tui, journal::today(), true);
+                    }
+                    KeyCode::Char('[') => return self.open_adjacent_daily_note(tui, false),
+                    KeyCode::Char(']') => return self.open_adjacent_daily_note(tui, true),
                     KeyCode::Char('l') => return Ok(Some(Transition::Logout)),
+                    KeyCode::Char('p') => return Ok(Some(Transition::EditAliases)),
                     KeyCode::Char('q') => return Ok(Some(Transition::Exit)),
                     KeyCode::Char('s') => return Ok(Some(Transition::Search)),
+                    KeyCode::Char('t') => return Ok(Some(Transition::Tree)),
+                    KeyCode::Char('z') => return Ok(Some(Transition::Sequence)),
                     _ => {}
                 }
 
@@ -137,22 +185,7 @@ impl Zettel {
                         filter.push(c);
                         if filter.len() == zettel.link_char_size {
                             if let Some(link) = zettel.links.get(filter) {
-                                let transition = if let Some(sys_page) =
-                                    super::utils::try_get_sys_page(tui, link)?
-                                {
-                                    Transition::SysPage(sys_page)
-                                } else {
-                                    let zettel = zettelkasten_shared::block_on(
-                                        tui.storage.get_zettel_by_url(self.user.id, link),
-                                    )
-                                    .context(super::DatabaseSnafu)?
-                                    .unwrap_or_else(|| storage::Zettel {
-                                        path: (*link).to_string(),
-                                        ..Default::default()
-                                    });
-                                    Transition::NavigateTo(zettel)
-                                };
-                                return Ok(Some(transition));
+                                return self.follow_link(tui, link).map(Some);
                             }
                             render_link_input = None;
                         }
@@ -168,6 +201,112 @@ impl Zettel {
     }
 }
 
+impl Zettel {
+    fn follow_link(&self, tui: &mut crate::Tui, link: &str) -> super::Result<Transition> {
+        if let Some(sys_page) = super::utils::try_get_sys_page(tui, &self.user, link)? {
+            return Ok(Transition::SysPage(sys_page));
+        }
+        if let Some(search) = links::search_target(link) {
+            let saved = zettelkasten_shared::block_on(links::search_for_link(
+                tui.storage.as_ref(),
+                self.user.id,
+                search,
+            ))
+            .context(super::DatabaseSnafu)?;
+            let report = super::report::Report::search(tui, Arc::clone(&self.user), &saved)?;
+            return Ok(Transition::SysPage(report.into()));
+        }
+        let zettel =
+            zettelkasten_shared::block_on(tui.storage.get_zettel_by_url(self.user.id, link))
+                .context(super::DatabaseSnafu)?;
+        Ok(match zettel {
+            Some(zettel) => Transition::NavigateTo(zettel),
+            None => Transition::Create(link.to_string()),
+        })
+    }
+
+    /// Navigate to the daily note of `date`. If `create` is set and the note does not exist yet, it is saved right away.
+    fn open_daily_note(
+        &self,
+        tui: &crate::Tui,
+        date: NaiveDate,
+        create: bool,
+    ) -> super::Result<Option<Transition>> {
+        let storage = tui.storage.as_ref();
+        let zettel = if create {
+            zettelkasten_shared::block_on(journal::create_daily_note(
+                storage,
+                &self.user,
+                &tui.user_config,
+                date,
+            ))
+        } else {
+            zettelkasten_shared::block_on(journal::daily_note(
+                storage,
+                &self.user,
+                &tui.user_config,
+                date,
+            ))
+        }
+        .context(super::DatabaseSnafu)?;
+        Ok(Some(Transition::NavigateTo(zettel)))
+    }
+
+    /// Navigate to the daily note of the day before or after this daily note, or before or after today if this is not
+    /// a daily note
+    fn open_adjacent_daily_note(
+        &self,
+        tui: &crate::Tui,
+        forward: bool,
+    ) -> super::Result<Option<Transition>> {
+        let date = journal::journal_date(&self.zettel.path).unwrap_or_else(journal::today);
+        let date = if forward {
+            date.succ_opt()
+        } else {
+            date.pred_opt()
+        };
+        date.map_or(Ok(None), |date| self.open_daily_note(tui, date, false))
+    }
+
+    fn check_storage_events(&self, tui: &mut crate::Tui) -> super::Result<Option<Transition>> {
+        if self.zettel.id == 0 {
+            return Ok(None);
+        }
+        let mut transition = None;
+        for event in tui.storage_events() {
+            match event {
+                storage::StorageEvent::ZettelChanged { user, id }
+                    if user == self.user.id && id == self.zettel.id =>
+                {
+                    transition =
+                        match zettelkasten_shared::block_on(tui.storage.get_zettel(user, id)) {
+                            Ok(zettel) => Some(Transition::Reload(zettel)),
+                            // deleted later in the same poll, see below
+                            Err(storage::Error::ZettelNotFound { .. }) => {
+                                Some(Transition::Reload(storage::Zettel {
+                                    id: 0,
+                                    ..self.zettel.clone()
+                                }))
+                            }
+                            Err(source) => return Err(super::Error::Database { source }),
+                        };
+                }
+                storage::StorageEvent::ZettelDeleted { user, id }
+                    if user == self.user.id && id == self.zettel.id =>
+                {
+                    // keep showing the zettel, editing it will create it again
+                    transition = Some(Transition::Reload(storage::Zettel {
+                        id: 0,
+                        ..self.zettel.clone()
+                    }));
+                }
+                _ => {}
+            }
+        }
+        Ok(transition)
+    }
+}
+
 pub(super) enum Transition {
     Edit,
     Exit,
@@ -175,6 +314,13 @@ pub(super) enum Transition {
     OpenConfig,
     Search,
     ZettelList,
+    EditAliases,
+    Tree,
+    Sequence,
     NavigateTo(storage::Zettel),
+    /// The zettel at this path does not exist yet
+    Create(String),
+    /// The zettel was changed outside of this view
+    Reload(storage::Zettel),
     SysPage(super::ViewLayer),
 }
@@ -0,0 +1,482 @@
+//! The JSON API under `/api/v1`, described by the `OpenAPI` document at `/api/v1/openapi.json`.
+//!
+//! Clients log in with `POST /api/v1/login` and send the token they get back as `Authorization: Bearer <token>`.
+//! Every error has a JSON body with a machine readable `error` code and a `message` for humans.
+
+use crate::routes::{self, App, Response};
+use tiny_http::Method;
+use zettelkasten_shared::storage;
+
+const OPENAPI: &str = include_str!("../openapi.json");
+/// Zettel bodies can be long, but not this long
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+#[derive(serde::Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+    message: &'a str,
+}
+
+struct Error {
+    status: u16,
+    code: &'static str,
+    message: String,
+}
+
+type Result<T = Response> = std::result::Result<T, Error>;
+
+impl Error {
+    fn new(status: u16, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn not_found() -> Self {
+        Self::new(404, "not_found", "There is no endpoint at this address")
+    }
+
+    fn zettel_not_found(path: &str) -> Self {
+        Self::new(
+            404,
+            "zettel_not_found",
+            format!("There is no zettel at {path:?}"),
+        )
+    }
+
+    fn method_not_allowed() -> Self {
+        Self::new(
+            405,
+            "method_not_allowed",
+            "This endpoint does not support this method",
+        )
+    }
+
+    fn into_response(self) -> Response {
+        Response::json(
+            self.status,
+            &ErrorBody {
+                error: self.code,
+                message: &self.message,
+            },
+        )
+    }
+}
+
+impl From<storage::Error> for Error {
+    fn from(e: storage::Error) -> Self {
+        match e {
+            storage::Error::ZettelNotFound { id } => Self::new(
+                404,
+                "zettel_not_found",
+                format!("There is no zettel with id {id}"),
+            ),
+            storage::Error::PathInUse { path } => Self::new(
+                409,
+                "path_in_use",
+                format!("{path:?} is already used by another zettel"),
+            ),
+            storage::Error::InvalidFolgezettel { folgezettel } => Self::new(
+                400,
+                "invalid_folgezettel",
+                format!("{folgezettel:?} is not a valid Folgezettel id"),
+            ),
+            storage::Error::InvalidQuery { source } => {
+                Self::new(400, "invalid_query", source.to_string())
+            }
+            storage::Error::InvalidRegex { source } => {
+                Self::new(400, "invalid_regex", source.to_string())
+            }
+            storage::Error::InvalidSearchOpts => {
+                Self::new(400, "invalid_search", "Nothing to search for")
+            }
+            e => Self::new(500, "storage", format!("Database error: {e:?}")),
+        }
+    }
+}
+
+impl From<routes::Error> for Error {
+    fn from(e: routes::Error) -> Self {
+        match e {
+            routes::Error::Database { source } => source.into(),
+            routes::Error::ReadRequest { .. } => Self::new(400, "invalid_request", e.to_string()),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct User<'a> {
+    id: storage::UserId,
+    name: &'a str,
+    is_admin: bool,
+}
+
+impl<'a> From<&'a storage::User> for User<'a> {
+    fn from(user: &'a storage::User) -> Self {
+        Self {
+            id: user.id,
+            name: &user.name,
+            is_admin: user.is_admin,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Login {
+    username: String,
+    password: String,
+}
+
+#[derive(serde::Serialize)]
+struct Token<'a> {
+    token: &'a str,
+    user: User<'a>,
+}
+
+#[derive(serde::Serialize)]
+struct Zettel<'a> {
+    id: storage::ZettelId,
+    path: &'a str,
+    body: &'a str,
+    folgezettel: Option<&'a str>,
+    aliases: &'a [String],
+}
+
+impl<'a> From<&'a storage::Zettel> for Zettel<'a> {
+    fn from(zettel: &'a storage::Zettel) -> Self {
+        Self {
+            id: zettel.id,
+            path: &zettel.path,
+            body: &zettel.body,
+            folgezettel: zettel.folgezettel.as_deref(),
+            aliases: &zettel.aliases,
+        }
+    }
+}
+
+/// The new content of a zettel. Everything but the path is replaced, the path is kept if it's missing.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ZettelInput {
+    path: Option<String>,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    folgezettel: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+impl ZettelInput {
+    fn apply(self, zettel: &mut storage::Zettel) {
+        if let Some(path) = self.path {
+            zettel.path = path;
+        }
+        zettel.body = self.body;
+        zettel.folgezettel = self.folgezettel;
+        zettel.aliases = self.aliases;
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ZettelHeader<'a> {
+    id: storage::ZettelId,
+    path: &'a str,
+    folgezettel: Option<&'a str>,
+    snippets: Vec<Snippet<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct Snippet<'a> {
+    text: &'a str,
+    /// The byte ranges of the matches in `text`, as `[start, end]`
+    matches: Vec<[usize; 2]>,
+}
+
+impl<'a> From<&'a storage::ZettelHeader> for ZettelHeader<'a> {
+    fn from(header: &'a storage::ZettelHeader) -> Self {
+        Self {
+            id: header.id,
+            path: &header.path,
+            folgezettel: header.folgezettel.as_deref(),
+            snippets: header
+                .snippets
+                .iter()
+                .map(|snippet| Snippet {
+                    text: &snippet.text,
+                    matches: snippet
+                        .matches
+                        .iter()
+                        .map(|range| [range.start, range.end])
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Answer a request for `/api/{route}`
+pub fn handle(app: &App, request: &mut tiny_http::Request, route: &str, query: &str) -> Response {
+    dispatch(app, request, route, query).unwrap_or_else(Error::into_response)
+}
+
+fn dispatch(app: &App, request: &mut tiny_http::Request, route: &str, query: &str) -> Result {
+    let Some(route) = route.strip_prefix("v1/") else {
+        return Err(Error::not_found());
+    };
+    let method = request.method().clone();
+    match (&method, route) {
+        (Method::Get, "openapi.json") => return Ok(Response::raw_json(OPENAPI)),
+        (Method::Post, "login") => return login(app, request),
+        (_, "openapi.json" | "login") => return Err(Error::method_not_allowed()),
+        _ => {}
+    }
+
+    let (user, token) = authenticate(app, request)?;
+    match (&method, route) {
+        (Method::Post, "logout") => {
+            if let Some(token) = token {
+                app.sessions.remove(&token);
+            }
+            Ok(Response::no_content())
+        }
+        (Method::Get, "me") => Ok(Response::json(200, &User::from(&user))),
+        (Method::Get, "config") => {
+            require_admin(&user)?;
+            let config = app
+                .system_config
+                .read()
+                .expect("System config lock is poisoned");
+            Ok(Response::json(200, &*config))
+        }
+        (Method::Put, "config") => {
+            require_admin(&user)?;
+            let config: storage::SystemConfig = read_json(request)?;
+            zettelkasten_shared::block_on(app.storage.update_config(&config))?;
+            let response = Response::json(200, &config);
+            *app.system_config
+                .write()
+                .expect("System config lock is poisoned") = config;
+            Ok(response)
+        }
+        (Method::Get, "zettels") => search(app, &user, query),
+        (Method::Post, "zettels") => {
+            let input: ZettelInput = read_json(request)?;
+            let path = input.path.clone().unwrap_or_default();
+            create_zettel(app, &user, input, &path)
+        }
+        (_, "logout" | "me" | "config" | "zettels") => Err(Error::method_not_allowed()),
+        (_, route) => {
+            if let Some(id) = route.strip_prefix("zettels/") {
+                let id: storage::ZettelId = id.parse().map_err(|_| Error::not_found())?;
+                zettel_by_id(app, request, &user, &method, id)
+            } else if let Some(path) = route.strip_prefix("paths/") {
+                let path = percent_encoding::percent_decode_str(path)
+                    .decode_utf8()
+                    .map_err(|_| Error::not_found())?;
+                zettel_by_path(app, request, &user, &method, &path)
+            } else {
+                Err(Error::not_found())
+            }
+        }
+    }
+}
+
+/// The user that sent `request`, and their token if they sent one
+fn authenticate(
+    app: &App,
+    request: &tiny_http::Request,
+) -> Result<(storage::User, Option<String>)> {
+    let token = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .map(|token| token.trim().to_owned());
+    if let Some(token) = token {
+        return match app.sessions.user(&token, app.storage.as_ref())? {
+            Some(user) => Ok((user, Some(token))),
+            None => Err(Error::new(
+                401,
+                "unauthorized",
+                "The token is invalid or expired",
+            )),
+        };
+    }
+    match app.auto_login()? {
+        Some(user) => Ok((user, None)),
+        None => Err(Error::new(
+            401,
+            "unauthorized",
+            "Log in at /api/v1/login and send the token as `Authorization: Bearer <token>`",
+        )),
+    }
+}
+
+fn require_admin(user: &storage::User) -> Result<()> {
+    if user.is_admin {
+        Ok(())
+    } else {
+        Err(Error::new(403, "forbidden", "Only admins can do this"))
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(request: &mut tiny_http::Request) -> Result<T> {
+    let body = routes::read_body(request, MAX_BODY_SIZE + 1)?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err(Error::new(
+            413,
+            "too_large",
+            format!("Request bodies can be at most {MAX_BODY_SIZE} bytes"),
+        ));
+    }
+    serde_json::from_slice(&body).map_err(|e| Error::new(400, "invalid_json", e.to_string()))
+}
+
+fn login(app: &App, request: &mut tiny_http::Request) -> Result {
+    let login: Login = read_json(request)?;
+    let user = zettelkasten_shared::block_on(app.storage.login(&login.username, &login.password))?
+        .ok_or_else(|| Error::new(401, "invalid_credentials", "Wrong username or password"))?;
+    let token = app.sessions.create(user.id);
+    Ok(Response::json(
+        200,
+        &Token {
+            token: &token,
+            user: User::from(&user),
+        },
+    ))
+}
+
+/// Search with the `query`, `mode`, `property` and `all` parameters of [`storage::SearchOpts`]. Without a query or
+/// properties, all zettels are listed.
+fn search(app: &App, user: &storage::User, query: &str) -> Result {
+    let params: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let mode = match routes::field(&params, "mode") {
+        "" => storage::SearchMode::default(),
+        mode => storage::SearchMode::ALL
+            .into_iter()
+            .find(|option| format!("{option:?}") == mode)
+            .ok_or_else(|| {
+                Error::new(400, "invalid_mode", format!("Unknown search mode {mode:?}"))
+            })?,
+    };
+    let properties = params
+        .iter()
+        .filter(|(key, _)| key == "property")
+        .map(|(_, value)| value.parse())
+        .collect::<std::result::Result<Vec<storage::PropertyFilter>, _>>()
+        .map_err(|message| Error::new(400, "invalid_property", message))?;
+    let text = routes::field(&params, "query");
+    let list_all = routes::field(&params, "all") == "true"
+        || (text.trim().is_empty() && properties.is_empty());
+    let zettels = zettelkasten_shared::block_on(app.storage.get_zettels(
+        user.id,
+        storage::SearchOpts {
+            query: text,
+            mode,
+            list_all,
+            properties: &properties,
+        },
+    ))?;
+    let zettels: Vec<ZettelHeader> = zettels.iter().map(ZettelHeader::from).collect();
+    Ok(Response::json(200, &zettels))
+}
+
+/// Fail if `path` is the path or an alias of another zettel than `id`
+fn ensure_path_is_free(
+    app: &App,
+    user: &storage::User,
+    path: &str,
+    id: storage::ZettelId,
+) -> Result<()> {
+    match zettelkasten_shared::block_on(app.storage.get_zettel_by_url(user.id, path))? {
+        Some(other) if other.id != id => Err(storage::Error::PathInUse {
+            path: path.to_owned(),
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+fn create_zettel(app: &App, user: &storage::User, input: ZettelInput, path: &str) -> Result {
+    if path.trim().is_empty() {
+        return Err(Error::new(400, "invalid_zettel", "A zettel needs a path"));
+    }
+    ensure_path_is_free(app, user, path, 0)?;
+    let mut zettel = storage::Zettel::default();
+    input.apply(&mut zettel);
+    path.clone_into(&mut zettel.path);
+    zettelkasten_shared::block_on(app.storage.update_zettel(user.id, &mut zettel))?;
+    Ok(Response::json(201, &Zettel::from(&zettel))
+        .with_location(format!("/api/v1/zettels/{}", zettel.id)))
+}
+
+fn update_zettel(
+    app: &App,
+    user: &storage::User,
+    mut zettel: storage::Zettel,
+    input: ZettelInput,
+) -> Result {
+    input.apply(&mut zettel);
+    ensure_path_is_free(app, user, &zettel.path, zettel.id)?;
+    zettelkasten_shared::block_on(app.storage.update_zettel(user.id, &mut zettel))?;
+    Ok(Response::json(200, &Zettel::from(&zettel)))
+}
+
+fn zettel_by_id(
+    app: &App,
+    request: &mut tiny_http::Request,
+    user: &storage::User,
+    method: &Method,
+    id: storage::ZettelId,
+) -> Result {
+    match method {
+        Method::Get => {
+            let zettel = zettelkasten_shared::block_on(app.storage.get_zettel(user.id, id))?;
+            Ok(Response::json(200, &Zettel::from(&zettel)))
+        }
+        Method::Put => {
+            let input: ZettelInput = read_json(request)?;
+            let zettel = zettelkasten_shared::block_on(app.storage.get_zettel(user.id, id))?;
+            update_zettel(app, user, zettel, input)
+        }
+        Method::Delete => {
+            zettelkasten_shared::block_on(app.storage.delete_zettel(user.id, id))?;
+            Ok(Response::no_content())
+        }
+        _ => Err(Error::method_not_allowed()),
+    }
+}
+
+/// Like [`zettel_by_id`], but `PUT` creates the zettel if there is none at `path`
+fn zettel_by_path(
+    app: &App,
+    request: &mut tiny_http::Request,
+    user: &storage::User,
+    method: &Method,
+    path: &str,
+) -> Result {
+    let zettel = zettelkasten_shared::block_on(app.storage.get_zettel_by_url(user.id, path))?;
+    match (method, zettel) {
+        (Method::Get, Some(zettel)) => Ok(Response::json(200, &Zettel::from(&zettel))),
+        (Method::Put, Some(zettel)) => {
+            let input: ZettelInput = read_json(request)?;
+            update_zettel(app, user, zettel, input)
+        }
+        (Method::Put, None) => {
+            let input: ZettelInput = read_json(request)?;
+            let path = input.path.clone().unwrap_or_else(|| path.to_owned());
+            create_zettel(app, user, input, &path)
+        }
+        (Method::Delete, Some(zettel)) => {
+            zettelkasten_shared::block_on(app.storage.delete_zettel(user.id, zettel.id))?;
+            Ok(Response::no_content())
+        }
+        (Method::Get | Method::Delete, None) => Err(Error::zettel_not_found(path)),
+        _ => Err(Error::method_not_allowed()),
+    }
+}
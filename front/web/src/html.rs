@@ -0,0 +1,321 @@
+//! The HTML of every page. Everything that comes from a user goes through [`escape`].
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::{fmt::Write, ops::Range};
+use zettelkasten_shared::{frontmatter, links, storage};
+
+/// The characters that are encoded in a zettel path in a URL. Namespace separators stay readable.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+const STYLE: &str = "
+body { font-family: sans-serif; max-width: 50em; margin: 0 auto; padding: 0 1em; }
+nav { display: flex; gap: 1em; align-items: center; border-bottom: 1px solid #ccc; padding: 0.5em 0; }
+nav form { margin: 0; }
+nav .user { margin-left: auto; color: #666; }
+.zettel { white-space: pre-wrap; }
+.properties { color: #666; }
+.properties dt { font-weight: bold; float: left; margin-right: 0.5em; }
+.sys { color: #666; }
+.error { color: #c00; }
+.snippet { color: #444; }
+mark { font-weight: bold; }
+";
+
+pub enum Form {
+    Login,
+    Register,
+}
+
+/// `text` with the characters that have a meaning in HTML replaced by entities
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The URL of the zettel at `path`
+pub fn zettel_href(path: &str) -> String {
+    format!("/zettel/{}", utf8_percent_encode(path, PATH))
+}
+
+fn search_href(params: &[(&str, &str)]) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    format!("/search?{query}")
+}
+
+/// A whole page, with the navigation for `user` if someone is logged in
+pub fn page(title: &str, user: Option<&storage::User>, content: &str) -> String {
+    let nav = match user {
+        Some(user) => format!(
+            r#"<nav><a href="/">All zettels</a><a href="/search">Search</a><span class="user">{}</span><form method="post" action="/logout"><button>Log out</button></form></nav>"#,
+            escape(&user.name)
+        ),
+        None => String::new(),
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{} - zettelkasten</title>
+<style>{STYLE}</style>
+</head>
+<body>
+{nav}
+{content}
+</body>
+</html>
+"#,
+        escape(title)
+    )
+}
+
+pub fn login(form: &Form, error: Option<&str>, can_register: bool) -> String {
+    let (title, action, other) = match form {
+        Form::Login => (
+            "Log in",
+            "/login",
+            can_register.then_some(r#"<p>No account yet? <a href="/register">Register</a></p>"#),
+        ),
+        Form::Register => (
+            "Register",
+            "/register",
+            Some(r#"<p>Already registered? <a href="/login">Log in</a></p>"#),
+        ),
+    };
+    let error = error.map_or(String::new(), |error| {
+        format!(r#"<p class="error">{}</p>"#, escape(error))
+    });
+    format!(
+        r#"<h1>{title}</h1>
+{error}
+<form method="post" action="{action}">
+<p><label>Username <input name="username" autofocus required></label></p>
+<p><label>Password <input name="password" type="password" required></label></p>
+<p><button>{title}</button></p>
+</form>
+{}"#,
+        other.unwrap_or_default()
+    )
+}
+
+/// A list of zettels, with the snippets of a search
+pub fn zettel_list(zettels: &[storage::ZettelHeader]) -> String {
+    if zettels.is_empty() {
+        return "<p>No zettels found</p>".to_owned();
+    }
+    let mut html = String::from("<ul>");
+    for zettel in zettels {
+        write!(
+            html,
+            r#"<li><a href="{}">{}</a>"#,
+            escape(&zettel_href(&zettel.path)),
+            escape(&zettel.path)
+        )
+        .unwrap();
+        for snippet in &zettel.snippets {
+            write!(
+                html,
+                r#" <span class="snippet">{}</span>"#,
+                highlight(&snippet.text, &snippet.matches)
+            )
+            .unwrap();
+        }
+        html.push_str("</li>");
+    }
+    html.push_str("</ul>");
+    html
+}
+
+/// `text` with the byte `ranges` in `<mark>`. `ranges` must be sorted and must not overlap.
+fn highlight(text: &str, ranges: &[Range<usize>]) -> String {
+    let mut html = String::new();
+    let mut start = 0;
+    for range in ranges {
+        html.push_str(&escape(&text[start..range.start]));
+        write!(html, "<mark>{}</mark>", escape(&text[range.clone()])).unwrap();
+        start = range.end;
+    }
+    html.push_str(&escape(&text[start..]));
+    html
+}
+
+pub fn zettel(zettel: &storage::Zettel) -> String {
+    let mut html = format!("<h1>{}</h1>\n", escape(&zettel.path));
+    if !zettel.aliases.is_empty() {
+        writeln!(
+            html,
+            "<p class=\"properties\">Also known as {}</p>",
+            escape(&zettel.aliases.join(", "))
+        )
+        .unwrap();
+    }
+
+    let front_matter = frontmatter::parse(&zettel.body);
+    let content = front_matter
+        .as_ref()
+        .map_or(zettel.body.as_str(), |f| f.content);
+    if let Some(front_matter) = &front_matter {
+        html.push_str(&properties(&front_matter.properties));
+    }
+
+    html.push_str(r#"<div class="zettel">"#);
+    for line in content.lines() {
+        let mut start = 0;
+        for link in links::parse(line) {
+            html.push_str(&escape(&line[start..link.range.start]));
+            // the text without the brackets
+            let text = escape(&line[link.text.start + 1..link.text.end - 1]);
+            if links::is_sys_page(link.target) {
+                write!(
+                    html,
+                    r#"<span class="sys" title="{} is only available in the terminal">{text}</span>"#,
+                    escape(link.target)
+                ).unwrap();
+            } else {
+                let href = match links::search_target(link.target) {
+                    Some(search) => search_href(&[("link", search)]),
+                    None => zettel_href(link.target),
+                };
+                write!(html, r#"<a href="{}">{text}</a>"#, escape(&href)).unwrap();
+            }
+            start = link.range.end;
+        }
+        html.push_str(&escape(&line[start..]));
+        html.push('\n');
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+/// The front matter, with the values of the same key on one line
+fn properties(properties: &[(&str, &str)]) -> String {
+    let mut keys: Vec<(&str, Vec<&str>)> = Vec::new();
+    for &(key, value) in properties {
+        match keys.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            Some((_, values)) => values.push(value),
+            None => keys.push((key, vec![value])),
+        }
+    }
+    let mut html = String::from(r#"<dl class="properties">"#);
+    for (key, values) in keys {
+        write!(
+            html,
+            "<dt>{}:</dt><dd>{}</dd>",
+            escape(key),
+            escape(&values.join(", "))
+        )
+        .unwrap();
+    }
+    html.push_str("</dl>\n");
+    html
+}
+
+/// The page of a link to a zettel that does not exist yet
+pub fn missing(path: &str) -> String {
+    format!(
+        "<h1>{}</h1>\n<p>This zettel does not exist yet, create it in the terminal.</p>\n",
+        escape(path)
+    )
+}
+
+/// The search form, followed by the results or the reason why the search is invalid
+pub fn search(
+    title: &str,
+    query: &str,
+    mode: storage::SearchMode,
+    results: Option<Result<&[storage::ZettelHeader], &str>>,
+) -> String {
+    let mut options = String::new();
+    for option in storage::SearchMode::ALL {
+        write!(
+            options,
+            r#"<option value="{option:?}"{}>{}</option>"#,
+            if option == mode { " selected" } else { "" },
+            option.name()
+        )
+        .unwrap();
+    }
+    let results = match results {
+        Some(Ok(zettels)) => zettel_list(zettels),
+        Some(Err(error)) => format!(r#"<p class="error">{}</p>"#, escape(error)),
+        None => String::new(),
+    };
+    format!(
+        r#"<h1>{}</h1>
+<form method="get" action="/search">
+<input name="q" value="{}" autofocus>
+<select name="mode">{options}</select>
+<button>Search</button>
+</form>
+{results}"#,
+        escape(title),
+        escape(query)
+    )
+}
+
+pub fn not_found() -> String {
+    "<h1>Not found</h1>\n<p>There is no page at this address.</p>\n".to_owned()
+}
+
+pub fn error(message: &str) -> String {
+    format!(
+        "<h1>Something went wrong</h1>\n<p class=\"error\">{}</p>\n",
+        escape(message)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"<a href="x" title='y'>Tom & Jerry</a>"#),
+            "&lt;a href=&quot;x&quot; title=&#39;y&#39;&gt;Tom &amp; Jerry&lt;/a&gt;"
+        );
+        assert_eq!(escape("Nothing to see"), "Nothing to see");
+    }
+
+    #[test]
+    fn test_zettel() {
+        let html = zettel(&storage::Zettel {
+            path: "<b>notes</b>".to_owned(),
+            body: "<script>alert(1)</script>\n[x](javascript:document.cookie) and [open](search:tag:question)"
+                .to_owned(),
+            aliases: vec!["a&b".to_owned()],
+            ..Default::default()
+        });
+        assert!(html.starts_with("<h1>&lt;b&gt;notes&lt;/b&gt;</h1>\n"));
+        assert!(html.contains("Also known as a&amp;b"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        // link targets are always paths on this server
+        assert!(!html.contains(r#"href="javascript:"#));
+        assert!(html.contains(r#"<a href="/zettel/javascript:document.cookie">x</a>"#));
+        assert!(html.contains(r#"<a href="/search?link=tag%3Aquestion">open</a>"#));
+    }
+}
@@ -1 +1,141 @@
+#![deny(clippy::perf)]
+#![warn(clippy::pedantic)]
 
+mod api;
+mod html;
+mod routes;
+mod session;
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+use zettelkasten_shared::{storage, Front};
+
+/// Serves the zettelkasten as server rendered HTML pages, and as a JSON API under `/api/v1`
+pub struct Web;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The address the HTTP server listens on. Only the local machine can connect by default.
+    pub address: IpAddr,
+    /// Port 0 picks any free port, see [`Server::address`]
+    pub port: u16,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+        }
+    }
+}
+
+impl Front for Web {
+    type Config = Config;
+
+    fn run(
+        config: Self::Config,
+        system_config: storage::SystemConfig,
+        storage: Arc<dyn storage::Storage>,
+    ) {
+        let server = Server::start(&config, system_config, storage).unwrap_or_else(|e| {
+            panic!(
+                "Could not listen on {}:{}: {e}",
+                config.address, config.port
+            )
+        });
+        println!("Listening on http://{}", server.address());
+        server.join();
+    }
+}
+
+/// A web front that handles requests on background threads
+pub struct Server {
+    http: Arc<tiny_http::Server>,
+    address: SocketAddr,
+    stopping: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Listen on the address of `config` and handle requests until [`Server::stop`] is called
+    ///
+    /// # Errors
+    ///
+    /// When the server can't listen on the address, e.g. because the port is in use
+    pub fn start(
+        config: &Config,
+        system_config: storage::SystemConfig,
+        storage: Arc<dyn storage::Storage>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let http = Arc::new(tiny_http::Server::http((config.address, config.port))?);
+        let address = http
+            .server_addr()
+            .to_ip()
+            .ok_or("The server does not listen on an IP address")?;
+        let stopping = Arc::new(AtomicBool::new(false));
+        let app = Arc::new(routes::App::new(system_config, storage));
+        // every request blocks its thread while it waits for the storage, so handle a few at once
+        let workers = std::thread::available_parallelism().map_or(4, NonZeroUsize::get);
+        let workers = (0..workers)
+            .map(|_| {
+                let http = Arc::clone(&http);
+                let stopping = Arc::clone(&stopping);
+                let app = Arc::clone(&app);
+                std::thread::spawn(move || loop {
+                    match http.recv() {
+                        Ok(request) => app.respond(request),
+                        Err(_) if stopping.load(Ordering::SeqCst) => break,
+                        // a client that went away, the next one might not
+                        Err(_) => {}
+                    }
+                })
+            })
+            .collect();
+        Ok(Self {
+            http,
+            address,
+            stopping,
+            workers,
+        })
+    }
+
+    /// The address the server listens on
+    #[must_use]
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Block until all workers are done, which is never unless one of them panics
+    ///
+    /// # Panics
+    ///
+    /// When a worker panicked while handling a request
+    pub fn join(self) {
+        for worker in self.workers {
+            worker.join().expect("A web worker panicked");
+        }
+    }
+
+    /// Finish the requests that are being handled and stop
+    ///
+    /// # Panics
+    ///
+    /// When a worker panicked while handling a request
+    pub fn stop(self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // every call wakes up a single worker
+        for _ in &self.workers {
+            self.http.unblock();
+        }
+        self.join();
+    }
+}
@@ -0,0 +1,406 @@
+use crate::{
+    api,
+    html::{self, Form},
+    session::{self, Sessions},
+};
+use snafu::{ResultExt, Snafu};
+use std::{
+    io::Read,
+    sync::{Arc, RwLock},
+};
+use tiny_http::{Header, Method};
+use zettelkasten_shared::{links, storage};
+
+/// Form bodies are small, anything bigger is cut off
+const MAX_FORM_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Database error: {source:?}"))]
+    Database { source: storage::Error },
+    #[snafu(display("Could not read the request"))]
+    ReadRequest { source: std::io::Error },
+}
+
+pub type Result<T = Response> = std::result::Result<T, Error>;
+
+pub struct Response {
+    status: u16,
+    content_type: &'static str,
+    /// Where the browser should go instead, for redirects
+    location: Option<String>,
+    /// The value of the `Set-Cookie` header
+    cookie: Option<String>,
+    body: String,
+}
+
+impl Response {
+    fn page(status: u16, body: String) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8",
+            location: None,
+            cookie: None,
+            body,
+        }
+    }
+
+    /// Send the browser to `location`. Uses `303 See Other` so forms that were posted are loaded with `GET`.
+    fn redirect(location: impl Into<String>) -> Self {
+        Self {
+            status: 303,
+            content_type: "text/html; charset=utf-8",
+            location: Some(location.into()),
+            cookie: None,
+            body: String::new(),
+        }
+    }
+
+    pub fn json(status: u16, value: &impl serde::Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            location: None,
+            cookie: None,
+            body: serde_json::to_string(value).expect("API responses can always be serialized"),
+        }
+    }
+
+    /// A JSON document that is already serialized
+    pub fn raw_json(body: &str) -> Self {
+        Self {
+            body: body.to_owned(),
+            ..Self::json(200, &())
+        }
+    }
+
+    pub fn no_content() -> Self {
+        Self {
+            status: 204,
+            content_type: "text/plain",
+            location: None,
+            cookie: None,
+            body: String::new(),
+        }
+    }
+
+    /// Point at a resource that was created
+    pub fn with_location(self, location: String) -> Self {
+        Self {
+            location: Some(location),
+            ..self
+        }
+    }
+
+    fn with_cookie(self, cookie: String) -> Self {
+        Self {
+            cookie: Some(cookie),
+            ..self
+        }
+    }
+
+    fn into_tiny(self) -> tiny_http::Response<std::io::Cursor<Vec<u8>>> {
+        let mut response = tiny_http::Response::from_string(self.body)
+            .with_status_code(self.status)
+            .with_header(header("Content-Type", self.content_type));
+        if let Some(location) = self.location {
+            response.add_header(header("Location", &location));
+        }
+        if let Some(cookie) = self.cookie {
+            response.add_header(header("Set-Cookie", &cookie));
+        }
+        response
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("Header values are ASCII")
+}
+
+/// Everything a request needs, shared by all workers
+pub struct App {
+    /// Admins can change it through the API
+    pub(crate) system_config: RwLock<storage::SystemConfig>,
+    pub(crate) storage: Arc<dyn storage::Storage>,
+    /// The sessions of the HTML pages and the tokens of the API
+    pub(crate) sessions: Sessions,
+}
+
+impl App {
+    pub fn new(system_config: storage::SystemConfig, storage: Arc<dyn storage::Storage>) -> Self {
+        Self {
+            system_config: RwLock::new(system_config),
+            storage,
+            sessions: Sessions::default(),
+        }
+    }
+
+    pub(crate) fn user_mode(&self) -> storage::UserMode {
+        self.system_config
+            .read()
+            .expect("System config lock is poisoned")
+            .user_mode
+    }
+
+    /// The only user, if they are logged in without a password
+    pub(crate) fn auto_login(&self) -> Result<Option<storage::User>> {
+        match self.user_mode() {
+            storage::UserMode::SingleUserAutoLogin => {
+                zettelkasten_shared::block_on(self.storage.login_single_user())
+                    .map(Some)
+                    .context(DatabaseSnafu)
+            }
+            storage::UserMode::SingleUserManualLogin | storage::UserMode::MultiUser => Ok(None),
+        }
+    }
+
+    pub fn respond(&self, mut request: tiny_http::Request) {
+        let response = self.handle(&mut request).unwrap_or_else(|e| {
+            Response::page(500, html::page("Error", None, &html::error(&e.to_string())))
+        });
+        // the browser might be gone already, there's nobody left to tell about that
+        drop(request.respond(response.into_tiny()));
+    }
+
+    fn handle(&self, request: &mut tiny_http::Request) -> Result {
+        let url = request.url().to_owned();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        if let Some(route) = path.strip_prefix("/api/") {
+            return Ok(api::handle(self, request, route, query));
+        }
+        let token = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Cookie"))
+            .and_then(|header| session::token_from_cookies(header.value.as_str()))
+            .map(str::to_owned);
+
+        match (request.method(), path) {
+            (Method::Get, "/login") => {
+                return Ok(Response::page(200, self.login_page(&Form::Login, None)?));
+            }
+            (Method::Post, "/login") => return self.login(&read_form(request)?),
+            (Method::Get, "/register") => {
+                return Ok(Response::page(200, self.login_page(&Form::Register, None)?));
+            }
+            (Method::Post, "/register") => return self.register(&read_form(request)?),
+            (Method::Post, "/logout") => {
+                if let Some(token) = &token {
+                    self.sessions.remove(token);
+                }
+                return Ok(Response::redirect("/login").with_cookie(session::clear_cookie()));
+            }
+            _ => {}
+        }
+
+        let user = match &token {
+            Some(token) => self
+                .sessions
+                .user(token, self.storage.as_ref())
+                .context(DatabaseSnafu)?,
+            None => None,
+        };
+        let (user, cookie) = match user {
+            Some(user) => (user, None),
+            None => match self.auto_login()? {
+                Some(user) => {
+                    let token = self.sessions.create(user.id);
+                    (user, Some(session::set_cookie(&token)))
+                }
+                None => return Ok(Response::redirect("/login")),
+            },
+        };
+
+        let response = match (request.method(), path) {
+            (Method::Get, "/") => self.index(&user)?,
+            (Method::Get, "/search") => self.search(&user, query)?,
+            (Method::Get, _) => match path.strip_prefix("/zettel/") {
+                Some(zettel_path) => {
+                    let zettel_path = percent_encoding::percent_decode_str(zettel_path)
+                        .decode_utf8_lossy()
+                        .into_owned();
+                    self.zettel(&user, &zettel_path)?
+                }
+                None => Response::page(
+                    404,
+                    html::page("Not found", Some(&user), &html::not_found()),
+                ),
+            },
+            _ => Response::page(
+                404,
+                html::page("Not found", Some(&user), &html::not_found()),
+            ),
+        };
+        Ok(match cookie {
+            Some(cookie) => response.with_cookie(cookie),
+            None => response,
+        })
+    }
+
+    fn can_register(&self) -> Result<bool> {
+        if let storage::UserMode::MultiUser = self.user_mode() {
+            Ok(true)
+        } else {
+            let user_count =
+                zettelkasten_shared::block_on(self.storage.user_count()).context(DatabaseSnafu)?;
+            Ok(user_count == 0)
+        }
+    }
+
+    fn login_page(&self, form: &Form, error: Option<&str>) -> Result<String> {
+        let title = match form {
+            Form::Login => "Log in",
+            Form::Register => "Register",
+        };
+        Ok(html::page(
+            title,
+            None,
+            &html::login(form, error, self.can_register()?),
+        ))
+    }
+
+    fn login(&self, form: &[(String, String)]) -> Result {
+        let (username, password) = (field(form, "username"), field(form, "password"));
+        let user = zettelkasten_shared::block_on(self.storage.login(username, password))
+            .context(DatabaseSnafu)?;
+        Ok(match user {
+            Some(user) => {
+                let token = self.sessions.create(user.id);
+                Response::redirect("/").with_cookie(session::set_cookie(&token))
+            }
+            None => Response::page(
+                401,
+                self.login_page(&Form::Login, Some("Wrong username or password"))?,
+            ),
+        })
+    }
+
+    fn register(&self, form: &[(String, String)]) -> Result {
+        if !self.can_register()? {
+            return Ok(Response::page(
+                403,
+                self.login_page(&Form::Register, Some("Registration is closed"))?,
+            ));
+        }
+        let (username, password) = (field(form, "username"), field(form, "password"));
+        if username.trim().is_empty() || password.is_empty() {
+            return Ok(Response::page(
+                400,
+                self.login_page(&Form::Register, Some("Username and password are required"))?,
+            ));
+        }
+        match zettelkasten_shared::block_on(self.storage.register(username.trim(), password)) {
+            Ok(user) => {
+                let token = self.sessions.create(user.id);
+                Ok(Response::redirect("/").with_cookie(session::set_cookie(&token)))
+            }
+            Err(storage::Error::UserAlreadyExists) => Ok(Response::page(
+                409,
+                self.login_page(&Form::Register, Some("This username is taken"))?,
+            )),
+            Err(source) => Err(Error::Database { source }),
+        }
+    }
+
+    /// All zettels of `user`
+    fn index(&self, user: &storage::User) -> Result {
+        let zettels = zettelkasten_shared::block_on(self.storage.get_zettels(
+            user.id,
+            storage::SearchOpts {
+                list_all: true,
+                ..Default::default()
+            },
+        ))
+        .context(DatabaseSnafu)?;
+        let content = format!("<h1>All zettels</h1>\n{}", html::zettel_list(&zettels));
+        Ok(Response::page(
+            200,
+            html::page("All zettels", Some(user), &content),
+        ))
+    }
+
+    fn zettel(&self, user: &storage::User, path: &str) -> Result {
+        let zettel = zettelkasten_shared::block_on(self.storage.get_zettel_by_url(user.id, path))
+            .context(DatabaseSnafu)?;
+        Ok(match zettel {
+            Some(zettel) => Response::page(
+                200,
+                html::page(&zettel.path, Some(user), &html::zettel(&zettel)),
+            ),
+            None => Response::page(404, html::page(path, Some(user), &html::missing(path))),
+        })
+    }
+
+    /// The search page. `?link=` shows the results of a `search:` link, see [`links::search_for_link`].
+    fn search(&self, user: &storage::User, query: &str) -> Result {
+        let params: Vec<(String, String)> = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect();
+        let saved = match params.iter().find(|(key, _)| key == "link") {
+            Some((_, link)) => zettelkasten_shared::block_on(links::search_for_link(
+                self.storage.as_ref(),
+                user.id,
+                link,
+            ))
+            .context(DatabaseSnafu)?,
+            None => storage::SavedSearch {
+                name: "Search".to_owned(),
+                query: field(&params, "q").to_owned(),
+                mode: storage::SearchMode::ALL
+                    .into_iter()
+                    .find(|mode| format!("{mode:?}") == field(&params, "mode"))
+                    .unwrap_or_default(),
+                properties: Vec::new(),
+            },
+        };
+
+        let results = if saved.query.is_empty() && saved.properties.is_empty() {
+            None
+        } else {
+            match zettelkasten_shared::block_on(self.storage.get_zettels(user.id, saved.opts())) {
+                Ok(zettels) => Some(Ok(zettels)),
+                // the same messages as the terminal shows while typing a search
+                Err(storage::Error::InvalidQuery { source }) => Some(Err(source.to_string())),
+                Err(storage::Error::InvalidRegex { source }) => Some(Err(source.to_string())),
+                Err(source) => return Err(Error::Database { source }),
+            }
+        };
+        let results = match &results {
+            Some(Ok(zettels)) => Some(Ok(zettels.as_slice())),
+            Some(Err(error)) => Some(Err(error.as_str())),
+            None => None,
+        };
+        Ok(Response::page(
+            200,
+            html::page(
+                &saved.name,
+                Some(user),
+                &html::search(&saved.name, &saved.query, saved.mode, results),
+            ),
+        ))
+    }
+}
+
+/// The `application/x-www-form-urlencoded` body of `request`
+fn read_form(request: &mut tiny_http::Request) -> Result<Vec<(String, String)>> {
+    let body = read_body(request, MAX_FORM_SIZE)?;
+    Ok(form_urlencoded::parse(&body).into_owned().collect())
+}
+
+/// The body of `request`, cut off after `limit` bytes
+pub(crate) fn read_body(request: &mut tiny_http::Request, limit: u64) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(limit)
+        .read_to_end(&mut body)
+        .context(ReadRequestSnafu)?;
+    Ok(body)
+}
+
+/// The value of the form field `name`, or an empty string if it's missing
+pub(crate) fn field<'a>(form: &'a [(String, String)], name: &str) -> &'a str {
+    form.iter()
+        .find(|(key, _)| key == name)
+        .map_or("", |(_, value)| value.as_str())
+}
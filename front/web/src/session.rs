@@ -0,0 +1,168 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use zettelkasten_shared::storage;
+
+/// The cookie that holds the session token
+pub const COOKIE: &str = "zettelkasten_session";
+/// Sessions that are not used for this long end
+pub const IDLE_TIMEOUT: Duration = Duration::from_hours(24);
+/// Sessions end this long after the login, however often they are used
+pub const MAX_AGE: Duration = Duration::from_hours(30 * 24);
+
+/// The users that are logged in, by session token. Sessions end after [`IDLE_TIMEOUT`] or [`MAX_AGE`], and restarting
+/// the server logs everyone out.
+///
+/// A session only holds the id of its user, who is loaded again for every request, so changes to the user like losing
+/// admin rights apply right away.
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+struct Session {
+    user: storage::UserId,
+    created: Instant,
+    last_used: Instant,
+}
+
+impl Session {
+    fn is_expired(&self, now: Instant) -> bool {
+        now.duration_since(self.last_used) >= IDLE_TIMEOUT
+            || now.duration_since(self.created) >= MAX_AGE
+    }
+}
+
+impl Sessions {
+    /// Start a session for `user`, returning its token
+    pub fn create(&self, user: storage::UserId) -> String {
+        self.create_at(user, Instant::now())
+    }
+
+    /// The user of the session `token`, loaded from `storage`, or `None` if there is no such session. Sessions of
+    /// users that no longer exist are removed.
+    ///
+    /// # Errors
+    ///
+    /// When the user can't be loaded
+    pub fn user(
+        &self,
+        token: &str,
+        storage: &dyn storage::Storage,
+    ) -> Result<Option<storage::User>, storage::Error> {
+        let Some(id) = self.get_at(token, Instant::now()) else {
+            return Ok(None);
+        };
+        let user = zettelkasten_shared::block_on(storage.get_user(id))?;
+        if user.is_none() {
+            self.remove(token);
+        }
+        Ok(user)
+    }
+
+    pub fn remove(&self, token: &str) {
+        self.lock().remove(token);
+    }
+
+    fn create_at(&self, user: storage::UserId, now: Instant) -> String {
+        let mut bytes = [0; 32];
+        getrandom::getrandom(&mut bytes).expect("Could not generate a session token");
+        let mut token = String::with_capacity(bytes.len() * 2);
+        for byte in bytes {
+            write!(token, "{byte:02x}").unwrap();
+        }
+        let mut sessions = self.lock();
+        // expired sessions are only noticed when they are used, so the ones that are never used again go here
+        sessions.retain(|_, session| !session.is_expired(now));
+        sessions.insert(
+            token.clone(),
+            Session {
+                user,
+                created: now,
+                last_used: now,
+            },
+        );
+        token
+    }
+
+    /// The user id of the session `token`, which counts as a use of it
+    fn get_at(&self, token: &str, now: Instant) -> Option<storage::UserId> {
+        let mut sessions = self.lock();
+        let session = sessions.get_mut(token)?;
+        if session.is_expired(now) {
+            sessions.remove(token);
+            return None;
+        }
+        session.last_used = now;
+        Some(session.user)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Session>> {
+        self.sessions.lock().expect("Session mutex is poisoned")
+    }
+}
+
+/// The session token in a `Cookie` header
+pub fn token_from_cookies(cookies: &str) -> Option<&str> {
+    cookies.split(';').find_map(|cookie| {
+        let (name, value) = cookie.split_once('=')?;
+        (name.trim() == COOKIE).then(|| value.trim())
+    })
+}
+
+/// The `Set-Cookie` header value that stores `token` in the browser
+pub fn set_cookie(token: &str) -> String {
+    format!("{COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax")
+}
+
+/// The `Set-Cookie` header value that removes the session token from the browser
+pub fn clear_cookie() -> String {
+    format!("{COOKIE}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_timeout() {
+        let sessions = Sessions::default();
+        let start = Instant::now();
+        let token = sessions.create_at(1, start);
+        let used = start + IDLE_TIMEOUT / 2;
+        assert_eq!(sessions.get_at(&token, used), Some(1));
+        // using a session keeps it alive
+        assert_eq!(sessions.get_at(&token, used + IDLE_TIMEOUT / 2), Some(1));
+        assert_eq!(sessions.get_at(&token, used + IDLE_TIMEOUT * 2), None);
+        // and expired sessions are gone
+        assert_eq!(sessions.get_at(&token, used), None);
+    }
+
+    #[test]
+    fn test_max_age() {
+        let sessions = Sessions::default();
+        let start = Instant::now();
+        let token = sessions.create_at(1, start);
+        let mut now = start;
+        while now + IDLE_TIMEOUT / 2 < start + MAX_AGE {
+            now += IDLE_TIMEOUT / 2;
+            assert_eq!(sessions.get_at(&token, now), Some(1));
+        }
+        assert_eq!(sessions.get_at(&token, start + MAX_AGE), None);
+    }
+
+    #[test]
+    fn test_create_removes_expired() {
+        let sessions = Sessions::default();
+        let start = Instant::now();
+        sessions.create_at(1, start);
+        let token = sessions.create_at(2, start + IDLE_TIMEOUT);
+        assert_eq!(sessions.lock().len(), 1);
+        assert_eq!(sessions.get_at(&token, start + IDLE_TIMEOUT), Some(2));
+        sessions.remove(&token);
+        assert_eq!(sessions.get_at(&token, start + IDLE_TIMEOUT), None);
+    }
+}
@@ -0,0 +1,409 @@
+use serde_json::{json, Value};
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+};
+use zettelkasten_shared::storage::{ConnectableStorage, Storage, SystemConfig, UserMode};
+use zettelkasten_web::{Config, Server};
+
+struct Api {
+    server: Server,
+    address: SocketAddr,
+    url: String,
+    _dir: tempfile::TempDir,
+}
+
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn error(&self) -> &str {
+        self.body["error"].as_str().expect("Not an error body")
+    }
+}
+
+impl Api {
+    /// Start a server on a fresh database with the admin `admin` and the user `other`
+    fn start(user_mode: UserMode) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let url = dir.path().join("zettelkasten.db").display().to_string();
+        let storage = zettelkasten_shared::block_on(async {
+            let (storage, _) = zettelkasten_sqlite::Connection::connect(url.clone())
+                .await
+                .unwrap();
+            storage.register("admin", "admin").await.unwrap();
+            storage.register("other", "other").await.unwrap();
+            storage
+        });
+        let server = Server::start(
+            &Config {
+                port: 0,
+                ..Config::default()
+            },
+            SystemConfig {
+                user_mode,
+                ..SystemConfig::default()
+            },
+            Arc::new(storage),
+        )
+        .unwrap();
+        Self {
+            address: server.address(),
+            server,
+            url,
+            _dir: dir,
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, token: Option<&str>, body: Option<Value>) -> Reply {
+        let mut stream = TcpStream::connect(self.address).unwrap();
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let mut request = format!(
+            "{method} /api/v1/{path} HTTP/1.0\r\nHost: {}\r\nContent-Length: {}\r\n",
+            self.address,
+            body.len()
+        );
+        if let Some(token) = token {
+            request += &format!("Authorization: Bearer {token}\r\n");
+        }
+        request += "\r\n";
+        request += &body;
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().split(' ').nth(1).unwrap();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+            .collect();
+        Reply {
+            status: status.parse().unwrap(),
+            headers,
+            body: if body.is_empty() {
+                Value::Null
+            } else {
+                serde_json::from_str(body).unwrap()
+            },
+        }
+    }
+
+    fn login(&self, username: &str) -> String {
+        let reply = self.request(
+            "POST",
+            "login",
+            None,
+            Some(json!({ "username": username, "password": username })),
+        );
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["user"]["name"], username);
+        reply.body["token"].as_str().unwrap().to_owned()
+    }
+}
+
+#[test]
+fn test_login() {
+    let api = Api::start(UserMode::MultiUser);
+    let reply = api.request(
+        "POST",
+        "login",
+        None,
+        Some(json!({ "username": "admin", "password": "wrong" })),
+    );
+    assert_eq!(reply.status, 401);
+    assert_eq!(reply.error(), "invalid_credentials");
+    let reply = api.request("POST", "login", None, Some(json!({ "username": "admin" })));
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.error(), "invalid_json");
+
+    let reply = api.request("GET", "me", None, None);
+    assert_eq!(reply.status, 401);
+    assert_eq!(reply.error(), "unauthorized");
+    let reply = api.request("GET", "me", Some("nonsense"), None);
+    assert_eq!(reply.status, 401);
+
+    let token = api.login("admin");
+    let reply = api.request("GET", "me", Some(&token), None);
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body["name"], "admin");
+    assert_eq!(reply.body["is_admin"], true);
+
+    assert_eq!(
+        api.request("POST", "logout", Some(&token), None).status,
+        204
+    );
+    assert_eq!(api.request("GET", "me", Some(&token), None).status, 401);
+    api.server.stop();
+}
+
+#[test]
+fn test_auto_login() {
+    let api = Api::start(UserMode::SingleUserAutoLogin);
+    let reply = api.request("GET", "me", None, None);
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body["name"], "admin");
+    api.server.stop();
+}
+
+#[test]
+fn test_zettels() {
+    let api = Api::start(UserMode::MultiUser);
+    let token = api.login("admin");
+    let token = Some(token.as_str());
+
+    let reply = api.request(
+        "POST",
+        "zettels",
+        token,
+        Some(json!({ "path": "notes/rust", "body": "# Rust\nA language", "aliases": ["rs"] })),
+    );
+    assert_eq!(reply.status, 201);
+    let id = reply.body["id"].as_i64().unwrap();
+    assert_eq!(
+        reply.header("Location"),
+        Some(format!("/api/v1/zettels/{id}").as_str())
+    );
+    assert_eq!(reply.body["aliases"], json!(["rs"]));
+
+    let reply = api.request("GET", &format!("zettels/{id}"), token, None);
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body["path"], "notes/rust");
+    assert_eq!(reply.body["body"], "# Rust\nA language");
+    let reply = api.request("GET", "paths/RS", token, None);
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body["id"], id);
+
+    // the path is kept when it's left out
+    let reply = api.request(
+        "PUT",
+        &format!("zettels/{id}"),
+        token,
+        Some(json!({ "body": "# Rust\nA systems language" })),
+    );
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body["path"], "notes/rust");
+    assert_eq!(reply.body["aliases"], json!([]));
+
+    // PUT on a new path creates the zettel there
+    let reply = api.request(
+        "PUT",
+        "paths/notes/c%2B%2B",
+        token,
+        Some(json!({ "body": "# C++" })),
+    );
+    assert_eq!(reply.status, 201);
+    assert_eq!(reply.body["path"], "notes/c++");
+    let other_id = reply.body["id"].as_i64().unwrap();
+    let reply = api.request(
+        "PUT",
+        "paths/notes/c++",
+        token,
+        Some(json!({ "body": "# C++\nAlso a systems language" })),
+    );
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body["id"], other_id);
+
+    let reply = api.request(
+        "PUT",
+        &format!("zettels/{other_id}"),
+        token,
+        Some(json!({ "path": "notes/rust" })),
+    );
+    assert_eq!(reply.status, 409);
+    assert_eq!(reply.error(), "path_in_use");
+    let reply = api.request("POST", "zettels", token, Some(json!({ "path": "" })));
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.error(), "invalid_zettel");
+    let reply = api.request(
+        "POST",
+        "zettels",
+        token,
+        Some(json!({ "path": "x", "title": "x" })),
+    );
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.error(), "invalid_json");
+
+    let reply = api.request("GET", "zettels", token, None);
+    assert_eq!(reply.status, 200);
+    let paths: Vec<_> = reply
+        .body
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|z| &z["path"])
+        .collect();
+    assert_eq!(paths, ["notes/c++", "notes/rust"]);
+
+    let reply = api.request("DELETE", "paths/notes/c++", token, None);
+    assert_eq!(reply.status, 204);
+    let reply = api.request("GET", &format!("zettels/{other_id}"), token, None);
+    assert_eq!(reply.status, 404);
+    assert_eq!(reply.error(), "zettel_not_found");
+    let reply = api.request("DELETE", "paths/notes/c++", token, None);
+    assert_eq!(reply.status, 404);
+    assert_eq!(
+        api.request("GET", "zettels/nonsense", token, None).status,
+        404
+    );
+
+    // other users can't see or change the zettel
+    let other = api.login("other");
+    let other = Some(other.as_str());
+    let reply = api.request("GET", &format!("zettels/{id}"), other, None);
+    assert_eq!(reply.status, 404);
+    let reply = api.request("DELETE", &format!("zettels/{id}"), other, None);
+    assert_eq!(reply.status, 404);
+    assert_eq!(
+        api.request("GET", "paths/notes/rust", other, None).status,
+        404
+    );
+    assert_eq!(
+        api.request("GET", &format!("zettels/{id}"), token, None)
+            .status,
+        200
+    );
+    api.server.stop();
+}
+
+#[test]
+fn test_search() {
+    let api = Api::start(UserMode::MultiUser);
+    let token = api.login("admin");
+    let token = Some(token.as_str());
+    for (path, body) in [
+        ("apple", "---\ntags: fruit\n---\nAn apple a day"),
+        ("carrot", "---\ntags: vegetable\n---\nCarrots are orange"),
+    ] {
+        let reply = api.request(
+            "POST",
+            "zettels",
+            token,
+            Some(json!({ "path": path, "body": body })),
+        );
+        assert_eq!(reply.status, 201);
+    }
+
+    let reply = api.request("GET", "zettels?query=apple", token, None);
+    assert_eq!(reply.status, 200);
+    let zettels = reply.body.as_array().unwrap();
+    assert_eq!(zettels.len(), 1);
+    assert_eq!(zettels[0]["path"], "apple");
+    let snippet = &zettels[0]["snippets"][0];
+    let [start, end] = [0, 1].map(|i| snippet["matches"][0][i].as_u64().unwrap() as usize);
+    assert_eq!(&snippet["text"].as_str().unwrap()[start..end], "apple");
+
+    let reply = api.request("GET", "zettels?query=ORANGE&mode=Literal", token, None);
+    assert_eq!(reply.body, json!([]));
+    let reply = api.request(
+        "GET",
+        "zettels?query=ORANGE&mode=LiteralIgnoreCase",
+        token,
+        None,
+    );
+    assert_eq!(reply.body.as_array().unwrap().len(), 1);
+    let reply = api.request("GET", "zettels?property=tags%3Dvegetable", token, None);
+    assert_eq!(reply.body[0]["path"], "carrot");
+
+    let reply = api.request("GET", "zettels?query=(apple", token, None);
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.error(), "invalid_query");
+    let reply = api.request("GET", "zettels?query=(&mode=Regex", token, None);
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.error(), "invalid_regex");
+    let reply = api.request("GET", "zettels?query=a&mode=Fuzzy", token, None);
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.error(), "invalid_mode");
+    let reply = api.request("GET", "zettels?property=tags", token, None);
+    assert_eq!(reply.status, 400);
+    assert_eq!(reply.error(), "invalid_property");
+    api.server.stop();
+}
+
+#[test]
+fn test_config() {
+    let api = Api::start(UserMode::MultiUser);
+    let other = api.login("other");
+    let reply = api.request("GET", "config", Some(&other), None);
+    assert_eq!(reply.status, 403);
+    assert_eq!(reply.error(), "forbidden");
+
+    let admin = api.login("admin");
+    let reply = api.request("GET", "config", Some(&admin), None);
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.body["user_mode"], "MultiUser");
+    let mut config = reply.body;
+    config["terminal_editor"] = json!("vi");
+    let reply = api.request("PUT", "config", Some(&admin), Some(config.clone()));
+    assert_eq!(reply.status, 200);
+    assert_eq!(
+        api.request("GET", "config", Some(&admin), None).body,
+        config
+    );
+    let reply = api.request("PUT", "config", Some(&other), Some(config));
+    assert_eq!(reply.status, 403);
+    api.server.stop();
+}
+
+#[test]
+fn test_demoted_admin() {
+    let api = Api::start(UserMode::MultiUser);
+    let admin = api.login("admin");
+    assert_eq!(api.request("GET", "config", Some(&admin), None).status, 200);
+
+    // sessions load their user for every request, so this applies without logging in again
+    zettelkasten_shared::block_on(async {
+        let mut conn = <sqlx::SqliteConnection as sqlx::Connection>::connect(&api.url)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE users SET is_admin = FALSE WHERE username = 'admin'")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    });
+    let reply = api.request("GET", "me", Some(&admin), None);
+    assert_eq!(reply.body["is_admin"], false);
+    let reply = api.request("GET", "config", Some(&admin), None);
+    assert_eq!(reply.status, 403);
+    api.server.stop();
+}
+
+#[test]
+fn test_routes() {
+    let api = Api::start(UserMode::MultiUser);
+    let reply = api.request("GET", "openapi.json", None, None);
+    assert_eq!(reply.status, 200);
+    assert_eq!(reply.header("Content-Type"), Some("application/json"));
+    let paths = reply.body["paths"].as_object().unwrap();
+    for path in [
+        "/login",
+        "/me",
+        "/config",
+        "/zettels",
+        "/zettels/{id}",
+        "/paths/{path}",
+    ] {
+        assert!(paths.contains_key(path), "{path} is not documented");
+    }
+
+    let token = api.login("admin");
+    let reply = api.request("GET", "nonsense", Some(&token), None);
+    assert_eq!(reply.status, 404);
+    assert_eq!(reply.error(), "not_found");
+    let reply = api.request("DELETE", "zettels", Some(&token), None);
+    assert_eq!(reply.status, 405);
+    assert_eq!(reply.error(), "method_not_allowed");
+    api.server.stop();
+}
@@ -0,0 +1,149 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::Arc,
+};
+use zettelkasten_shared::storage::{ConnectableStorage, Storage, SystemConfig, UserMode};
+use zettelkasten_web::{Config, Server};
+
+struct Pages {
+    server: Server,
+    address: SocketAddr,
+    _dir: tempfile::TempDir,
+}
+
+struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Pages {
+    /// Start a server on a fresh database with the user `admin`
+    fn start(user_mode: UserMode) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let url = dir.path().join("zettelkasten.db").display().to_string();
+        let storage = zettelkasten_shared::block_on(async {
+            let (storage, _) = zettelkasten_sqlite::Connection::connect(url).await.unwrap();
+            storage.register("admin", "admin").await.unwrap();
+            storage
+        });
+        let server = Server::start(
+            &Config {
+                port: 0,
+                ..Config::default()
+            },
+            SystemConfig {
+                user_mode,
+                ..SystemConfig::default()
+            },
+            Arc::new(storage),
+        )
+        .unwrap();
+        Self {
+            address: server.address(),
+            server,
+            _dir: dir,
+        }
+    }
+
+    /// Request `path`, with the session `cookie`, posting `form` if it's given
+    fn request(&self, path: &str, cookie: Option<&str>, form: Option<&str>) -> Reply {
+        let mut stream = TcpStream::connect(self.address).unwrap();
+        let method = if form.is_some() { "POST" } else { "GET" };
+        let body = form.unwrap_or_default();
+        let mut request = format!(
+            "{method} {path} HTTP/1.0\r\nHost: {}\r\nContent-Length: {}\r\n",
+            self.address,
+            body.len()
+        );
+        if form.is_some() {
+            request += "Content-Type: application/x-www-form-urlencoded\r\n";
+        }
+        if let Some(cookie) = cookie {
+            request += &format!("Cookie: {cookie}\r\n");
+        }
+        request += "\r\n";
+        request += body;
+        stream.write_all(request.as_bytes()).unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut lines = head.lines();
+        let status = lines.next().unwrap().split(' ').nth(1).unwrap();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+            .collect();
+        Reply {
+            status: status.parse().unwrap(),
+            headers,
+            body: body.to_owned(),
+        }
+    }
+}
+
+#[test]
+fn test_login() {
+    let pages = Pages::start(UserMode::MultiUser);
+    let reply = pages.request("/", None, None);
+    assert_eq!(reply.status, 303);
+    assert_eq!(reply.header("Location"), Some("/login"));
+    let reply = pages.request("/", Some("zettelkasten_session=nonsense"), None);
+    assert_eq!(reply.status, 303);
+    assert_eq!(reply.header("Location"), Some("/login"));
+
+    let reply = pages.request("/login", None, Some("username=admin&password=wrong"));
+    assert_eq!(reply.status, 401);
+    assert!(reply.body.contains("Wrong username or password"));
+    assert_eq!(reply.header("Set-Cookie"), None);
+
+    let reply = pages.request("/login", None, Some("username=admin&password=admin"));
+    assert_eq!(reply.status, 303);
+    assert_eq!(reply.header("Location"), Some("/"));
+    let set_cookie = reply.header("Set-Cookie").unwrap();
+    assert!(set_cookie.contains("HttpOnly"));
+    let cookie = set_cookie.split(';').next().unwrap();
+    let reply = pages.request("/", Some(cookie), None);
+    assert_eq!(reply.status, 200);
+    assert!(reply.body.contains("<h1>All zettels</h1>"));
+
+    let reply = pages.request("/logout", Some(cookie), Some(""));
+    assert_eq!(reply.status, 303);
+    assert_eq!(reply.header("Location"), Some("/login"));
+    assert_eq!(pages.request("/", Some(cookie), None).status, 303);
+    pages.server.stop();
+}
+
+#[test]
+fn test_register() {
+    let pages = Pages::start(UserMode::SingleUserManualLogin);
+    let reply = pages.request("/login", None, None);
+    assert_eq!(reply.status, 200);
+    assert!(!reply.body.contains(r#"href="/register""#));
+    let reply = pages.request("/register", None, Some("username=other&password=other"));
+    assert_eq!(reply.status, 403);
+    assert!(reply.body.contains("Registration is closed"));
+    assert_eq!(reply.header("Set-Cookie"), None);
+    pages.server.stop();
+
+    let pages = Pages::start(UserMode::MultiUser);
+    let reply = pages.request("/login", None, None);
+    assert!(reply.body.contains(r#"href="/register""#));
+    let reply = pages.request("/register", None, Some("username=other&password=other"));
+    assert_eq!(reply.status, 303);
+    assert!(reply.header("Set-Cookie").is_some());
+    let reply = pages.request("/register", None, Some("username=other&password=other"));
+    assert_eq!(reply.status, 409);
+    pages.server.stop();
+}
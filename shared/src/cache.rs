@@ -0,0 +1,414 @@
+//! A [`Storage`] wrapper that caches the zettels and zettel listings of the inner storage.
+//!
+//! Reads from [`CachedStorage`] are served from memory when possible. Writes that go through the wrapper
+//! invalidate the cache, as well as the events from [`Storage::subscribe`], so changes made by other processes are
+//! picked up as well.
+
+use crate::{
+    async_trait,
+    storage::{
+        BatchOperation, BatchResult, Error, PathChild, PropertyFilter, SavedSearch, SearchMode,
+        SearchOpts, Storage, StorageEvent, SystemConfig, User, UserConfig, UserId, Zettel,
+        ZettelHeader, ZettelId,
+    },
+};
+use futures::{stream::BoxStream, FutureExt, StreamExt};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// The amount of entries that is cached by default, see [`CachedStorage::new`]
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+pub struct CachedStorage<S> {
+    storage: S,
+    state: Mutex<CacheState>,
+    events: Mutex<BoxStream<'static, StorageEvent>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl<S: Storage> CachedStorage<S> {
+    /// Wrap `storage` in a cache. Each kind of cached data (zettels, paths, listings and namespaces) holds at most
+    /// `capacity` entries, after which the least recently used entries are evicted.
+    pub async fn new(storage: S, capacity: usize) -> Result<Self, Error> {
+        let events = storage.subscribe().await?;
+        Ok(Self {
+            storage,
+            state: Mutex::new(CacheState {
+                zettels: Lru::new(capacity),
+                paths: Lru::new(capacity),
+                listings: Lru::new(capacity),
+                children: Lru::new(capacity),
+                generation: 0,
+            }),
+            events: Mutex::new(events),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// The amount of reads that were served from the cache, and the amount of reads that went to the inner storage.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Remove every cached entry.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.zettels.clear();
+        state.paths.clear();
+        state.listings.clear();
+        state.children.clear();
+        state.generation += 1;
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().expect("Cache mutex is poisoned")
+    }
+
+    /// Invalidate the cache for all events that were received from the inner storage, without blocking.
+    fn process_events(&self) {
+        let mut events = self.events.lock().expect("Cache mutex is poisoned");
+        while let Some(Some(event)) = events.next().now_or_never() {
+            match event {
+                StorageEvent::ZettelChanged { user, id }
+                | StorageEvent::ZettelDeleted { user, id } => {
+                    self.invalidate(user, Some(id));
+                }
+                StorageEvent::ConfigChanged | StorageEvent::UserConfigChanged { .. } => {}
+            }
+        }
+    }
+
+    /// Invalidate the zettel with the given `id`, and everything that is cached by path, listing or namespace for
+    /// `user`.
+    ///
+    /// If `id` is `None`, all zettels of `user` are invalidated.
+    fn invalidate(&self, user: UserId, id: Option<ZettelId>) {
+        let mut state = self.state();
+        state.generation += 1;
+        match id {
+            Some(id) => state.zettels.remove(&(user, id)),
+            None => state
+                .zettels
+                .retain(|(zettel_user, _)| *zettel_user != user),
+        }
+        state.paths.retain(|(path_user, _)| *path_user != user);
+        state
+            .listings
+            .retain(|(listing_user, _)| *listing_user != user);
+        state
+            .children
+            .retain(|(children_user, _)| *children_user != user);
+    }
+
+    /// The current [`CacheState::generation`], to take before reading from the inner storage
+    fn generation(&self) -> u64 {
+        self.state().generation
+    }
+
+    /// Cache what was read from the inner storage, unless the cache was invalidated since `generation` was taken. The
+    /// read may have happened before a change whose event was already processed, so it could be stale.
+    fn insert(&self, generation: u64, insert: impl FnOnce(&mut CacheState)) {
+        let mut state = self.state();
+        if state.generation == generation {
+            insert(&mut state);
+        }
+    }
+
+    fn hit<T>(&self, value: T) -> T {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl<S: Storage> Storage for CachedStorage<S> {
+    async fn user_count(&self) -> Result<u64, Error> {
+        self.storage.user_count().await
+    }
+
+    async fn login_single_user(&self) -> Result<User, Error> {
+        self.storage.login_single_user().await
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Option<User>, Error> {
+        self.storage.login(username, password).await
+    }
+
+    async fn register(&self, username: &str, password: &str) -> Result<User, Error> {
+        self.storage.register(username, password).await
+    }
+
+    async fn get_user(&self, user: UserId) -> Result<Option<User>, Error> {
+        self.storage.get_user(user).await
+    }
+
+    async fn get_zettels(
+        &self,
+        user: UserId,
+        search: SearchOpts<'_>,
+    ) -> Result<Vec<ZettelHeader>, Error> {
+        self.process_events();
+        let key = (user, ListingKey::from(&search));
+        if let Some(headers) = self.state().listings.get(&key) {
+            return Ok(self.hit(headers.clone()));
+        }
+        self.miss();
+        let generation = self.generation();
+        let headers = self.storage.get_zettels(user, search).await?;
+        self.insert(generation, |state| {
+            state.listings.insert(key, headers.clone())
+        });
+        Ok(headers)
+    }
+
+    async fn get_children(&self, user: UserId, parent: &str) -> Result<Vec<PathChild>, Error> {
+        self.process_events();
+        // namespaces are case-insensitive, like paths
+        let key = (user, parent.to_lowercase());
+        if let Some(children) = self.state().children.get(&key) {
+            return Ok(self.hit(children.clone()));
+        }
+        self.miss();
+        let generation = self.generation();
+        let children = self.storage.get_children(user, parent).await?;
+        self.insert(generation, |state| {
+            state.children.insert(key, children.clone())
+        });
+        Ok(children)
+    }
+
+    async fn get_zettel(&self, user: UserId, id: ZettelId) -> Result<Zettel, Error> {
+        self.process_events();
+        if let Some(zettel) = self.state().zettels.get(&(user, id)) {
+            return Ok(self.hit(zettel.clone()));
+        }
+        self.miss();
+        let generation = self.generation();
+        let zettel = self.storage.get_zettel(user, id).await?;
+        self.insert(generation, |state| {
+            state.zettels.insert((user, id), zettel.clone())
+        });
+        Ok(zettel)
+    }
+
+    async fn get_zettel_by_url(&self, user: UserId, url: &str) -> Result<Option<Zettel>, Error> {
+        self.process_events();
+        // paths are case-insensitive
+        let key = (user, url.to_lowercase());
+        {
+            let mut state = self.state();
+            match state.paths.get(&key).copied() {
+                // we know this path does not exist
+                Some(None) => return Ok(self.hit(None)),
+                Some(Some(id)) => {
+                    if let Some(zettel) = state.zettels.get(&(user, id)) {
+                        return Ok(self.hit(Some(zettel.clone())));
+                    }
+                }
+                None => {}
+            }
+        }
+        self.miss();
+        let generation = self.generation();
+        let zettel = self.storage.get_zettel_by_url(user, url).await?;
+        self.insert(generation, |state| {
+            state.paths.insert(key, zettel.as_ref().map(|z| z.id));
+            if let Some(zettel) = &zettel {
+                state.zettels.insert((user, zettel.id), zettel.clone());
+            }
+        });
+        Ok(zettel)
+    }
+
+    async fn get_all_zettels(&self, user: UserId) -> Result<Vec<Zettel>, Error> {
+        self.process_events();
+        let generation = self.generation();
+        let zettels = self.storage.get_all_zettels(user).await?;
+        self.insert(generation, |state| {
+            for zettel in &zettels {
+                state.zettels.insert((user, zettel.id), zettel.clone());
+            }
+        });
+        Ok(zettels)
+    }
+
+    async fn update_zettel(&self, user: UserId, zettel: &mut Zettel) -> Result<(), Error> {
+        let result = self.storage.update_zettel(user, zettel).await;
+        self.invalidate(user, Some(zettel.id));
+        result
+    }
+
+    async fn update_zettel_body(
+        &self,
+        user: UserId,
+        id: ZettelId,
+        base: Option<&str>,
+        body: &str,
+    ) -> Result<Zettel, Error> {
+        let result = self.storage.update_zettel_body(user, id, base, body).await;
+        self.invalidate(user, Some(id));
+        result
+    }
+
+    async fn delete_zettel(&self, user: UserId, id: ZettelId) -> Result<(), Error> {
+        let result = self.storage.delete_zettel(user, id).await;
+        self.invalidate(user, Some(id));
+        result
+    }
+
+    async fn set_user_last_visited_zettel(
+        &self,
+        user: UserId,
+        zettel_id: Option<ZettelId>,
+    ) -> Result<(), Error> {
+        self.storage
+            .set_user_last_visited_zettel(user, zettel_id)
+            .await
+    }
+
+    async fn update_config(&self, config: &SystemConfig) -> Result<(), Error> {
+        self.storage.update_config(config).await
+    }
+
+    async fn get_user_config(&self, user: UserId) -> Result<UserConfig, Error> {
+        self.storage.get_user_config(user).await
+    }
+
+    async fn update_user_config(&self, user: UserId, config: &UserConfig) -> Result<(), Error> {
+        self.storage.update_user_config(user, config).await
+    }
+
+    async fn get_saved_searches(&self, user: UserId) -> Result<Vec<SavedSearch>, Error> {
+        self.storage.get_saved_searches(user).await
+    }
+
+    async fn save_search(&self, user: UserId, search: &SavedSearch) -> Result<(), Error> {
+        self.storage.save_search(user, search).await
+    }
+
+    async fn delete_saved_search(&self, user: UserId, name: &str) -> Result<(), Error> {
+        self.storage.delete_saved_search(user, name).await
+    }
+
+    async fn batch(
+        &self,
+        user: UserId,
+        operations: &mut [BatchOperation],
+    ) -> Result<Vec<BatchResult>, Error> {
+        let result = self.storage.batch(user, operations).await;
+        self.invalidate(user, None);
+        result
+    }
+
+    async fn subscribe(&self) -> Result<BoxStream<'static, StorageEvent>, Error> {
+        self.storage.subscribe().await
+    }
+}
+
+struct CacheState {
+    zettels: Lru<(UserId, ZettelId), Zettel>,
+    /// The id of the zettel for a given lowercase path, or `None` if the path does not exist
+    paths: Lru<(UserId, String), Option<ZettelId>>,
+    listings: Lru<(UserId, ListingKey), Vec<ZettelHeader>>,
+    /// The children of a namespace, by its lowercase path
+    children: Lru<(UserId, String), Vec<PathChild>>,
+    /// Counts the invalidations, so reads that overlap with one don't cache what they read
+    generation: u64,
+}
+
+/// An owned version of [`SearchOpts`]
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct ListingKey {
+    query: String,
+    mode: SearchMode,
+    list_all: bool,
+    properties: Vec<PropertyFilter>,
+}
+
+impl From<&SearchOpts<'_>> for ListingKey {
+    fn from(search: &SearchOpts<'_>) -> Self {
+        Self {
+            query: search.query.to_owned(),
+            mode: search.mode,
+            list_all: search.list_all,
+            properties: search.properties.to_vec(),
+        }
+    }
+}
+
+/// A map that evicts the least recently used entry when it's full.
+///
+/// Eviction walks over all entries, which is fine for the few thousand entries we keep around.
+struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (u64, V)>,
+}
+
+impl<K: Hash + Eq + Clone, V> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let (last_used, value) = self.entries.get_mut(key)?;
+        *last_used = self.tick;
+        Some(value)
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (last_used, _))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (self.tick, value));
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    fn retain(&mut self, mut f: impl FnMut(&K) -> bool) {
+        self.entries.retain(|key, _| f(key));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,52 @@
+//! Saving a zettel that was edited outside of the storage, like in an editor or a language client, without overwriting
+//! what was saved elsewhere while it was edited.
+//!
+//! The body that the edit started from is compared with the stored body in the same statement that saves the edit, see
+//! [`Storage::update_zettel_body`]. Only the body is saved, so a rename in another session is kept.
+
+use crate::storage::{Error, Storage, UserId, Zettel};
+
+/// What [`save_edit`] did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saved {
+    Saved,
+    /// Nothing was saved, because the body was changed elsewhere since the edit started
+    Changed,
+    /// Nothing was saved, because the zettel was deleted elsewhere since the edit started
+    Deleted,
+}
+
+/// Save `body` as the body of `zettel`, whose body is what the edit started from. Unless `overwrite` is set, nothing is
+/// saved if the stored body is not that anymore. A zettel with the id `0` is created.
+///
+/// When it is saved, `zettel` is updated to the stored zettel.
+///
+/// # Errors
+///
+/// When the zettel can't be saved
+pub async fn save_edit(
+    storage: &dyn Storage,
+    user: UserId,
+    zettel: &mut Zettel,
+    body: &str,
+    overwrite: bool,
+) -> Result<Saved, Error> {
+    if zettel.id == 0 {
+        zettel.body = body.to_owned();
+        storage.update_zettel(user, zettel).await?;
+        return Ok(Saved::Saved);
+    }
+    let base = (!overwrite).then_some(zettel.body.as_str());
+    match storage
+        .update_zettel_body(user, zettel.id, base, body)
+        .await
+    {
+        Ok(stored) => {
+            *zettel = stored;
+            Ok(Saved::Saved)
+        }
+        Err(Error::EditConflict { .. }) => Ok(Saved::Changed),
+        Err(Error::ZettelNotFound { .. }) => Ok(Saved::Deleted),
+        Err(e) => Err(e),
+    }
+}
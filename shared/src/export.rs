@@ -0,0 +1,315 @@
+//! Export the link graph of a user, e.g. to visualise it in Graphviz or Gephi.
+
+use crate::{
+    links::LinkGraph,
+    storage::{ZettelHeader, ZettelId},
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Write,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Graphviz DOT
+    Dot,
+    GraphMl,
+    /// `{"nodes": [{"id", "path"}], "edges": [{"source", "target"}]}`
+    Json,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Dot, Format::GraphMl, Format::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dot => "dot",
+            Self::GraphMl => "graphml",
+            Self::Json => "json",
+        }
+    }
+
+    /// The file extension that is commonly used for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Dot => "gv",
+            Self::GraphMl => "graphml",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown export format {s:?}, expected dot, graphml or json"))
+    }
+}
+
+/// Which part of the graph is exported. By default everything is exported.
+#[derive(Debug, Default, Clone)]
+pub struct ExportOpts<'a> {
+    /// Only export zettels whose path starts with this prefix
+    pub prefix: Option<&'a str>,
+    /// Only export the zettels that are at most `hops` links away from the zettel at this path. Links are followed in
+    /// both directions.
+    pub around: Option<(&'a str, usize)>,
+}
+
+/// The zettels and links that are exported
+#[derive(Debug, Clone)]
+pub struct ExportGraph {
+    pub nodes: Vec<ZettelHeader>,
+    /// Links between two exported zettels, as `(source, target)`
+    pub edges: BTreeSet<(ZettelId, ZettelId)>,
+}
+
+impl ExportGraph {
+    /// Select the part of `graph` that is described by `opts`.
+    ///
+    /// Returns `None` if the zettel of [`ExportOpts::around`] does not exist.
+    pub fn new(graph: &LinkGraph, opts: &ExportOpts<'_>) -> Option<Self> {
+        let mut nodes: HashSet<ZettelId> = graph
+            .zettels
+            .iter()
+            .filter(|zettel| {
+                opts.prefix.is_none_or(|prefix| {
+                    zettel
+                        .path
+                        .get(..prefix.len())
+                        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+                })
+            })
+            .map(|zettel| zettel.id)
+            .collect();
+        let mut edges: BTreeSet<(ZettelId, ZettelId)> = graph
+            .links
+            .iter()
+            .flat_map(|(source, targets)| {
+                targets
+                    .iter()
+                    .filter_map(|target| graph.resolve(target))
+                    .map(move |target| (*source, target))
+            })
+            .filter(|(source, target)| nodes.contains(source) && nodes.contains(target))
+            .collect();
+
+        if let Some((path, hops)) = opts.around {
+            let center = graph.resolve(path).filter(|id| nodes.contains(id))?;
+            nodes = neighbourhood(&edges, center, hops);
+            edges.retain(|(source, target)| nodes.contains(source) && nodes.contains(target));
+        }
+
+        Some(Self {
+            nodes: graph
+                .zettels
+                .iter()
+                .filter(|zettel| nodes.contains(&zettel.id))
+                .cloned()
+                .collect(),
+            edges,
+        })
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Dot => self.to_dot(),
+            Format::GraphMl => self.to_graphml(),
+            Format::Json => self.to_json(),
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut out = String::from("digraph zettelkasten {\n");
+        for node in &self.nodes {
+            let label = node.path.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(out, "    {} [label=\"{label}\"];", node.id).unwrap();
+        }
+        for (source, target) in &self.edges {
+            writeln!(out, "    {source} -> {target};").unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"path\" for=\"node\" attr.name=\"path\" attr.type=\"string\"/>\n",
+            "  <graph id=\"zettelkasten\" edgedefault=\"directed\">\n",
+        ));
+        for node in &self.nodes {
+            writeln!(
+                out,
+                "    <node id=\"n{}\"><data key=\"path\">{}</data></node>",
+                node.id,
+                xml_escape(&node.path)
+            )
+            .unwrap();
+        }
+        for (source, target) in &self.edges {
+            writeln!(out, "    <edge source=\"n{source}\" target=\"n{target}\"/>").unwrap();
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    fn to_json(&self) -> String {
+        #[derive(serde::Serialize)]
+        struct Node<'a> {
+            id: ZettelId,
+            path: &'a str,
+        }
+        #[derive(serde::Serialize)]
+        struct Edge {
+            source: ZettelId,
+            target: ZettelId,
+        }
+        #[derive(serde::Serialize)]
+        struct Graph<'a> {
+            nodes: Vec<Node<'a>>,
+            edges: Vec<Edge>,
+        }
+
+        let graph = Graph {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|node| Node {
+                    id: node.id,
+                    path: &node.path,
+                })
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .map(|&(source, target)| Edge { source, target })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&graph).expect("Graph can always be serialized")
+    }
+}
+
+/// The zettels that are at most `hops` links away from `center`, ignoring the direction of the links
+fn neighbourhood(
+    edges: &BTreeSet<(ZettelId, ZettelId)>,
+    center: ZettelId,
+    hops: usize,
+) -> HashSet<ZettelId> {
+    let mut neighbours: HashMap<ZettelId, Vec<ZettelId>> = HashMap::new();
+    for &(source, target) in edges {
+        neighbours.entry(source).or_default().push(target);
+        neighbours.entry(target).or_default().push(source);
+    }
+
+    let mut visited = HashSet::from([center]);
+    let mut queue = VecDeque::from([(center, 0)]);
+    while let Some((id, distance)) = queue.pop_front() {
+        if distance == hops {
+            continue;
+        }
+        for &neighbour in neighbours.get(&id).into_iter().flatten() {
+            if visited.insert(neighbour) {
+                queue.push_back((neighbour, distance + 1));
+            }
+        }
+    }
+    visited
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// home -> a/1 -> a/2 -> b
+    fn graph() -> LinkGraph {
+        let zettels = [(1, "a/1"), (2, "a/2"), (3, "b"), (4, "home")]
+            .into_iter()
+            .map(|(id, path)| ZettelHeader {
+                id,
+                path: path.to_owned(),
+                snippets: Vec::new(),
+                folgezettel: None,
+            })
+            .collect();
+        let links = [
+            (4, vec!["a/1"]),
+            (1, vec!["A/2"]),
+            (2, vec!["b", "missing"]),
+            (3, vec![]),
+        ]
+        .into_iter()
+        .map(|(id, targets)| (id, targets.into_iter().map(str::to_owned).collect()))
+        .collect();
+        LinkGraph::new(zettels, links)
+    }
+
+    fn paths(graph: &ExportGraph) -> Vec<&str> {
+        graph.nodes.iter().map(|node| node.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_all() {
+        let all = ExportGraph::new(&graph(), &ExportOpts::default()).unwrap();
+        assert_eq!(paths(&all), ["a/1", "a/2", "b", "home"]);
+        assert_eq!(all.edges, [(1, 2), (2, 3), (4, 1)].into());
+    }
+
+    #[test]
+    fn test_prefix() {
+        let opts = ExportOpts {
+            prefix: Some("A/"),
+            ..Default::default()
+        };
+        let prefixed = ExportGraph::new(&graph(), &opts).unwrap();
+        assert_eq!(paths(&prefixed), ["a/1", "a/2"]);
+        assert_eq!(prefixed.edges, [(1, 2)].into());
+    }
+
+    #[test]
+    fn test_around() {
+        let graph = graph();
+        let opts = ExportOpts {
+            around: Some(("a/2", 1)),
+            ..Default::default()
+        };
+        let around = ExportGraph::new(&graph, &opts).unwrap();
+        assert_eq!(paths(&around), ["a/1", "a/2", "b"]);
+        let opts = ExportOpts {
+            around: Some(("missing", 1)),
+            ..Default::default()
+        };
+        assert!(ExportGraph::new(&graph, &opts).is_none());
+    }
+
+    #[test]
+    fn test_render() {
+        let all = ExportGraph::new(&graph(), &ExportOpts::default()).unwrap();
+        let dot = all.render(Format::Dot);
+        assert!(dot.starts_with("digraph zettelkasten {\n"));
+        assert!(dot.contains("[label=\"a/2\"]"));
+        let graphml = all.render(Format::GraphMl);
+        assert_eq!(graphml.matches("<edge ").count(), 3);
+        let json: serde_json::Value = serde_json::from_str(&all.render(Format::Json)).unwrap();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 4);
+        assert_eq!(json["edges"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!("GraphML".parse(), Ok(Format::GraphMl));
+        assert!("svg".parse::<Format>().is_err());
+    }
+}
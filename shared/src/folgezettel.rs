@@ -0,0 +1,255 @@
+//! Luhmann style Folgezettel ids.
+//!
+//! A Folgezettel id alternates between numbers and letters, like `1`, `1a`, `1a1`, `1a1b`. The zettel `1a` continues
+//! with `1b`, and branches off into `1a1`. Sorting the ids puts every branch right after the zettel it branches off
+//! from, which gives the sequence in which the zettels are meant to be read.
+
+use crate::storage::{Error, ZettelHeader};
+use std::{collections::HashSet, fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Folgezettel(Vec<Segment>);
+
+/// A part of a [`Folgezettel`] id. Numbers and letters always alternate, starting with a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Segment {
+    Number(u64),
+    /// The letters `a`, ..., `z`, `aa`, `ab`, ... as `1`, ..., `26`, `27`, `28`, ...
+    Letters(u64),
+}
+
+impl Segment {
+    fn next(self) -> Self {
+        match self {
+            Self::Number(n) => Self::Number(n + 1),
+            Self::Letters(n) => Self::Letters(n + 1),
+        }
+    }
+
+    /// The first segment that can follow this one
+    fn first_child(self) -> Self {
+        match self {
+            Self::Number(_) => Self::Letters(1),
+            Self::Letters(_) => Self::Number(1),
+        }
+    }
+}
+
+impl Folgezettel {
+    /// The first top level id, `1`
+    pub fn first() -> Self {
+        Self(vec![Segment::Number(1)])
+    }
+
+    /// How many times this id branches off, `1` has a depth of 0 and `1a1` a depth of 2
+    pub fn depth(&self) -> usize {
+        self.0.len() - 1
+    }
+
+    /// The id that follows this one on the same level, `1b` for `1a`
+    pub fn next_sibling(&self) -> Self {
+        let mut segments = self.0.clone();
+        let last = segments
+            .last_mut()
+            .expect("Folgezettel ids are never empty");
+        *last = last.next();
+        Self(segments)
+    }
+
+    /// The first id that branches off from this one, `1a1` for `1a`
+    pub fn first_child(&self) -> Self {
+        let mut segments = self.0.clone();
+        let last = *segments.last().expect("Folgezettel ids are never empty");
+        segments.push(last.first_child());
+        Self(segments)
+    }
+
+    /// The next free id that continues `current` on the same level. Without a current id, this is the next free top
+    /// level id.
+    pub fn continuation(current: Option<&Self>, taken: &HashSet<Self>) -> Self {
+        let mut next = current.map_or_else(Self::first, Self::next_sibling);
+        while taken.contains(&next) {
+            next = next.next_sibling();
+        }
+        next
+    }
+
+    /// The next free id that branches off from `current`
+    pub fn branch(current: &Self, taken: &HashSet<Self>) -> Self {
+        let mut next = current.first_child();
+        while taken.contains(&next) {
+            next = next.next_sibling();
+        }
+        next
+    }
+}
+
+impl FromStr for Folgezettel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = s;
+        while !rest.is_empty() {
+            let expect_number = segments.len() % 2 == 0;
+            let end = rest
+                .find(|c: char| {
+                    if expect_number {
+                        !c.is_ascii_digit()
+                    } else {
+                        !c.is_ascii_lowercase()
+                    }
+                })
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(());
+            }
+            let (part, remaining) = rest.split_at(end);
+            let segment = if expect_number {
+                match part.parse() {
+                    Ok(0) | Err(_) => return Err(()),
+                    Ok(n) => Segment::Number(n),
+                }
+            } else {
+                Segment::Letters(
+                    part.bytes()
+                        .try_fold(0u64, |n, c| {
+                            n.checked_mul(26)?.checked_add(u64::from(c - b'a') + 1)
+                        })
+                        .ok_or(())?,
+                )
+            };
+            segments.push(segment);
+            rest = remaining;
+        }
+        if segments.is_empty() {
+            Err(())
+        } else {
+            Ok(Self(segments))
+        }
+    }
+}
+
+impl fmt::Display for Folgezettel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.0 {
+            match *segment {
+                Segment::Number(n) => write!(f, "{n}")?,
+                Segment::Letters(mut n) => {
+                    let mut letters = Vec::new();
+                    while n > 0 {
+                        n -= 1;
+                        letters.push(b'a' + u8::try_from(n % 26).unwrap());
+                        n /= 26;
+                    }
+                    letters.reverse();
+                    f.write_str(std::str::from_utf8(&letters).unwrap())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// All zettels that have a Folgezettel id, in reading order
+pub fn sequence(zettels: &[ZettelHeader]) -> Vec<(Folgezettel, &ZettelHeader)> {
+    let mut sequence: Vec<(Folgezettel, &ZettelHeader)> = zettels
+        .iter()
+        .filter_map(|zettel| Some((zettel.folgezettel.as_deref()?.parse().ok()?, zettel)))
+        .collect();
+    sequence.sort_by(|(left, _), (right, _)| left.cmp(right));
+    sequence
+}
+
+/// The Folgezettel ids that are in use by `zettels`
+pub fn taken(zettels: &[ZettelHeader]) -> HashSet<Folgezettel> {
+    sequence(zettels).into_iter().map(|(id, _)| id).collect()
+}
+
+/// Check that `folgezettel` is a valid id, and bring it into its canonical form. Empty ids are removed.
+pub fn normalize(folgezettel: &mut Option<String>) -> Result<(), Error> {
+    let Some(value) = folgezettel.as_deref().map(str::trim) else {
+        return Ok(());
+    };
+    if value.is_empty() {
+        *folgezettel = None;
+        return Ok(());
+    }
+    let id: Folgezettel = value.parse().map_err(|()| Error::InvalidFolgezettel {
+        folgezettel: value.to_owned(),
+    })?;
+    *folgezettel = Some(id.to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(s: &str) -> Folgezettel {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(id("12ab3").to_string(), "12ab3");
+        assert!("a1".parse::<Folgezettel>().is_err());
+        assert!("0".parse::<Folgezettel>().is_err());
+        assert!("".parse::<Folgezettel>().is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut folgezettel = Some(" 1a ".to_owned());
+        normalize(&mut folgezettel).unwrap();
+        assert_eq!(folgezettel.as_deref(), Some("1a"));
+        let mut empty = Some(" ".to_owned());
+        normalize(&mut empty).unwrap();
+        assert_eq!(empty, None);
+        assert!(matches!(
+            normalize(&mut Some("a1".to_owned())),
+            Err(Error::InvalidFolgezettel { .. })
+        ));
+    }
+
+    #[test]
+    fn test_order() {
+        let mut ordered = [id("2"), id("1b"), id("1a1"), id("10"), id("1"), id("1a")];
+        ordered.sort();
+        assert_eq!(
+            ordered.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["1", "1a", "1a1", "1b", "2", "10"]
+        );
+    }
+
+    #[test]
+    fn test_siblings_and_children() {
+        assert_eq!(id("1z").next_sibling().to_string(), "1aa");
+        let taken = HashSet::from([id("1"), id("1a"), id("1b"), id("1a1")]);
+        assert_eq!(Folgezettel::continuation(None, &taken).to_string(), "2");
+        assert_eq!(
+            Folgezettel::continuation(Some(&id("1a")), &taken).to_string(),
+            "1c"
+        );
+        assert_eq!(Folgezettel::branch(&id("1a"), &taken).to_string(), "1a2");
+        assert_eq!(Folgezettel::branch(&id("1b"), &taken).to_string(), "1b1");
+    }
+
+    #[test]
+    fn test_sequence() {
+        let zettels: Vec<ZettelHeader> = [("c", Some("1a")), ("a", None), ("b", Some("1"))]
+            .into_iter()
+            .map(|(path, folgezettel)| ZettelHeader {
+                id: 0,
+                path: path.to_owned(),
+                snippets: Vec::new(),
+                folgezettel: folgezettel.map(ToOwned::to_owned),
+            })
+            .collect();
+        let sequence: Vec<_> = sequence(&zettels)
+            .into_iter()
+            .map(|(_, zettel)| zettel.path.as_str())
+            .collect();
+        assert_eq!(sequence, ["b", "c"]);
+    }
+}
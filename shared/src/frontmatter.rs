@@ -0,0 +1,131 @@
+//! Properties in a front matter block at the top of a zettel.
+//!
+//! The front matter is a block that starts and ends with a `---` line, holding a small subset of YAML:
+//!
+//! ```text
+//! ---
+//! status: draft
+//! tags: [rust, async]
+//! sources:
+//!   - "The Rust Book"
+//! ---
+//! ```
+//!
+//! Every value of a list becomes its own property, so the example has the properties `status = draft`,
+//! `tags = rust`, `tags = async` and `sources = The Rust Book`. Properties are stored when a zettel is saved, and can
+//! be searched for with [`crate::storage::SearchOpts::properties`].
+
+/// The line that starts and ends the front matter
+const DELIMITER: &str = "---";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontMatter<'a> {
+    /// The properties as `(key, value)`, in the order in which they appear
+    pub properties: Vec<(&'a str, &'a str)>,
+    /// The rest of the body, after the front matter
+    pub content: &'a str,
+}
+
+/// Split `body` into its front matter and the content after it. Returns `None` if `body` has no front matter.
+pub fn parse(body: &str) -> Option<FrontMatter<'_>> {
+    let mut lines = body.split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != DELIMITER {
+        return None;
+    }
+    let mut offset = first.len();
+
+    let mut properties = Vec::new();
+    // the key of a list whose items follow on the next lines
+    let mut list_key = None;
+    for line in lines {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == DELIMITER {
+            return Some(FrontMatter {
+                properties,
+                content: &body[offset..],
+            });
+        }
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            if let Some(key) = list_key {
+                properties.push((key, unquote(item)));
+            }
+            continue;
+        }
+        list_key = None;
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() {
+            continue;
+        }
+        if value.is_empty() {
+            list_key = Some(key);
+        } else if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            properties.extend(
+                items
+                    .split(',')
+                    .map(unquote)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| (key, item)),
+            );
+        } else {
+            properties.push((key, unquote(value)));
+        }
+    }
+    // the front matter was never closed, so it's just text
+    None
+}
+
+/// Remove surrounding whitespace and quotes
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .into_iter()
+        .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::PropertyFilter;
+
+    #[test]
+    fn test_parse() {
+        let body = "---\nstatus: draft\ntags: [rust, 'async']\nsources:\n  - \"The Rust Book\"\n  - Blog\n---\n# Notes\n";
+        let front_matter = parse(body).unwrap();
+        assert_eq!(
+            front_matter.properties,
+            [
+                ("status", "draft"),
+                ("tags", "rust"),
+                ("tags", "async"),
+                ("sources", "The Rust Book"),
+                ("sources", "Blog"),
+            ]
+        );
+        assert_eq!(front_matter.content, "# Notes\n");
+    }
+
+    #[test]
+    fn test_no_front_matter() {
+        assert!(parse("---\nstatus: draft\n").is_none());
+        assert!(parse("# ---\n").is_none());
+    }
+
+    #[test]
+    fn test_property_filter() {
+        let filter: PropertyFilter = " status = draft ".parse().unwrap();
+        assert_eq!(filter.key, "status");
+        assert_eq!(filter.value, "draft");
+        assert!("status".parse::<PropertyFilter>().is_err());
+        assert!("= draft".parse::<PropertyFilter>().is_err());
+    }
+}
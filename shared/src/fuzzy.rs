@@ -0,0 +1,192 @@
+//! Fuzzy matching in the style of fzf, to jump to a zettel by typing a few characters of its path.
+//!
+//! Every character of the pattern has to appear in the text in the same order, ignoring case, so `rstlang` finds
+//! `rust-language`. Matches score higher when their characters are consecutive or start a namespace or word, so
+//! `rl` prefers `rust/lang` over `parallel`.
+
+use crate::namespace;
+use std::cmp::Reverse;
+
+const SCORE_MATCH: i32 = 16;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+/// At the start of the text or of a namespace
+const BONUS_NAMESPACE: i32 = 10;
+/// At the start of a word
+const BONUS_BOUNDARY: i32 = 8;
+/// At the start of a `camelCase` hump or a number
+const BONUS_CAMEL: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = 4;
+/// The bonus of the first character of the pattern counts more
+const FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Higher is better
+    pub score: i32,
+    /// The byte offsets of the matched characters in the text, in order
+    pub positions: Vec<usize>,
+}
+
+/// Find the best match of `pattern` in `text`. Whitespace in `pattern` is ignored, and an empty pattern matches
+/// everything.
+pub fn find(pattern: &str, text: &str) -> Option<Match> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect();
+    if pattern.is_empty() {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut remaining = pattern.iter().peekable();
+    for &(_, c) in &chars {
+        remaining.next_if_eq(&&fold(c));
+    }
+    if remaining.peek().is_some() {
+        return None;
+    }
+
+    let bonuses: Vec<i32> = chars
+        .iter()
+        .enumerate()
+        .map(|(idx, &(_, c))| bonus(idx.checked_sub(1).map(|prev| chars[prev].1), c))
+        .collect();
+    // `scores[i][j]` is the best score of `pattern[..=i]` with `pattern[i]` at `chars[j]`, and the index of the
+    // character that `pattern[i - 1]` is at
+    let mut scores = vec![vec![None::<(i32, usize)>; chars.len()]; pattern.len()];
+    for (i, &p) in pattern.iter().enumerate() {
+        // the best match of `pattern[i - 1]` that leaves a gap before `j`, with the gap penalty applied
+        let mut gapped: Option<(i32, usize)> = None;
+        for (j, &(_, c)) in chars.iter().enumerate() {
+            if i > 0 && j >= 2 {
+                gapped = gapped.map(|(score, prev)| (score - PENALTY_GAP_EXTENSION, prev));
+                if let Some((score, _)) = scores[i - 1][j - 2] {
+                    let started = score - PENALTY_GAP_START;
+                    if gapped.is_none_or(|(best, _)| started > best) {
+                        gapped = Some((started, j - 2));
+                    }
+                }
+            }
+            if fold(c) != p {
+                continue;
+            }
+            let bonus = bonuses[j];
+            scores[i][j] = if i == 0 {
+                Some((SCORE_MATCH + bonus * FIRST_CHAR_MULTIPLIER, 0))
+            } else {
+                let consecutive = j
+                    .checked_sub(1)
+                    .and_then(|prev| Some((scores[i - 1][prev]?.0, prev)))
+                    .map(|(score, prev)| {
+                        (score + SCORE_MATCH + bonus.max(BONUS_CONSECUTIVE), prev)
+                    });
+                let gapped = gapped.map(|(score, prev)| (score + SCORE_MATCH + bonus, prev));
+                match (consecutive, gapped) {
+                    (Some(a), Some(b)) => Some(if b.0 > a.0 { b } else { a }),
+                    (a, b) => a.or(b),
+                }
+            };
+        }
+    }
+
+    let last = pattern.len() - 1;
+    let (mut j, score) = scores[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, score)| Some((j, score.as_ref()?.0)))
+        .fold(None, |best: Option<(usize, i32)>, (j, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((j, score)),
+        })?;
+    let mut positions = vec![0; pattern.len()];
+    for i in (0..pattern.len()).rev() {
+        positions[i] = chars[j].0;
+        if let Some((_, prev)) = scores[i][j] {
+            j = prev;
+        }
+    }
+    Some(Match { score, positions })
+}
+
+/// The items whose `key` matches `pattern`, best matches first. Shorter keys win ties, and items with the same score
+/// and length keep their order.
+pub fn filter<T>(
+    pattern: &str,
+    items: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> &str,
+) -> Vec<(T, Match)> {
+    let mut matches: Vec<(T, Match)> = items
+        .into_iter()
+        .filter_map(|item| {
+            let found = find(pattern, key(&item))?;
+            Some((item, found))
+        })
+        .collect();
+    matches.sort_by_key(|(item, found)| (Reverse(found.score), key(item).len()));
+    matches
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn bonus(previous: Option<char>, c: char) -> i32 {
+    match previous {
+        None | Some(namespace::SEPARATOR) => BONUS_NAMESPACE,
+        Some(prev) if !prev.is_alphanumeric() => {
+            if c.is_alphanumeric() {
+                BONUS_BOUNDARY
+            } else {
+                0
+            }
+        }
+        Some(prev)
+            if (prev.is_lowercase() && c.is_uppercase())
+                || (!prev.is_numeric() && c.is_numeric()) =>
+        {
+            BONUS_CAMEL
+        }
+        Some(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let found = find("rstlang", "rust-language").unwrap();
+        assert_eq!(found.positions, [0, 2, 3, 5, 6, 7, 8]);
+        assert!(find("rstlang", "rust").is_none());
+        assert_eq!(find("", "rust").unwrap().score, 0);
+        assert_eq!(find("R L", "rust/lang").unwrap().positions, [0, 5]);
+    }
+
+    #[test]
+    fn test_namespace_beats_inside_word() {
+        let found = find("ra", "rust/parallel/async").unwrap();
+        assert_eq!(found.positions, [0, 14]);
+    }
+
+    #[test]
+    fn test_multi_byte() {
+        // multi byte characters are matched by their byte offsets
+        assert_eq!(find("üb", "Über").unwrap().positions, [0, 2]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let paths = ["rust/planning", "parallel", "rust/lang", "notes"];
+        let matches: Vec<_> = filter("rl", paths, |path| path)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(matches, ["rust/lang", "rust/planning", "parallel"]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Daily notes.
+//!
+//! Every day has its own zettel at `journal/YYYY-MM-DD`. New daily notes are created from
+//! [`UserConfig::journal_template`], or from the default template for [`JOURNAL_PREFIX`] if that is not set.
+
+use crate::{
+    storage::{Error, Storage, User, UserConfig, Zettel},
+    template::{self, TemplateVars},
+};
+use chrono::NaiveDate;
+
+/// The path prefix under which daily notes are stored
+pub const JOURNAL_PREFIX: &str = "journal/";
+
+/// The path of the daily note for `date`
+pub fn journal_path(date: NaiveDate) -> String {
+    format!("{JOURNAL_PREFIX}{}", date.format("%Y-%m-%d"))
+}
+
+/// The day of the daily note at `path`, if `path` is a daily note
+pub fn journal_date(path: &str) -> Option<NaiveDate> {
+    let prefix = path.get(..JOURNAL_PREFIX.len())?;
+    if !prefix.eq_ignore_ascii_case(JOURNAL_PREFIX) {
+        return None;
+    }
+    NaiveDate::parse_from_str(&path[JOURNAL_PREFIX.len()..], "%Y-%m-%d").ok()
+}
+
+/// Today, in the local timezone
+pub fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+/// The daily note for `date`. If it does not exist yet, a new, unsaved zettel is created from the journal template.
+pub async fn daily_note(
+    storage: &dyn Storage,
+    user: &User,
+    config: &UserConfig,
+    date: NaiveDate,
+) -> Result<Zettel, Error> {
+    let path = journal_path(date);
+    if let Some(zettel) = storage.get_zettel_by_url(user.id, &path).await? {
+        return Ok(zettel);
+    }
+    let Some(template) = config
+        .journal_template
+        .as_deref()
+        .or_else(|| template::default_template(config, &path))
+    else {
+        return Ok(Zettel {
+            path,
+            ..Default::default()
+        });
+    };
+    let vars = TemplateVars {
+        path: &path,
+        date,
+        user: &user.name,
+    };
+    template::new_zettel(storage, user.id, &template::template_path(template), &vars).await
+}
+
+/// Like [`daily_note`], but the daily note is saved if it does not exist yet.
+pub async fn create_daily_note(
+    storage: &dyn Storage,
+    user: &User,
+    config: &UserConfig,
+    date: NaiveDate,
+) -> Result<Zettel, Error> {
+    let mut zettel = daily_note(storage, user, config, date).await?;
+    if zettel.id == 0 {
+        storage.update_zettel(user.id, &mut zettel).await?;
+    }
+    Ok(zettel)
+}
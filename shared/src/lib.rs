@@ -1,6 +1,19 @@
+pub mod cache;
+pub mod edit;
+pub mod export;
+pub mod folgezettel;
+pub mod frontmatter;
+pub mod fuzzy;
+pub mod journal;
+pub mod links;
+pub mod namespace;
+pub mod query;
+pub mod snippet;
 pub mod storage;
+pub mod template;
 
 pub use async_trait::async_trait;
+pub use chrono;
 pub use futures;
 
 use std::future::Future;
@@ -12,6 +25,9 @@ pub trait Front: Send + Sync {
     fn run(config: Self::Config, system_config: SystemConfig, storage: Arc<dyn storage::Storage>);
 }
 
+#[cfg(all(feature = "runtime-async-std", feature = "runtime-tokio"))]
+compile_error!("Only one of the `runtime-async-std` and `runtime-tokio` features can be enabled");
+
 #[cfg(feature = "runtime-async-std")]
 pub fn block_on<F, RET>(f: F) -> RET
 where
@@ -19,3 +35,47 @@ where
 {
     async_std::task::block_on(f)
 }
+
+#[cfg(feature = "runtime-async-std")]
+pub fn spawn<F>(f: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    drop(async_std::task::spawn(f));
+}
+
+/// The runtime that is used when [`block_on`] or [`spawn`] are called outside of a tokio runtime
+#[cfg(feature = "runtime-tokio")]
+static TOKIO_RUNTIME: std::sync::LazyLock<tokio::runtime::Runtime> =
+    std::sync::LazyLock::new(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("Could not start the tokio runtime")
+    });
+
+/// Block the current thread until `f` completes.
+///
+/// When called from within a tokio runtime, that runtime must be multi-threaded because the current worker thread
+/// is handed off with [`tokio::task::block_in_place`].
+#[cfg(feature = "runtime-tokio")]
+pub fn block_on<F, RET>(f: F) -> RET
+where
+    F: Future<Output = RET>,
+{
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(f)),
+        Err(_) => TOKIO_RUNTIME.block_on(f),
+    }
+}
+
+#[cfg(feature = "runtime-tokio")]
+pub fn spawn<F>(f: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => drop(handle.spawn(f)),
+        Err(_) => drop(TOKIO_RUNTIME.spawn(f)),
+    }
+}
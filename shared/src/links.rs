@@ -0,0 +1,191 @@
+//! Links between zettels.
+//!
+//! Links are written as `[name]` or `[name](path)`. If no path is given, the name is the path of the linked zettel.
+
+use crate::{
+    storage::{Error, SavedSearch, Storage, UserId, ZettelHeader, ZettelId},
+    template,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::LazyLock,
+};
+
+// https://regex101.com/r/wOR3xF/1
+// matches:
+// - [asd]
+// - [asd](dsa)
+// but not:
+// - `[asd]
+// - `[asd](dsa)
+static LINK_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?:^|[^`])(\[[^\]]+\])(\([^)]+\))?").unwrap());
+
+/// A link in the body of a zettel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link<'a> {
+    /// The range of the `[name]` part of the link
+    pub text: Range<usize>,
+    /// The range of the whole link, including the `(path)` part
+    pub range: Range<usize>,
+    /// The path that is linked to
+    pub target: &'a str,
+}
+
+/// All links in `text`, in order.
+///
+/// Links can't span multiple lines, so this can be called per line as well.
+pub fn parse(text: &str) -> impl Iterator<Item = Link<'_>> {
+    LINK_REGEX.captures_iter(text).map(|captures| {
+        let name = captures.get(1).unwrap();
+        let path = captures.get(2);
+        let target = match path {
+            Some(path) => path.as_str().trim_start_matches('(').trim_end_matches(')'),
+            None => name.as_str().trim_start_matches('[').trim_end_matches(']'),
+        };
+        Link {
+            text: name.range(),
+            range: name.start()..path.map_or_else(|| name.end(), |path| path.end()),
+            target,
+        }
+    })
+}
+
+/// Whether `target` links to a system page instead of a zettel
+pub fn is_sys_page(target: &str) -> bool {
+    target.starts_with("sys:")
+}
+
+/// The search of a `search:` link, like `[open questions](search:tag:question)`, which shows live results instead of
+/// a zettel. See [`search_for_link`].
+pub fn search_target(target: &str) -> Option<&str> {
+    target.strip_prefix("search:")
+}
+
+/// The search behind a [`search_target`]: the saved search with that name, or else a query in the
+/// [query language](crate::query)
+pub async fn search_for_link(
+    storage: &dyn Storage,
+    user: UserId,
+    search: &str,
+) -> Result<SavedSearch, Error> {
+    let saved = storage.get_saved_searches(user).await?;
+    Ok(saved
+        .into_iter()
+        .find(|saved| saved.name.eq_ignore_ascii_case(search))
+        .unwrap_or_else(|| SavedSearch {
+            name: search.to_owned(),
+            query: search.to_owned(),
+            ..Default::default()
+        }))
+}
+
+/// A link whose target does not exist
+#[derive(Debug, Clone)]
+pub struct BrokenLink {
+    /// The zettel that contains the link
+    pub source: ZettelHeader,
+    pub target: String,
+}
+
+/// The links between all zettels of a user
+pub struct LinkGraph {
+    pub zettels: Vec<ZettelHeader>,
+    /// The outgoing links of every zettel, by zettel id. Links to system pages are not included.
+    pub links: HashMap<ZettelId, Vec<String>>,
+    /// The ids of the zettels, by lowercase path or alias
+    ids: HashMap<String, ZettelId>,
+    templates: HashSet<ZettelId>,
+}
+
+impl LinkGraph {
+    /// Load the links of all zettels of `user`. This reads every zettel, so it's not cheap.
+    pub async fn load(storage: &dyn Storage, user: UserId) -> Result<Self, Error> {
+        let zettels = storage.get_all_zettels(user).await?;
+        let mut headers = Vec::with_capacity(zettels.len());
+        let mut links = HashMap::with_capacity(zettels.len());
+        let mut aliases = Vec::new();
+        for zettel in zettels {
+            let targets = parse(&zettel.body)
+                .filter(|link| !is_sys_page(link.target) && search_target(link.target).is_none())
+                .map(|link| link.target.to_owned())
+                .collect();
+            links.insert(zettel.id, targets);
+            aliases.extend(zettel.aliases.into_iter().map(|alias| (alias, zettel.id)));
+            headers.push(ZettelHeader {
+                id: zettel.id,
+                path: zettel.path,
+                snippets: Vec::new(),
+                folgezettel: zettel.folgezettel,
+            });
+        }
+        let mut graph = Self::new(headers, links);
+        for (alias, id) in aliases {
+            graph.ids.entry(alias.to_lowercase()).or_insert(id);
+        }
+        Ok(graph)
+    }
+
+    pub fn new(zettels: Vec<ZettelHeader>, links: HashMap<ZettelId, Vec<String>>) -> Self {
+        let ids = zettels
+            .iter()
+            .map(|zettel| (zettel.path.to_lowercase(), zettel.id))
+            .collect();
+        let templates = zettels
+            .iter()
+            .filter(|zettel| template::template_name(&zettel.path).is_some())
+            .map(|zettel| zettel.id)
+            .collect();
+        Self {
+            zettels,
+            links,
+            ids,
+            templates,
+        }
+    }
+
+    /// The id of the zettel at `path`, which may also be an alias. Paths are case insensitive.
+    pub fn resolve(&self, path: &str) -> Option<ZettelId> {
+        self.ids.get(&path.to_lowercase()).copied()
+    }
+
+    /// The zettels that are not linked to by any other zettel. Templates are not included, and neither are the links in
+    /// templates.
+    pub fn orphans(&self) -> Vec<&ZettelHeader> {
+        let linked: HashSet<ZettelId> = self
+            .links
+            .iter()
+            .filter(|(source, _)| !self.templates.contains(source))
+            .flat_map(|(source, targets)| {
+                targets
+                    .iter()
+                    .filter_map(|target| self.resolve(target))
+                    .filter(move |target| target != source)
+            })
+            .collect();
+        self.zettels
+            .iter()
+            .filter(|zettel| !linked.contains(&zettel.id) && !self.templates.contains(&zettel.id))
+            .collect()
+    }
+
+    /// The links whose target path has no zettel. Links in templates are not included.
+    pub fn broken_links(&self) -> Vec<BrokenLink> {
+        self.zettels
+            .iter()
+            .filter(|zettel| !self.templates.contains(&zettel.id))
+            .flat_map(|zettel| {
+                self.links
+                    .get(&zettel.id)
+                    .into_iter()
+                    .flatten()
+                    .filter(|target| self.resolve(target).is_none())
+                    .map(|target| BrokenLink {
+                        source: zettel.clone(),
+                        target: target.clone(),
+                    })
+            })
+            .collect()
+    }
+}
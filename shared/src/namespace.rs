@@ -0,0 +1,33 @@
+//! Zettel paths as a tree of namespaces.
+//!
+//! A `/` in a zettel path separates namespaces, so `project/rust/async` is the zettel `async` in the namespace
+//! `project/rust`. Namespaces exist implicitly as long as a zettel below them exists, and may be a zettel themselves.
+
+/// Separates the namespaces of a zettel path
+pub const SEPARATOR: char = '/';
+
+/// The namespace that `path` is in, or `None` for top level paths
+pub fn parent(path: &str) -> Option<&str> {
+    path.rsplit_once(SEPARATOR).map(|(parent, _)| parent)
+}
+
+/// The last segment of `path`
+pub fn name(path: &str) -> &str {
+    path.rsplit_once(SEPARATOR).map_or(path, |(_, name)| name)
+}
+
+/// The path of `name` in the namespace `parent`. An empty `parent` is the top level.
+pub fn join(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{parent}{SEPARATOR}{name}")
+    }
+}
+
+/// A `LIKE` pattern, escaped with `\`, that matches every path below `parent`. An empty `parent` matches every path.
+pub fn like_pattern(parent: &str) -> String {
+    let mut pattern = crate::query::escape_like(&join(parent, ""));
+    pattern.push('%');
+    pattern
+}
@@ -0,0 +1,427 @@
+//! The search query language of [`SearchOpts::query`](crate::storage::SearchOpts::query).
+//!
+//! A query is a list of conditions that all have to match:
+//!
+//! - `rust async`: zettels that contain both words in their path or body, ignoring case
+//! - `"async rust"`: the exact phrase, ignoring case
+//! - `path:project/`: zettels whose path starts with `project/`
+//! - `tag:rust`: zettels with the [front matter](crate::frontmatter) property `tags: rust` or `tag: rust`
+//! - `before:2023-03-01` and `after:2023-03-01`: zettels that were created before or after that day
+//! - `links-to:rust`: zettels that link to the path `rust`
+//! - `/ru?st/`: zettels whose path or body match the regex. A `/` in the regex is written as `\/`.
+//!
+//! Conditions can be combined with `AND`, `OR`, `NOT` (or a leading `-`) and parentheses, e.g.
+//! `tag:rust (async OR "futures") -path:journal/`. `AND` binds stronger than `OR`. Values of fields can be quoted, as
+//! in `path:"my notes/"`.
+//!
+//! Every backend compiles a parsed [`Query`] into its own SQL dialect.
+
+use chrono::NaiveDate;
+use regex::Regex;
+use snafu::{ResultExt, Snafu};
+use std::{iter::Peekable, str::Chars};
+
+/// The front matter keys that are searched by `tag:`
+pub const TAG_KEYS: [&str; 2] = ["tag", "tags"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// A word or phrase in the path or body, ignoring case
+    Term(String),
+    /// The path starts with this prefix, ignoring case
+    Path(String),
+    /// One of the [`TAG_KEYS`] properties has this value, ignoring case
+    Tag(String),
+    /// The zettel was created before this day
+    Before(NaiveDate),
+    /// The zettel was created after this day
+    After(NaiveDate),
+    /// The body has a link to this path, ignoring case
+    LinksTo(String),
+    /// The path or body match this regex
+    Regex(String),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+#[derive(Debug, Snafu)]
+pub enum ParseError {
+    #[snafu(display("The query is empty"))]
+    Empty,
+    #[snafu(display("A phrase is missing its closing `\"`"))]
+    UnterminatedPhrase,
+    #[snafu(display("A regex is missing its closing `/`"))]
+    UnterminatedRegex,
+    #[snafu(display("Invalid regex: {source}"))]
+    InvalidRegex { source: regex::Error },
+    #[snafu(display("{value:?} is not a date, expected YYYY-MM-DD"))]
+    InvalidDate { value: String },
+    #[snafu(display("`{field}:` needs a value"))]
+    MissingValue { field: String },
+    #[snafu(display("`{operator}` needs a condition after it"))]
+    MissingOperand { operator: &'static str },
+    #[snafu(display("The parentheses do not match"))]
+    UnbalancedParentheses,
+}
+
+impl std::str::FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+        };
+        if parser.tokens.peek().is_none() {
+            return Err(ParseError::Empty);
+        }
+        let query = parser.or()?;
+        match parser.tokens.next() {
+            None => Ok(query),
+            Some(_) => Err(ParseError::UnbalancedParentheses),
+        }
+    }
+}
+
+impl Query {
+    /// A case insensitive regex that finds the terms and regexes of this query, to highlight them in the results.
+    /// Conditions inside a `NOT` are not included.
+    pub fn highlight_regex(&self) -> Option<Regex> {
+        let mut patterns = Vec::new();
+        self.highlight_patterns(&mut patterns);
+        if patterns.is_empty() {
+            return None;
+        }
+        Regex::new(&format!("(?i){}", patterns.join("|"))).ok()
+    }
+
+    fn highlight_patterns(&self, patterns: &mut Vec<String>) {
+        match self {
+            Self::Term(term) => patterns.push(regex::escape(term)),
+            Self::Regex(regex) => patterns.push(format!("(?:{regex})")),
+            Self::And(queries) | Self::Or(queries) => {
+                for query in queries {
+                    query.highlight_patterns(patterns);
+                }
+            }
+            Self::Path(_)
+            | Self::Tag(_)
+            | Self::Before(_)
+            | Self::After(_)
+            | Self::LinksTo(_)
+            | Self::Not(_) => {}
+        }
+    }
+}
+
+/// A value that a backend binds to its compiled query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Param {
+    Text(String),
+    Date(NaiveDate),
+}
+
+/// Escape the wildcards of a `LIKE` pattern, using `\` as the escape character
+pub fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A regex that finds links to `target` in a body, see [`crate::links`]. It only uses syntax that POSIX regexes
+/// understand as well, and backends have to match it case insensitively.
+pub fn links_to_regex(target: &str) -> String {
+    let target = regex::escape(target);
+    format!(r"(^|[^`])\[{target}\]([^(]|$)|\]\({target}\)")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    /// A term, phrase or field
+    Condition(Query),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                tokens.push(Token::Condition(Query::Term(phrase(&mut chars)?)));
+            }
+            '/' => {
+                chars.next();
+                let regex = regex(&mut chars)?;
+                Regex::new(&regex).context(InvalidRegexSnafu)?;
+                tokens.push(Token::Condition(Query::Regex(regex)));
+            }
+            '-' => {
+                chars.next();
+                tokens.push(Token::Not);
+            }
+            _ => tokens.push(word(&mut chars)?),
+        }
+    }
+    Ok(tokens)
+}
+
+/// The rest of a phrase, after the opening `"`
+fn phrase(chars: &mut Peekable<Chars<'_>>) -> Result<String, ParseError> {
+    let mut phrase = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(phrase);
+        }
+        phrase.push(c);
+    }
+    Err(ParseError::UnterminatedPhrase)
+}
+
+/// The rest of a regex, after the opening `/`
+fn regex(chars: &mut Peekable<Chars<'_>>) -> Result<String, ParseError> {
+    let mut regex = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' => return Ok(regex),
+            '\\' if chars.peek() == Some(&'/') => {
+                regex.push('/');
+                chars.next();
+            }
+            '\\' => {
+                regex.push('\\');
+                if let Some(escaped) = chars.next() {
+                    regex.push(escaped);
+                }
+            }
+            c => regex.push(c),
+        }
+    }
+    Err(ParseError::UnterminatedRegex)
+}
+
+/// An operator, a term or a `field:value`
+fn word(chars: &mut Peekable<Chars<'_>>) -> Result<Token, ParseError> {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    match word.as_str() {
+        "AND" => return Ok(Token::And),
+        "OR" => return Ok(Token::Or),
+        "NOT" => return Ok(Token::Not),
+        _ => {}
+    }
+
+    let Some((field, value)) = word.split_once(':') else {
+        return Ok(Token::Condition(Query::Term(word)));
+    };
+    let field = field.to_ascii_lowercase();
+    if !matches!(
+        field.as_str(),
+        "path" | "tag" | "before" | "after" | "links-to"
+    ) {
+        // something like `https://`, which is just a term
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            word.push_str(&phrase(chars)?);
+        }
+        return Ok(Token::Condition(Query::Term(word)));
+    }
+    let value = if value.is_empty() && chars.peek() == Some(&'"') {
+        chars.next();
+        phrase(chars)?
+    } else {
+        value.to_owned()
+    };
+    if value.is_empty() {
+        return Err(ParseError::MissingValue { field });
+    }
+    let date = |value: &str| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| ParseError::InvalidDate {
+            value: value.to_owned(),
+        })
+    };
+    Ok(Token::Condition(match field.as_str() {
+        "path" => Query::Path(value),
+        "tag" => Query::Tag(value),
+        "before" => Query::Before(date(&value)?),
+        "after" => Query::After(date(&value)?),
+        _ => Query::LinksTo(value),
+    }))
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.and()?];
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            if matches!(self.tokens.peek(), None | Some(Token::Close | Token::Or)) {
+                return Err(ParseError::MissingOperand { operator: "OR" });
+            }
+            queries.push(self.and()?);
+        }
+        Ok(flatten(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.unary()?];
+        loop {
+            if self.tokens.next_if_eq(&Token::And).is_some() {
+                if matches!(self.tokens.peek(), None | Some(Token::Close | Token::Or)) {
+                    return Err(ParseError::MissingOperand { operator: "AND" });
+                }
+            } else if matches!(self.tokens.peek(), None | Some(Token::Close | Token::Or)) {
+                break;
+            }
+            queries.push(self.unary()?);
+        }
+        Ok(flatten(queries, Query::And))
+    }
+
+    fn unary(&mut self) -> Result<Query, ParseError> {
+        match self.tokens.next() {
+            Some(Token::Not) => {
+                if matches!(
+                    self.tokens.peek(),
+                    None | Some(Token::Close | Token::Or | Token::And)
+                ) {
+                    return Err(ParseError::MissingOperand { operator: "NOT" });
+                }
+                Ok(Query::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Open) => {
+                let query = self.or()?;
+                match self.tokens.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(ParseError::UnbalancedParentheses),
+                }
+            }
+            Some(Token::Condition(query)) => Ok(query),
+            Some(Token::Close) => Err(ParseError::UnbalancedParentheses),
+            Some(Token::And) => Err(ParseError::MissingOperand { operator: "AND" }),
+            Some(Token::Or) => Err(ParseError::MissingOperand { operator: "OR" }),
+            None => Err(ParseError::Empty),
+        }
+    }
+}
+
+/// A single query stays as it is, more are combined with `combine`
+fn flatten(mut queries: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        queries.remove(0)
+    } else {
+        combine(queries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{self, SearchOpts};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "rust -path:journal/ (tag:async OR \"futures crate\")"
+                .parse::<Query>()
+                .unwrap(),
+            Query::And(vec![
+                Query::Term("rust".to_owned()),
+                Query::Not(Box::new(Query::Path("journal/".to_owned()))),
+                Query::Or(vec![
+                    Query::Tag("async".to_owned()),
+                    Query::Term("futures crate".to_owned()),
+                ]),
+            ])
+        );
+        assert_eq!(
+            r"/a\/b/ OR NOT links-to:x".parse::<Query>().unwrap(),
+            Query::Or(vec![
+                Query::Regex("a/b".to_owned()),
+                Query::Not(Box::new(Query::LinksTo("x".to_owned()))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!("".parse::<Query>(), Err(ParseError::Empty)));
+        assert!(matches!(
+            "\"open".parse::<Query>(),
+            Err(ParseError::UnterminatedPhrase)
+        ));
+        assert!(matches!(
+            "/[/".parse::<Query>(),
+            Err(ParseError::InvalidRegex { .. })
+        ));
+        assert!(matches!(
+            "before:yesterday".parse::<Query>(),
+            Err(ParseError::InvalidDate { .. })
+        ));
+        assert!(matches!(
+            "a OR".parse::<Query>(),
+            Err(ParseError::MissingOperand { .. })
+        ));
+        assert!(matches!(
+            "(a".parse::<Query>(),
+            Err(ParseError::UnbalancedParentheses)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_query() {
+        let opts = SearchOpts {
+            query: "(rust",
+            ..Default::default()
+        };
+        assert!(matches!(
+            opts.highlight_regex(),
+            Err(storage::Error::InvalidQuery { .. })
+        ));
+    }
+
+    #[test]
+    fn test_highlight_regex() {
+        let query: Query = "Rust -async /fut[a-z]+/ path:x".parse().unwrap();
+        let regex = query.highlight_regex().unwrap();
+        assert!(regex.is_match("RUST"));
+        assert!(regex.is_match("futures"));
+        assert!(!regex.is_match("async"));
+        assert!(!regex.is_match("x"));
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like(r"100% a_b\c"), r"100\% a\_b\\c");
+    }
+}
@@ -0,0 +1,168 @@
+//! Short excerpts of a zettel body around the matches of a search, see [`crate::storage::ZettelHeader::snippets`].
+
+use regex::Regex;
+use std::ops::Range;
+
+/// The number of characters that are shown before and after a match, at most
+pub const CONTEXT: usize = 20;
+/// The number of snippets that are built for a single body, at most
+pub const MAX_SNIPPETS: usize = 3;
+/// Marks that a snippet does not start or end with the body
+pub const ELLIPSIS: &str = "…";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snippet {
+    /// A single line of text, as line breaks are replaced with spaces
+    pub text: String,
+    /// The byte ranges of the matches in `text`
+    pub matches: Vec<Range<usize>>,
+}
+
+/// Up to [`MAX_SNIPPETS`] snippets of `body` around the matches of `regex`. Matches that are close to each other share
+/// a snippet, and snippets are never cut in the middle of a word unless the word itself is too long.
+pub fn build(body: &str, regex: &Regex) -> Vec<Snippet> {
+    // the part of the body that a snippet shows, and the matches in it
+    let mut windows: Vec<(Range<usize>, Vec<Range<usize>>)> = Vec::new();
+    for found in regex
+        .find_iter(body)
+        .filter(|found| !found.range().is_empty())
+    {
+        let start = context_start(body, found.start());
+        let end = context_end(body, found.end());
+        if let Some((window, matches)) =
+            windows.last_mut().filter(|(window, _)| start <= window.end)
+        {
+            window.end = window.end.max(end);
+            matches.push(found.range());
+        } else if windows.len() == MAX_SNIPPETS {
+            break;
+        } else {
+            windows.push((start..end, vec![found.range()]));
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|(window, matches)| {
+            let mut text = String::new();
+            if window.start > 0 {
+                text.push_str(ELLIPSIS);
+            }
+            let offset = text.len();
+            // every line break is a single byte, just like the space, so the match ranges stay the same
+            text.push_str(&body[window.clone()].replace(['\n', '\r', '\t'], " "));
+            if window.end < body.len() {
+                text.push_str(ELLIPSIS);
+            }
+            Snippet {
+                text,
+                matches: matches
+                    .into_iter()
+                    .map(|found| {
+                        found.start - window.start + offset..found.end - window.start + offset
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Where the snippet for a match at `at` starts
+fn context_start(body: &str, at: usize) -> usize {
+    let before = &body[..at];
+    let mut start = before
+        .char_indices()
+        .rev()
+        .nth(CONTEXT - 1)
+        .map_or(0, |(idx, _)| idx);
+    let cut_word = before[..start]
+        .chars()
+        .next_back()
+        .is_some_and(|c| !c.is_whitespace())
+        && before[start..]
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_whitespace());
+    if cut_word {
+        // skip the rest of the word that was cut
+        start = before[start..]
+            .find(char::is_whitespace)
+            .map_or(at, |idx| start + idx);
+    }
+    at - before[start..].trim_start().len()
+}
+
+/// Where the snippet for a match that ends at `at` ends
+fn context_end(body: &str, at: usize) -> usize {
+    let after = &body[at..];
+    let mut end = after
+        .char_indices()
+        .nth(CONTEXT)
+        .map_or(after.len(), |(idx, _)| idx);
+    let cut_word = after[end..]
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_whitespace())
+        && after[..end]
+            .chars()
+            .next_back()
+            .is_some_and(|c| !c.is_whitespace());
+    if cut_word {
+        end = after[..end].rfind(char::is_whitespace).unwrap_or(0);
+    }
+    at + after[..end].trim_end().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regex(pattern: &str) -> Regex {
+        Regex::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn test_multi_byte() {
+        // multi byte characters around the match don't split
+        let snippets = build("Gedanken Über Rust", &regex("(?i)über"));
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].text, "Gedanken Über Rust");
+        assert_eq!(snippets[0].matches, vec![9..14]);
+    }
+
+    #[test]
+    fn test_context() {
+        // words are not cut, line breaks become spaces and close matches share a snippet
+        let body = "Ein sehr langer Satz mit Wörtern\nüber über und noch viel mehr Wörtern danach und so weiter";
+        let snippets = build(body, &regex("(?i)über"));
+        assert_eq!(snippets.len(), 1);
+        let text = &snippets[0].text;
+        assert_eq!(text, "…Satz mit Wörtern über über und noch viel mehr…");
+        let found: Vec<&str> = snippets[0]
+            .matches
+            .iter()
+            .map(|m| &text[m.clone()])
+            .collect();
+        assert_eq!(found, ["über", "über"]);
+    }
+
+    #[test]
+    fn test_max_snippets() {
+        let body = format!("日本語{}日本語", "x ".repeat(50)).repeat(5);
+        let snippets = build(&body, &regex("日本"));
+        assert_eq!(snippets.len(), MAX_SNIPPETS);
+        for snippet in snippets {
+            assert!(snippet
+                .matches
+                .iter()
+                .all(|m| &snippet.text[m.clone()] == "日本"));
+        }
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert!(build("Rust", &regex("python")).is_empty());
+        // empty matches are no matches
+        assert!(build("Rust", &regex("x*")).is_empty());
+    }
+}
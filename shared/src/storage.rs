@@ -1,6 +1,6 @@
 use crate::async_trait;
-use futures::future::LocalBoxFuture;
-use std::{path::PathBuf, sync::Arc};
+use futures::{future::LocalBoxFuture, stream::BoxStream};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
 
 pub type ZettelId = i64;
 pub type UserId = i64;
@@ -11,20 +11,64 @@ pub trait Storage: Send + Sync {
     async fn login_single_user(&self) -> Result<User, Error>;
     async fn login(&self, username: &str, password: &str) -> Result<Option<User>, Error>;
     async fn register(&self, username: &str, password: &str) -> Result<User, Error>;
+    /// The user with the id `user`, or `None` if there is none
+    async fn get_user(&self, user: UserId) -> Result<Option<User>, Error>;
     async fn get_zettels(
         &self,
         user: UserId,
         search: SearchOpts<'_>,
     ) -> Result<Vec<ZettelHeader>, Error>;
+    /// The direct children of the namespace `parent`, ordered by name. An empty `parent` lists the top level.
+    ///
+    /// See [`crate::namespace`].
+    async fn get_children(&self, user: UserId, parent: &str) -> Result<Vec<PathChild>, Error>;
     async fn get_zettel(&self, user: UserId, id: ZettelId) -> Result<Zettel, Error>;
+    /// The zettel whose path or one of whose [aliases](Zettel::aliases) is `url`
     async fn get_zettel_by_url(&self, user: UserId, url: &str) -> Result<Option<Zettel>, Error>;
+    /// All zettels of `user` with their bodies and aliases, ordered by path. Unlike calling [`Storage::get_zettel`]
+    /// for every zettel, this does not take a query per zettel.
+    async fn get_all_zettels(&self, user: UserId) -> Result<Vec<Zettel>, Error>;
     async fn update_zettel(&self, user: UserId, zettel: &mut Zettel) -> Result<(), Error>;
+    /// Replace the body of the zettel `id` with `body`, keeping the path, aliases and folgezettel that are stored.
+    /// If `base` is set, the body is only replaced if it is still `base`, which is checked in the same statement as
+    /// the update. Returns the updated zettel.
+    ///
+    /// Fails with [`Error::EditConflict`] if the body is not `base`, and [`Error::ZettelNotFound`] if the zettel does
+    /// not exist.
+    async fn update_zettel_body(
+        &self,
+        user: UserId,
+        id: ZettelId,
+        base: Option<&str>,
+        body: &str,
+    ) -> Result<Zettel, Error>;
+    async fn delete_zettel(&self, user: UserId, id: ZettelId) -> Result<(), Error>;
     async fn set_user_last_visited_zettel(
         &self,
         user: UserId,
         zettel_id: Option<ZettelId>,
     ) -> Result<(), Error>;
     async fn update_config(&self, config: &SystemConfig) -> Result<(), Error>;
+    async fn get_user_config(&self, user: UserId) -> Result<UserConfig, Error>;
+    async fn update_user_config(&self, user: UserId, config: &UserConfig) -> Result<(), Error>;
+    /// The searches that `user` saved, ordered by name
+    async fn get_saved_searches(&self, user: UserId) -> Result<Vec<SavedSearch>, Error>;
+    /// Save `search`, replacing the saved search with the same name. Names are case insensitive.
+    async fn save_search(&self, user: UserId, search: &SavedSearch) -> Result<(), Error>;
+    async fn delete_saved_search(&self, user: UserId, name: &str) -> Result<(), Error>;
+    /// Apply all `operations` in a single transaction. If any of the operations fails, none of them are applied.
+    ///
+    /// The returned list contains a [`BatchResult`] for every operation, in the same order as `operations`.
+    /// Zettels created by [`BatchOperation::UpdateZettel`] will have their id updated, like [`Storage::update_zettel`].
+    async fn batch(
+        &self,
+        user: UserId,
+        operations: &mut [BatchOperation],
+    ) -> Result<Vec<BatchResult>, Error>;
+    /// Subscribe to the changes that are made to this storage.
+    ///
+    /// Depending on the implementation this might only report changes that are made from within this process.
+    async fn subscribe(&self) -> Result<BoxStream<'static, StorageEvent>, Error>;
 }
 
 pub trait ConnectableStorage: Storage + Sized {
@@ -40,19 +84,112 @@ pub struct User {
     pub name: String,
     pub password: String,
     pub last_visited_zettel: Option<ZettelId>,
+    /// Whether this user may change the [`SystemConfig`]. The first user that registers is an admin.
+    pub is_admin: bool,
 }
 
 #[derive(Default)]
 pub struct SearchOpts<'a> {
+    /// What to search for, interpreted according to `mode`
     pub query: &'a str,
+    pub mode: SearchMode,
     pub list_all: bool,
+    /// Only return zettels that have all of these [front matter](crate::frontmatter) properties. Can be used without
+    /// a `query` as well.
+    pub properties: &'a [PropertyFilter],
+}
+
+impl SearchOpts<'_> {
+    /// A regex that finds the matches of `query` in a body, to build [`ZettelHeader::snippets`]. This also checks
+    /// that `query` is valid in its `mode`, so backends call it before they search.
+    ///
+    /// Returns `None` if there is nothing to highlight.
+    pub fn highlight_regex(&self) -> Result<Option<regex::Regex>, Error> {
+        use snafu::ResultExt;
+
+        if self.list_all || self.query.trim().is_empty() {
+            return Ok(None);
+        }
+        let pattern = match self.mode {
+            SearchMode::Query => {
+                let query: crate::query::Query = self.query.parse().context(InvalidQuerySnafu)?;
+                return Ok(query.highlight_regex());
+            }
+            SearchMode::Literal => regex::escape(self.query),
+            SearchMode::LiteralIgnoreCase => format!("(?i){}", regex::escape(self.query)),
+            SearchMode::Regex => self.query.to_owned(),
+            SearchMode::RegexIgnoreCase => format!("(?i){}", self.query),
+        };
+        regex::Regex::new(&pattern)
+            .map(Some)
+            .context(InvalidRegexSnafu)
+    }
+}
+
+/// A search that a user saved under a name, see [`Storage::save_search`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+    pub mode: SearchMode,
+    pub properties: Vec<PropertyFilter>,
+}
+
+impl SavedSearch {
+    pub fn opts(&self) -> SearchOpts<'_> {
+        SearchOpts {
+            query: &self.query,
+            mode: self.mode,
+            list_all: false,
+            properties: &self.properties,
+        }
+    }
+}
+
+/// A front matter property that has to be present, written as `key = value`. Keys and values are case insensitive.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+pub struct PropertyFilter {
+    pub key: String,
+    pub value: String,
+}
+
+impl std::str::FromStr for PropertyFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((key, value)) = s.split_once('=') else {
+            return Err(format!("Expected `key = value`, got {s:?}"));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() {
+            return Err(format!("Missing the key in {s:?}"));
+        }
+        Ok(Self {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
 }
 
-#[derive(sqlx::FromRow)]
+#[derive(Clone, Debug)]
 pub struct ZettelHeader {
     pub id: ZettelId,
     pub path: String,
-    pub highlight_text: Option<String>,
+    /// Excerpts of the body around the matches of [`SearchOpts::query`], see [`crate::snippet`]
+    pub snippets: Vec<crate::snippet::Snippet>,
+    /// See [`crate::folgezettel`]
+    pub folgezettel: Option<String>,
+}
+
+/// A direct child of a namespace, see [`Storage::get_children`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathChild {
+    /// The full path of the child
+    pub path: String,
+    /// The zettel at exactly this path, if there is one
+    pub zettel: Option<ZettelId>,
+    /// The number of zettels below this path, not counting the zettel at this path
+    pub descendants: u64,
 }
 
 #[derive(sqlx::FromRow, Default, Clone, custom_debug::Debug)]
@@ -60,10 +197,88 @@ pub struct Zettel {
     pub id: ZettelId,
     pub path: String,
     pub body: String,
+    /// The Folgezettel id of this zettel, see [`crate::folgezettel`]. Must be unique per user.
+    pub folgezettel: Option<String>,
+    /// Other paths under which this zettel can be found. They may not be the path or an alias of any other zettel.
+    pub aliases: Vec<String>,
     #[debug(skip)]
     pub attachments: Vec<Arc<dyn Attachment>>,
 }
 
+impl Zettel {
+    /// Trim the aliases, and remove empty ones as well as duplicates of the path or of each other. Like paths, aliases
+    /// are case insensitive.
+    pub fn normalize_aliases(&mut self) {
+        let mut seen = std::collections::HashSet::from([self.path.to_lowercase()]);
+        self.aliases.retain_mut(|alias| {
+            *alias = alias.trim().to_owned();
+            !alias.is_empty() && seen.insert(alias.to_lowercase())
+        });
+    }
+}
+
+#[derive(Debug)]
+pub enum BatchOperation {
+    /// Create the zettel if its id is `0`, update it otherwise
+    UpdateZettel(Zettel),
+    DeleteZettel(ZettelId),
+    UpdateConfig(SystemConfig),
+    UpdateUserConfig(UserConfig),
+}
+
+impl BatchOperation {
+    /// The event that is emitted when this operation is committed
+    pub fn event(&self, user: UserId) -> StorageEvent {
+        match self {
+            Self::UpdateZettel(zettel) => StorageEvent::ZettelChanged {
+                user,
+                id: zettel.id,
+            },
+            Self::DeleteZettel(id) => StorageEvent::ZettelDeleted { user, id: *id },
+            Self::UpdateConfig(_) => StorageEvent::ConfigChanged,
+            Self::UpdateUserConfig(_) => StorageEvent::UserConfigChanged { user },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BatchResult {
+    /// The operation was applied and committed
+    Committed,
+    /// The operation succeeded, but was rolled back because another operation in the batch failed
+    RolledBack,
+    /// The operation failed, and the whole batch was rolled back
+    Failed(Error),
+    /// The operation was not attempted because an earlier operation in the batch failed
+    Skipped,
+}
+
+impl BatchResult {
+    /// Turn the outcome of each operation into a list of [`BatchResult`]. `outcome` holds the index and error of the
+    /// first failed operation, if any.
+    pub fn from_outcome(len: usize, outcome: Option<(usize, Error)>) -> Vec<BatchResult> {
+        let Some((failed_idx, error)) = outcome else {
+            return (0..len).map(|_| BatchResult::Committed).collect();
+        };
+        let mut error = Some(error);
+        (0..len)
+            .map(|idx| match idx.cmp(&failed_idx) {
+                std::cmp::Ordering::Less => BatchResult::RolledBack,
+                std::cmp::Ordering::Equal => BatchResult::Failed(error.take().unwrap()),
+                std::cmp::Ordering::Greater => BatchResult::Skipped,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub enum StorageEvent {
+    ZettelChanged { user: UserId, id: ZettelId },
+    ZettelDeleted { user: UserId, id: ZettelId },
+    ConfigChanged,
+    UserConfigChanged { user: UserId },
+}
+
 #[async_trait]
 pub trait Attachment: Send + Sync {
     fn name(&self) -> &str;
@@ -73,15 +288,42 @@ pub trait Attachment: Send + Sync {
 #[derive(Debug, snafu::Snafu)]
 #[snafu(visibility(pub))]
 pub enum Error {
-    Bcrypt { source: bcrypt::BcryptError },
-    Json { source: serde_json::Error },
-    Sqlx { source: sqlx::Error },
-    SqlxMigrate { source: sqlx::migrate::MigrateError },
-    InvalidRegex { source: regex::Error },
+    Bcrypt {
+        source: bcrypt::BcryptError,
+    },
+    Json {
+        source: serde_json::Error,
+    },
+    Sqlx {
+        source: sqlx::Error,
+    },
+    SqlxMigrate {
+        source: sqlx::migrate::MigrateError,
+    },
+    InvalidRegex {
+        source: regex::Error,
+    },
+    InvalidQuery {
+        source: crate::query::ParseError,
+    },
 
     SingleUserNotFound,
     UserAlreadyExists,
     InvalidSearchOpts,
+    ZettelNotFound {
+        id: ZettelId,
+    },
+    /// The body of the zettel was changed since it was loaded
+    EditConflict {
+        id: ZettelId,
+    },
+    InvalidFolgezettel {
+        folgezettel: String,
+    },
+    /// The path or alias is already used by another zettel
+    PathInUse {
+        path: String,
+    },
 }
 
 #[derive(Default, Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -99,3 +341,74 @@ pub enum UserMode {
     SingleUserManualLogin,
     MultiUser,
 }
+
+#[derive(Default, Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct UserConfig {
+    /// The editor used by the terminal front. If `None`, [`SystemConfig::terminal_editor`] is used.
+    #[serde(default)]
+    pub editor: Option<PathBuf>,
+    #[serde(default)]
+    pub theme: Theme,
+    /// The path of the zettel to open on login. If `None`, the last visited zettel is opened.
+    #[serde(default)]
+    pub landing_zettel: Option<String>,
+    #[serde(default)]
+    pub search_mode: SearchMode,
+    /// The template that is used for new zettels, by path prefix. See [`crate::template::default_template`].
+    #[serde(default)]
+    pub default_templates: BTreeMap<String, String>,
+    /// The name of the template for new daily notes, see [`crate::journal`]
+    #[serde(default)]
+    pub journal_template: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// How [`SearchOpts::query`] is interpreted
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+pub enum SearchMode {
+    /// A query in the [query language](crate::query)
+    #[default]
+    Query,
+    /// The exact text
+    Literal,
+    /// The text, ignoring case
+    LiteralIgnoreCase,
+    Regex,
+    /// A regex that ignores case
+    RegexIgnoreCase,
+}
+
+impl SearchMode {
+    pub const ALL: [Self; 5] = [
+        Self::Query,
+        Self::Literal,
+        Self::LiteralIgnoreCase,
+        Self::Regex,
+        Self::RegexIgnoreCase,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Query => "query",
+            Self::Literal => "literal",
+            Self::LiteralIgnoreCase => "literal, ignore case",
+            Self::Regex => "regex",
+            Self::RegexIgnoreCase => "regex, ignore case",
+        }
+    }
+
+    /// The mode after this one in [`Self::ALL`], wrapping around at the end
+    #[must_use]
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
@@ -0,0 +1,179 @@
+//! Zettel templates.
+//!
+//! Templates are regular zettels whose path starts with [`TEMPLATE_PREFIX`]. When a new zettel is created from a
+//! template, the placeholders in the body of the template are replaced:
+//!
+//! - `{{path}}`: the path of the new zettel
+//! - `{{date}}`: the current date, or the day of a daily note, formatted as `YYYY-MM-DD`
+//! - `{{user}}`: the name of the user that creates the zettel
+//!
+//! Unknown placeholders are kept as they are.
+
+use crate::storage::{Error, SearchOpts, Storage, User, UserConfig, UserId, Zettel, ZettelHeader};
+
+/// The path prefix under which templates are stored
+pub const TEMPLATE_PREFIX: &str = "templates/";
+
+/// The values that are filled in for the placeholders of a template
+pub struct TemplateVars<'a> {
+    pub path: &'a str,
+    pub date: chrono::NaiveDate,
+    pub user: &'a str,
+}
+
+impl<'a> TemplateVars<'a> {
+    /// The variables for a zettel at `path` that is created today by `user`
+    pub fn new(path: &'a str, user: &'a User) -> Self {
+        Self {
+            path,
+            date: chrono::Local::now().date_naive(),
+            user: &user.name,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "path" => Some(self.path.to_owned()),
+            "date" => Some(self.date.format("%Y-%m-%d").to_string()),
+            "user" => Some(self.user.to_owned()),
+            _ => None,
+        }
+    }
+}
+
+/// The name of a template, e.g. `literature` for the template at `templates/literature`
+pub fn template_name(path: &str) -> Option<&str> {
+    let prefix = path.get(..TEMPLATE_PREFIX.len())?;
+    if prefix.eq_ignore_ascii_case(TEMPLATE_PREFIX) {
+        Some(&path[TEMPLATE_PREFIX.len()..])
+    } else {
+        None
+    }
+}
+
+/// The path of the template with the given `name`
+pub fn template_path(name: &str) -> String {
+    format!("{TEMPLATE_PREFIX}{name}")
+}
+
+/// Replace the placeholders in `body` with `vars`
+pub fn render(body: &str, vars: &TemplateVars<'_>) -> String {
+    let mut result = String::with_capacity(body.len());
+    let mut remaining = body;
+    while let Some(start) = remaining.find("{{") {
+        let Some(end) = remaining[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        result.push_str(&remaining[..start]);
+        match vars.get(remaining[start + 2..end].trim()) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&remaining[start..end + 2]),
+        }
+        remaining = &remaining[end + 2..];
+    }
+    result.push_str(remaining);
+    result
+}
+
+/// All templates of `user`, sorted by path
+pub async fn list_templates(
+    storage: &dyn Storage,
+    user: &User,
+) -> Result<Vec<ZettelHeader>, Error> {
+    let zettels = storage
+        .get_zettels(
+            user.id,
+            SearchOpts {
+                list_all: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+    Ok(zettels
+        .into_iter()
+        .filter(|zettel| template_name(&zettel.path).is_some_and(|name| !name.is_empty()))
+        .collect())
+}
+
+/// The name of the template that is used by default for new zettels at `path`.
+///
+/// This is the template of the longest prefix in [`UserConfig::default_templates`] that matches `path`.
+pub fn default_template<'a>(config: &'a UserConfig, path: &str) -> Option<&'a str> {
+    config
+        .default_templates
+        .iter()
+        .filter(|(prefix, _)| {
+            path.get(..prefix.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, template)| template.as_str())
+}
+
+/// Create a new, unsaved zettel at `vars.path` from the template at `template_path`.
+///
+/// If the template does not exist, the zettel starts out empty.
+pub async fn new_zettel(
+    storage: &dyn Storage,
+    user: UserId,
+    template_path: &str,
+    vars: &TemplateVars<'_>,
+) -> Result<Zettel, Error> {
+    let template = storage.get_zettel_by_url(user, template_path).await?;
+    Ok(Zettel {
+        path: vars.path.to_owned(),
+        body: template
+            .map(|template| render(&template.body, vars))
+            .unwrap_or_default(),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(path: &str) -> TemplateVars<'_> {
+        TemplateVars {
+            path,
+            date: chrono::NaiveDate::from_ymd_opt(2023, 3, 1).unwrap(),
+            user: "ada",
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("# {{path}}\n{{ date }} by {{user}}", &vars("lit/paper")),
+            "# lit/paper\n2023-03-01 by ada"
+        );
+        // unknown and unterminated placeholders are kept
+        assert_eq!(
+            render("{{unknown}} {{path", &vars("x")),
+            "{{unknown}} {{path"
+        );
+    }
+
+    #[test]
+    fn test_template_name() {
+        assert_eq!(template_name("templates/literature"), Some("literature"));
+        assert_eq!(template_name("Templates/book"), Some("book"));
+        assert_eq!(template_name("notes/literature"), None);
+        assert_eq!(template_name(&template_path("book")), Some("book"));
+    }
+
+    #[test]
+    fn test_default_template() {
+        let mut config = UserConfig::default();
+        config
+            .default_templates
+            .insert("lit/".to_owned(), "literature".to_owned());
+        config
+            .default_templates
+            .insert("lit/books/".to_owned(), "book".to_owned());
+        assert_eq!(default_template(&config, "lit/paper"), Some("literature"));
+        // the longest prefix wins
+        assert_eq!(default_template(&config, "LIT/books/dune"), Some("book"));
+        assert_eq!(default_template(&config, "home"), None);
+    }
+}
@@ -0,0 +1,608 @@
+//! Subcommands that work on the storage without starting a front, so zettels can be used from shell scripts.
+//!
+//! Every command prints plain text by default and JSON with `--json`. Plain output is one zettel path per line where
+//! that makes sense, to pipe it into tools like `fzf`.
+
+use snafu::{ResultExt, Snafu};
+use std::io::{IsTerminal, Read, Write};
+use zettelkasten_shared::{
+    edit,
+    storage::{self, BatchOperation, BatchResult, PropertyFilter, SearchMode, SearchOpts, Storage},
+};
+
+/// A zettelkasten. Without a command the fronts are started.
+#[derive(Debug, clap::Parser)]
+#[command(version)]
+pub struct Args {
+    /// Print JSON instead of plain text
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Log in as this user, with the password that is asked for on the terminal. Without a user the single user is
+    /// logged in, which only works in the `SingleUserAutoLogin` user mode.
+    #[arg(long, global = true, env = "ZETTELKASTEN_USER")]
+    pub user: Option<String>,
+    /// Read the password of `--user` from the first line of stdin instead of asking for it, for scripts. The rest of
+    /// stdin is the input of the command.
+    #[arg(long, global = true, requires = "user")]
+    pub password_stdin: bool,
+    /// The config file to read instead of `<CONFIG_DIR>/zettelkasten/config.toml`
+    #[arg(long, global = true, env = "ZETTELKASTEN_CONFIG")]
+    pub config: Option<std::path::PathBuf>,
+    /// The database to open. Overrides `DATABASE_URL` and the config file.
+    #[arg(long, global = true)]
+    pub database_url: Option<String>,
+    /// The front to start when no command is given. Can be repeated to run the terminal and web fronts together.
+    /// Defaults to the fronts of the config file, or every front that is compiled in except `lsp`.
+    #[arg(
+        long = "front",
+        value_enum,
+        env = "ZETTELKASTEN_FRONT",
+        value_delimiter = ','
+    )]
+    pub fronts: Vec<Front>,
+    /// Override a setting of the config file, as `<key>=<value>` with a TOML value, e.g. `web.port=8081`. Can be
+    /// repeated.
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    pub settings: Vec<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Front {
+    Terminal,
+    Web,
+    Lsp,
+}
+
+impl Front {
+    pub const ALL: [Self; 3] = [Self::Terminal, Self::Web, Self::Lsp];
+
+    /// The cargo feature that compiles this front in
+    pub fn feature(self) -> &'static str {
+        match self {
+            Self::Terminal => "front-terminal",
+            Self::Web => "front-web",
+            Self::Lsp => "front-lsp",
+        }
+    }
+
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Self::Terminal => cfg!(feature = "front-terminal"),
+            Self::Web => cfg!(feature = "front-web"),
+            Self::Lsp => cfg!(feature = "front-lsp"),
+        }
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    #[command(flatten)]
+    Zettel(ZettelCommand),
+    /// Manage users
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Start the language server on stdio, the same as `--front lsp`
+    #[cfg(feature = "front-lsp")]
+    Lsp,
+}
+
+/// The commands that work on the zettels of the user that is logged in
+#[derive(Debug, clap::Subcommand)]
+pub enum ZettelCommand {
+    /// Print the body of the zettel at a path or alias
+    Show { path: String },
+    /// Create a zettel with the body read from stdin
+    New { path: String },
+    /// Edit a zettel in the configured editor. When stdin is not a terminal, its body is replaced with stdin instead.
+    /// Fails without saving if the zettel was changed somewhere else in the meantime.
+    Edit { path: String },
+    /// Print the zettels that match a query
+    Search {
+        query: String,
+        /// How the query is interpreted: query, literal, literalignorecase, regex or regexignorecase
+        #[arg(long, default_value = "query", value_parser = parse_mode)]
+        mode: SearchMode,
+        /// Only print zettels with this front matter property, as `key = value`. Can be repeated.
+        #[arg(long = "property")]
+        properties: Vec<PropertyFilter>,
+    },
+    /// Print the paths of all zettels, or of the zettels in a namespace
+    Ls { namespace: Option<String> },
+    /// Print all zettels as JSON, to `import` them elsewhere
+    Export,
+    /// Create or update the zettels of an `export` read from stdin. Zettels are matched by path.
+    Import,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective settings after applying the config file, the environment and the command line, and check
+    /// that the database can be opened
+    Check,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum UserCommand {
+    /// Register a user with the password that is asked for on the terminal, or read from the first line of stdin if
+    /// it is not a terminal. Unless the user mode is `MultiUser`, only the first user can be added.
+    Add { name: String },
+}
+
+fn parse_mode(s: &str) -> Result<SearchMode, String> {
+    SearchMode::ALL
+        .into_iter()
+        .find(|mode| format!("{mode:?}").eq_ignore_ascii_case(s))
+        .ok_or_else(|| format!("Unknown search mode {s:?}"))
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Database error: {source:?}"))]
+    Database { source: storage::Error },
+    #[snafu(display("{source}"))]
+    Io { source: std::io::Error },
+    #[snafu(display("Invalid JSON: {source}"))]
+    Json { source: serde_json::Error },
+    #[snafu(display("Could not log in. Pass `--user <username>` or set `ZETTELKASTEN_USER`."))]
+    Login,
+    #[snafu(display("Wrong username or password"))]
+    WrongPassword,
+    #[snafu(display("No zettel at `{path}`"))]
+    NotFound { path: String },
+    #[snafu(display("`{path}` is already used by another zettel"))]
+    PathInUse { path: String },
+    #[snafu(display("No editor configured. Set one in sys:config or set `EDITOR`."))]
+    NoEditor,
+    #[snafu(display("The editor failed with {status}, the zettel was not changed"))]
+    Editor { status: std::process::ExitStatus },
+    #[snafu(display(
+        "`{path}` was changed somewhere else while it was edited, so it was not saved"
+    ))]
+    EditConflict { path: String },
+    #[snafu(display(
+        "`{path}` was deleted somewhere else while it was edited, so it was not saved"
+    ))]
+    DeletedWhileEditing { path: String },
+    #[snafu(display("Could not keep the edited file: {source}"))]
+    Keep { source: tempfile::PersistError },
+    #[snafu(display("Could not import `{path}`: {source:?}"))]
+    Import {
+        path: String,
+        source: storage::Error,
+    },
+    #[snafu(display("Users can only be added in the `MultiUser` user mode"))]
+    RegistrationClosed,
+    #[snafu(display("`{name}` is already taken"))]
+    UserExists { name: String },
+    #[snafu(display("The password may not be empty"))]
+    EmptyPassword,
+}
+
+type Result<T = (), E = Error> = std::result::Result<T, E>;
+
+/// A zettel as it is printed and exported
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Zettel {
+    /// Left out of exports, as ids differ between databases
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<storage::ZettelId>,
+    path: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    folgezettel: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+impl From<storage::Zettel> for Zettel {
+    fn from(zettel: storage::Zettel) -> Self {
+        Self {
+            id: Some(zettel.id),
+            path: zettel.path,
+            body: zettel.body,
+            folgezettel: zettel.folgezettel,
+            aliases: zettel.aliases,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ZettelHeader<'a> {
+    id: storage::ZettelId,
+    path: &'a str,
+    folgezettel: Option<&'a str>,
+    /// The lines around the matches of a search
+    snippets: Vec<&'a str>,
+}
+
+impl<'a> From<&'a storage::ZettelHeader> for ZettelHeader<'a> {
+    fn from(header: &'a storage::ZettelHeader) -> Self {
+        Self {
+            id: header.id,
+            path: &header.path,
+            folgezettel: header.folgezettel.as_deref(),
+            snippets: header
+                .snippets
+                .iter()
+                .map(|snippet| snippet.text.as_str())
+                .collect(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct User<'a> {
+    id: storage::UserId,
+    name: &'a str,
+    is_admin: bool,
+}
+
+/// Log in, then run `command` and print its output
+pub async fn run(
+    args: &Args,
+    command: &ZettelCommand,
+    system_config: &storage::SystemConfig,
+    storage: &dyn Storage,
+) -> Result {
+    let output = Output { json: args.json };
+    let user = login(args, system_config, storage).await?;
+    match command {
+        ZettelCommand::Show { path } => {
+            let zettel = find(storage, &user, path).await?;
+            if output.json {
+                output.print_json(&Zettel::from(zettel))
+            } else {
+                output.print(&zettel.body)
+            }
+        }
+        ZettelCommand::New { path } => {
+            if find(storage, &user, path).await.is_ok() {
+                return Err(Error::PathInUse { path: path.clone() });
+            }
+            let mut zettel = storage::Zettel {
+                path: path.clone(),
+                body: read_stdin()?,
+                ..Default::default()
+            };
+            save(storage, &user, &mut zettel).await?;
+            output.print_zettel(zettel)
+        }
+        ZettelCommand::Edit { path } => {
+            let mut zettel = find(storage, &user, path).await?;
+            let (body, file) = if std::io::stdin().is_terminal() {
+                let user_config = storage
+                    .get_user_config(user.id)
+                    .await
+                    .context(DatabaseSnafu)?;
+                let editor = user_config
+                    .editor
+                    .or_else(|| system_config.terminal_editor.clone())
+                    .or_else(|| std::env::var_os("EDITOR").map(Into::into))
+                    .ok_or(Error::NoEditor)?;
+                let (body, file) = open_editor(&editor, &zettel.body)?;
+                (body, Some(file))
+            } else {
+                (read_stdin()?, None)
+            };
+            let saved = edit::save_edit(storage, user.id, &mut zettel, &body, false)
+                .await
+                .context(DatabaseSnafu)?;
+            if saved != edit::Saved::Saved {
+                // the edit is lost otherwise
+                if let Some(file) = file {
+                    let (_, kept) = file.keep().context(KeepSnafu)?;
+                    eprintln!("Your version is in {}", kept.display());
+                }
+            }
+            match saved {
+                edit::Saved::Saved => output.print_zettel(zettel),
+                edit::Saved::Changed => Err(Error::EditConflict { path: path.clone() }),
+                edit::Saved::Deleted => Err(Error::DeletedWhileEditing { path: path.clone() }),
+            }
+        }
+        ZettelCommand::Search {
+            query,
+            mode,
+            properties,
+        } => {
+            let opts = SearchOpts {
+                query,
+                mode: *mode,
+                list_all: false,
+                properties,
+            };
+            let headers = storage
+                .get_zettels(user.id, opts)
+                .await
+                .context(DatabaseSnafu)?;
+            output.print_headers(&headers)
+        }
+        ZettelCommand::Ls { namespace } => {
+            let mut headers = all_zettels(storage, &user).await?;
+            if let Some(namespace) = namespace {
+                let namespace =
+                    namespace.trim_end_matches(zettelkasten_shared::namespace::SEPARATOR);
+                headers.retain(|header| in_namespace(&header.path, namespace));
+            }
+            output.print_headers(&headers)
+        }
+        ZettelCommand::Export => {
+            let zettels: Vec<Zettel> = storage
+                .get_all_zettels(user.id)
+                .await
+                .context(DatabaseSnafu)?
+                .into_iter()
+                .map(|zettel| Zettel {
+                    id: None,
+                    ..zettel.into()
+                })
+                .collect();
+            output.print_json(&zettels)
+        }
+        ZettelCommand::Import => {
+            let zettels: Vec<Zettel> = serde_json::from_str(&read_stdin()?).context(JsonSnafu)?;
+            let count = import(storage, &user, zettels).await?;
+            if output.json {
+                output.print_json(&serde_json::json!({ "imported": count }))
+            } else {
+                output.print(&format!("Imported {count} zettels"))
+            }
+        }
+    }
+}
+
+/// Run `command` and print its output. Users are managed without logging in.
+pub async fn run_user(
+    args: &Args,
+    command: &UserCommand,
+    system_config: &storage::SystemConfig,
+    storage: &dyn Storage,
+) -> Result {
+    let output = Output { json: args.json };
+    match command {
+        UserCommand::Add { name } => add_user(output, system_config, storage, name).await,
+    }
+}
+
+async fn login(
+    args: &Args,
+    system_config: &storage::SystemConfig,
+    storage: &dyn Storage,
+) -> Result<storage::User> {
+    match (&args.user, system_config.user_mode) {
+        (Some(username), _) => {
+            let password = if args.password_stdin {
+                read_password_line()?
+            } else {
+                rpassword::prompt_password(format!("Password for {username}: ")).context(IoSnafu)?
+            };
+            storage
+                .login(username, &password)
+                .await
+                .context(DatabaseSnafu)?
+                .ok_or(Error::WrongPassword)
+        }
+        (None, storage::UserMode::SingleUserAutoLogin) => {
+            storage.login_single_user().await.context(DatabaseSnafu)
+        }
+        (None, _) => Err(Error::Login),
+    }
+}
+
+async fn add_user(
+    output: Output,
+    system_config: &storage::SystemConfig,
+    storage: &dyn Storage,
+    name: &str,
+) -> Result {
+    // the same rule as registering in the web front
+    if !matches!(system_config.user_mode, storage::UserMode::MultiUser)
+        && storage.user_count().await.context(DatabaseSnafu)? > 0
+    {
+        return Err(Error::RegistrationClosed);
+    }
+    let password = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(format!("Password for {name}: ")).context(IoSnafu)?
+    } else {
+        read_password_line()?
+    };
+    if password.is_empty() {
+        return Err(Error::EmptyPassword);
+    }
+    let user = match storage.register(name.trim(), &password).await {
+        Ok(user) => user,
+        Err(storage::Error::UserAlreadyExists) => {
+            return Err(Error::UserExists {
+                name: name.to_owned(),
+            })
+        }
+        Err(source) => return Err(Error::Database { source }),
+    };
+    if output.json {
+        output.print_json(&User {
+            id: user.id,
+            name: &user.name,
+            is_admin: user.is_admin,
+        })
+    } else {
+        output.print(&format!("Added {}", user.name))
+    }
+}
+
+async fn find(storage: &dyn Storage, user: &storage::User, path: &str) -> Result<storage::Zettel> {
+    storage
+        .get_zettel_by_url(user.id, path)
+        .await
+        .context(DatabaseSnafu)?
+        .ok_or_else(|| Error::NotFound {
+            path: path.to_owned(),
+        })
+}
+
+async fn save(storage: &dyn Storage, user: &storage::User, zettel: &mut storage::Zettel) -> Result {
+    match storage.update_zettel(user.id, zettel).await {
+        Err(storage::Error::PathInUse { path }) => Err(Error::PathInUse { path }),
+        result => result.context(DatabaseSnafu),
+    }
+}
+
+async fn all_zettels(
+    storage: &dyn Storage,
+    user: &storage::User,
+) -> Result<Vec<storage::ZettelHeader>> {
+    let opts = SearchOpts {
+        list_all: true,
+        ..Default::default()
+    };
+    storage
+        .get_zettels(user.id, opts)
+        .await
+        .context(DatabaseSnafu)
+}
+
+/// Whether `path` is `namespace` or below it. Paths are case insensitive.
+fn in_namespace(path: &str, namespace: &str) -> bool {
+    let path = path.to_lowercase();
+    let namespace = namespace.to_lowercase();
+    namespace.is_empty()
+        || path == namespace
+        || path
+            .strip_prefix(&namespace)
+            .is_some_and(|rest| rest.starts_with(zettelkasten_shared::namespace::SEPARATOR))
+}
+
+/// Save `zettels` in a single batch, updating the zettels that already exist at their paths. Returns how many zettels
+/// were saved.
+async fn import(
+    storage: &dyn Storage,
+    user: &storage::User,
+    zettels: Vec<Zettel>,
+) -> Result<usize> {
+    let existing: std::collections::HashMap<String, storage::ZettelId> = all_zettels(storage, user)
+        .await?
+        .into_iter()
+        .map(|header| (header.path.to_lowercase(), header.id))
+        .collect();
+    let mut operations: Vec<BatchOperation> = zettels
+        .into_iter()
+        .map(|zettel| {
+            BatchOperation::UpdateZettel(storage::Zettel {
+                id: existing
+                    .get(&zettel.path.to_lowercase())
+                    .copied()
+                    .unwrap_or_default(),
+                path: zettel.path,
+                body: zettel.body,
+                folgezettel: zettel.folgezettel,
+                aliases: zettel.aliases,
+                attachments: Vec::new(),
+            })
+        })
+        .collect();
+    let results = storage
+        .batch(user.id, &mut operations)
+        .await
+        .context(DatabaseSnafu)?;
+    for (result, operation) in results.into_iter().zip(&operations) {
+        if let (BatchResult::Failed(source), BatchOperation::UpdateZettel(zettel)) =
+            (result, operation)
+        {
+            return Err(Error::Import {
+                path: zettel.path.clone(),
+                source,
+            });
+        }
+    }
+    Ok(operations.len())
+}
+
+/// The first line of stdin, leaving the rest of it to [`read_stdin`]
+fn read_password_line() -> Result<String> {
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password).context(IoSnafu)?;
+    password.truncate(password.trim_end_matches(['\r', '\n']).len());
+    Ok(password)
+}
+
+fn read_stdin() -> Result<String> {
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .context(IoSnafu)?;
+    Ok(text)
+}
+
+/// Let the user edit `body` in `editor`, like the terminal front does
+/// The body after editing it in `editor`, and the file it was edited in
+fn open_editor(editor: &std::path::Path, body: &str) -> Result<(String, tempfile::NamedTempFile)> {
+    let mut file = tempfile::Builder::new()
+        .suffix(".md")
+        .tempfile()
+        .context(IoSnafu)?;
+    file.write_all(body.as_bytes()).context(IoSnafu)?;
+    let status = std::process::Command::new(editor)
+        .arg(file.path())
+        .status()
+        .context(IoSnafu)?;
+    if !status.success() {
+        return Err(Error::Editor { status });
+    }
+    let body = std::fs::read_to_string(file.path()).context(IoSnafu)?;
+    Ok((body, file))
+}
+
+#[derive(Clone, Copy)]
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    fn print(self, text: &str) -> Result {
+        let mut stdout = std::io::stdout().lock();
+        let result = if text.is_empty() || text.ends_with('\n') {
+            stdout.write_all(text.as_bytes())
+        } else {
+            writeln!(stdout, "{text}")
+        };
+        match result.and_then(|()| stdout.flush()) {
+            // the reader, e.g. `head`, is done
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+            result => result.context(IoSnafu),
+        }
+    }
+
+    fn print_json(self, value: &impl serde::Serialize) -> Result {
+        self.print(&serde_json::to_string_pretty(value).context(JsonSnafu)?)
+    }
+
+    /// Print a zettel that was saved. Plain output is the path, as the body came from the user.
+    fn print_zettel(self, zettel: storage::Zettel) -> Result {
+        if self.json {
+            self.print_json(&Zettel::from(zettel))
+        } else {
+            self.print(&zettel.path)
+        }
+    }
+
+    fn print_headers(self, headers: &[storage::ZettelHeader]) -> Result {
+        if self.json {
+            self.print_json(&headers.iter().map(ZettelHeader::from).collect::<Vec<_>>())
+        } else {
+            let paths: Vec<&str> = headers.iter().map(|header| header.path.as_str()).collect();
+            self.print(&paths.join("\n"))
+        }
+    }
+}
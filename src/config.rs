@@ -0,0 +1,263 @@
+//! The settings of the binary, read from a TOML config file at start-up and overridden by the environment and the
+//! command line.
+//!
+//! The config file is `<CONFIG_DIR>/zettelkasten/config.toml`, where `<CONFIG_DIR>` is `$XDG_CONFIG_HOME` or
+//! `~/.config` on Linux. It holds the database url, the fronts to start and a section per front, which is deserialized
+//! into the [`Front::Config`] of that front:
+//!
+//! ```toml
+//! database_url = "postgres://localhost/zettelkasten"
+//! fronts = ["terminal", "web"]
+//!
+//! [web]
+//! port = 8081
+//!
+//! [lsp]
+//! hover_lines = 10
+//! ```
+//!
+//! Sections of fronts that are not compiled in are ignored.
+
+use crate::{cli, database};
+use snafu::{OptionExt, ResultExt, Snafu};
+use std::path::{Path, PathBuf};
+#[cfg(any(
+    feature = "front-terminal",
+    feature = "front-web",
+    feature = "front-lsp"
+))]
+use zettelkasten_shared::Front;
+
+/// The config file as it is written
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    database_url: Option<String>,
+    fronts: Option<Vec<cli::Front>>,
+    terminal: toml::Table,
+    web: toml::Table,
+    lsp: toml::Table,
+}
+
+/// The effective settings, see the [module docs](self)
+#[derive(Debug, serde::Serialize)]
+pub struct Settings {
+    /// The config file that was read, or `None` if there is none
+    #[serde(skip)]
+    pub config_file: Option<PathBuf>,
+    #[serde(serialize_with = "serialize_url")]
+    pub database_url: String,
+    pub fronts: Vec<cli::Front>,
+    /// Left out of the report, as the terminal front has nothing to configure
+    #[cfg(feature = "front-terminal")]
+    #[serde(skip)]
+    pub terminal: <zettelkasten_terminal::Tui<'static> as Front>::Config,
+    #[cfg(feature = "front-web")]
+    pub web: <zettelkasten_web::Web as Front>::Config,
+    #[cfg(feature = "front-lsp")]
+    pub lsp: <zettelkasten_lsp::Lsp as Front>::Config,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Could not read {}: {source}", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not parse {}: {source}", path.display()))]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[snafu(display("Invalid config: {source}"))]
+    Invalid { source: toml::de::Error },
+    #[snafu(display("Invalid `[{front}]` section: {source}"))]
+    #[cfg(any(
+        feature = "front-terminal",
+        feature = "front-web",
+        feature = "front-lsp"
+    ))]
+    Section {
+        front: &'static str,
+        source: toml::de::Error,
+    },
+    #[snafu(display("Expected `<key>=<value>`, got {setting:?}"))]
+    InvalidSetting { setting: String },
+    #[snafu(display("This binary was built without `{feature}`"))]
+    FrontNotEnabled { feature: &'static str },
+    #[snafu(display("The lsp front talks over stdio, so it can't run with other fronts"))]
+    LspNotAlone,
+    #[snafu(display("This binary was built without any front, pass a command"))]
+    NoFront,
+    #[snafu(display("{source}"))]
+    Database { source: database::Error },
+}
+
+impl Settings {
+    /// Read the config file and apply the overrides of the environment and `args`
+    ///
+    /// # Errors
+    ///
+    /// When the config file can't be read or has invalid settings, or the fronts can't be started
+    pub fn load(args: &cli::Args) -> Result<Self, Error> {
+        let (config_file, mut table) = match &args.config {
+            Some(path) => (Some(path.clone()), read(path)?),
+            None => match default_path().filter(|path| path.exists()) {
+                Some(path) => {
+                    let table = read(&path)?;
+                    (Some(path), table)
+                }
+                None => (None, toml::Table::new()),
+            },
+        };
+        for setting in &args.settings {
+            set(&mut table, setting)?;
+        }
+        let file: File = toml::Value::Table(table).try_into().context(InvalidSnafu)?;
+
+        let database_url = match args
+            .database_url
+            .clone()
+            .or_else(database::url_from_env)
+            .or(file.database_url)
+        {
+            Some(url) => url,
+            None => database::default_url().context(DatabaseSnafu)?,
+        };
+        let fronts = fronts(args, file.fronts)?;
+
+        Ok(Self {
+            config_file,
+            database_url,
+            fronts,
+            #[cfg(feature = "front-terminal")]
+            terminal: front_config::<zettelkasten_terminal::Tui<'static>>(
+                "terminal",
+                file.terminal,
+            )?,
+            #[cfg(feature = "front-web")]
+            web: front_config::<zettelkasten_web::Web>("web", file.web)?,
+            #[cfg(feature = "front-lsp")]
+            lsp: front_config::<zettelkasten_lsp::Lsp>("lsp", file.lsp)?,
+        })
+    }
+
+    /// The settings as a config file, with the config file that was read as a comment
+    pub fn to_toml(&self) -> String {
+        let file = match &self.config_file {
+            Some(path) => format!("# config file: {}", path.display()),
+            None => match default_path() {
+                Some(path) => format!("# no config file at {}", path.display()),
+                None => "# no config file".to_owned(),
+            },
+        };
+        let settings = toml::to_string(self).expect("The settings can be written as TOML");
+        format!("{file}\n{settings}")
+    }
+}
+
+/// `<CONFIG_DIR>/zettelkasten/config.toml`
+fn default_path() -> Option<PathBuf> {
+    let mut path = dirs::config_dir()?;
+    path.push("zettelkasten");
+    path.push("config.toml");
+    Some(path)
+}
+
+fn read(path: &Path) -> Result<toml::Table, Error> {
+    let text = std::fs::read_to_string(path).context(ReadSnafu { path })?;
+    toml::from_str(&text).context(ParseSnafu { path })
+}
+
+/// Apply a `--set <key>=<value>` override. Keys are dotted paths into the config file. Values are TOML, or a string
+/// if they are not valid TOML, so `database_url=sqlite://zettelkasten.db` needs no quotes.
+fn set(table: &mut toml::Table, setting: &str) -> Result<(), Error> {
+    let invalid = || InvalidSettingSnafu { setting };
+    let (key, value) = setting.split_once('=').with_context(invalid)?;
+    let value = value.trim();
+    let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_owned()));
+
+    let mut keys: Vec<&str> = key.trim().split('.').map(str::trim).collect();
+    let last = keys
+        .pop()
+        .filter(|last| !last.is_empty())
+        .with_context(invalid)?;
+    let mut table = table;
+    for key in keys {
+        let entry = table
+            .entry(key)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        table = entry.as_table_mut().with_context(invalid)?;
+    }
+    table.insert(last.to_owned(), value);
+    Ok(())
+}
+
+/// The fronts from the command line or the environment, else from the config file, else every front that is compiled
+/// in except `lsp`. They are only checked if they are started.
+fn fronts(args: &cli::Args, file: Option<Vec<cli::Front>>) -> Result<Vec<cli::Front>, Error> {
+    let mut fronts = match (args.fronts.clone(), file) {
+        (fronts, _) if !fronts.is_empty() => fronts,
+        (_, Some(fronts)) => fronts,
+        (_, None) => cli::Front::ALL
+            .into_iter()
+            .filter(|&front| front.is_enabled() && front != cli::Front::Lsp)
+            .collect(),
+    };
+    let started = match &args.command {
+        None | Some(cli::Command::Config { .. }) => true,
+        #[cfg(feature = "front-lsp")]
+        Some(cli::Command::Lsp) => {
+            fronts = vec![cli::Front::Lsp];
+            true
+        }
+        Some(_) => false,
+    };
+    if !started {
+        return Ok(fronts);
+    }
+    // a front that is given twice is started once, wherever the repetition is
+    let mut seen = Vec::with_capacity(fronts.len());
+    fronts.retain(|&front| {
+        let first = !seen.contains(&front);
+        seen.push(front);
+        first
+    });
+    if let Some(front) = fronts.iter().find(|front| !front.is_enabled()) {
+        return FrontNotEnabledSnafu {
+            feature: front.feature(),
+        }
+        .fail();
+    }
+    if fronts.contains(&cli::Front::Lsp) && fronts.len() > 1 {
+        return LspNotAloneSnafu.fail();
+    }
+    if fronts.is_empty() && args.command.is_none() {
+        return NoFrontSnafu.fail();
+    }
+    Ok(fronts)
+}
+
+/// The config of `F` from its section. Fronts without settings have `()` as their config, so an empty section is
+/// their default config.
+#[cfg(any(
+    feature = "front-terminal",
+    feature = "front-web",
+    feature = "front-lsp"
+))]
+fn front_config<F: Front>(front: &'static str, section: toml::Table) -> Result<F::Config, Error> {
+    if section.is_empty() {
+        return Ok(F::Config::default());
+    }
+    toml::Value::Table(section)
+        .try_into()
+        .context(SectionSnafu { front })
+}
+
+fn serialize_url<S: serde::Serializer>(url: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&database::redact(url))
+}
@@ -0,0 +1,154 @@
+//! Connecting to one of the storage backends that are compiled in, chosen by the scheme of the database URL.
+//!
+//! `postgres://` and `postgresql://` URLs go to `data-postgres`. Everything else, `sqlite://` URLs as well as plain
+//! paths, goes to `data-sqlite`.
+
+use snafu::{ResultExt, Snafu};
+use std::{path::PathBuf, sync::Arc};
+use zettelkasten_shared::{
+    cache::{self, CachedStorage},
+    storage::{self, ConnectableStorage},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    /// The backend that `url` is for
+    ///
+    /// # Errors
+    ///
+    /// When the scheme of `url` is unknown
+    pub fn from_url(url: &str) -> Result<Self, Error> {
+        match scheme(url) {
+            None => Ok(Self::Sqlite),
+            Some(scheme) if scheme.eq_ignore_ascii_case("sqlite") => Ok(Self::Sqlite),
+            Some(scheme)
+                if scheme.eq_ignore_ascii_case("postgres")
+                    || scheme.eq_ignore_ascii_case("postgresql") =>
+            {
+                Ok(Self::Postgres)
+            }
+            Some(scheme) => UnknownSchemeSnafu { scheme }.fail(),
+        }
+    }
+
+    /// The cargo feature that compiles this backend in
+    pub fn feature(self) -> &'static str {
+        match self {
+            Self::Sqlite => "data-sqlite",
+            Self::Postgres => "data-postgres",
+        }
+    }
+
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Self::Sqlite => cfg!(feature = "data-sqlite"),
+            Self::Postgres => cfg!(feature = "data-postgres"),
+        }
+    }
+}
+
+/// The scheme of `url`, or `None` if it is a path. Single letters are Windows drives, not schemes.
+fn scheme(url: &str) -> Option<&str> {
+    let (scheme, _) = url.split_once(':')?;
+    (scheme.len() > 1
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')))
+    .then_some(scheme)
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unknown database scheme `{scheme}:`, expected `sqlite:` or `postgres:`"))]
+    UnknownScheme { scheme: String },
+    #[snafu(display("This binary was built without `{feature}`, which the database url needs"))]
+    NotEnabled { feature: &'static str },
+    #[snafu(display(
+        "The database url is not set. Please set `database_url` in the config file, `DATABASE_URL` or \
+         `ZETTELKASTEN_DATABASE_URL`"
+    ))]
+    MissingUrl,
+    #[snafu(display("Could not create {}: {source}", path.display()))]
+    CreateDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Could not open the database: {source:?}"))]
+    Connect { source: storage::Error },
+}
+
+/// The database url from the environment, which includes `.env`
+pub fn url_from_env() -> Option<String> {
+    std::env::var("DATABASE_URL")
+        .or_else(|_| std::env::var("ZETTELKASTEN_DATABASE_URL"))
+        .ok()
+}
+
+/// `url` with its password replaced by `***`, to show it to the user
+pub fn redact(url: &str) -> std::borrow::Cow<'_, str> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.into();
+    };
+    let authority = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    let Some(at) = authority.rfind('@') else {
+        return url.into();
+    };
+    let Some(colon) = authority[..at].find(':') else {
+        return url.into();
+    };
+    format!("{scheme}://{}:***{}", &rest[..colon], &rest[at..]).into()
+}
+
+/// The SQLite database in the data directory of the user, which is used when no url is set
+///
+/// # Errors
+///
+/// When the directory can't be created
+pub fn default_url() -> Result<String, Error> {
+    if !Backend::Sqlite.is_enabled() {
+        return MissingUrlSnafu.fail();
+    }
+    let mut path = dirs::data_dir().unwrap_or_default();
+    path.push("zettelkasten");
+    std::fs::create_dir_all(&path).context(CreateDirSnafu { path: &path })?;
+    path.push("database.db");
+    Ok(path.display().to_string())
+}
+
+/// Connect to the database at `url` with the backend that it is for, behind a cache
+///
+/// # Errors
+///
+/// When `url` is for a backend that is not compiled in, or the database can't be opened
+pub async fn connect(
+    url: String,
+) -> Result<(Arc<dyn storage::Storage>, storage::SystemConfig), Error> {
+    let backend = Backend::from_url(&url)?;
+    match backend {
+        #[cfg(feature = "data-sqlite")]
+        Backend::Sqlite => cached(zettelkasten_sqlite::Connection::connect(url).await).await,
+        #[cfg(feature = "data-postgres")]
+        Backend::Postgres => cached(zettelkasten_postgres::Connection::connect(url).await).await,
+        #[allow(unreachable_patterns)]
+        _ => NotEnabledSnafu {
+            feature: backend.feature(),
+        }
+        .fail(),
+    }
+}
+
+#[cfg(any(feature = "data-sqlite", feature = "data-postgres"))]
+async fn cached<S: storage::Storage + 'static>(
+    connection: Result<(S, storage::SystemConfig), storage::Error>,
+) -> Result<(Arc<dyn storage::Storage>, storage::SystemConfig), Error> {
+    let (connection, config) = connection.context(ConnectSnafu)?;
+    let connection = CachedStorage::new(connection, cache::DEFAULT_CAPACITY)
+        .await
+        .context(ConnectSnafu)?;
+    Ok((Arc::new(connection), config))
+}
@@ -1,51 +1,119 @@
+mod cli;
+mod config;
+mod database;
+
+use clap::Parser;
 use std::sync::Arc;
-use zettelkasten_shared::{
-    storage::{self, ConnectableStorage},
-    Front,
-};
+use zettelkasten_shared::storage;
+#[cfg(any(
+    feature = "front-lsp",
+    feature = "front-terminal",
+    feature = "front-web"
+))]
+use zettelkasten_shared::Front;
 
-#[allow(clippy::redundant_clone)]
 fn main() {
+    // before parsing the arguments, as some of them can be set in the environment
+    let _ = dotenv::dotenv();
+    let args = cli::Args::parse();
+    let settings = config::Settings::load(&args).unwrap_or_else(|e| exit(&e));
     zettelkasten_shared::block_on(async {
-        let (connection, system_config) = data_policy_should_exist_exactly_once().await;
-        #[cfg(feature = "front-terminal")]
-        zettelkasten_terminal::Tui::run((), system_config.clone(), Arc::clone(&connection));
+        let connect = || async {
+            database::connect(settings.database_url.clone())
+                .await
+                .unwrap_or_else(|e| exit(&e))
+        };
+        match &args.command {
+            Some(cli::Command::Config {
+                command: cli::ConfigCommand::Check,
+            }) => check_config(&settings, args.json).await,
+            Some(cli::Command::Zettel(command)) => {
+                let (connection, system_config) = connect().await;
+                if let Err(e) = cli::run(&args, command, &system_config, connection.as_ref()).await
+                {
+                    exit(&e);
+                }
+            }
+            Some(cli::Command::User { command }) => {
+                let (connection, system_config) = connect().await;
+                let result =
+                    cli::run_user(&args, command, &system_config, connection.as_ref()).await;
+                if let Err(e) = result {
+                    exit(&e);
+                }
+            }
+            #[cfg(feature = "front-lsp")]
+            Some(cli::Command::Lsp) => {
+                let (connection, system_config) = connect().await;
+                run_fronts(&settings, system_config, connection);
+            }
+            None => {
+                let (connection, system_config) = connect().await;
+                run_fronts(&settings, system_config, connection);
+            }
+        }
     });
 }
 
-#[cfg(feature = "data-sqlite")]
-async fn data_policy_should_exist_exactly_once(
-) -> (Arc<dyn storage::Storage>, storage::SystemConfig) {
-    let _ = dotenv::dotenv();
+fn exit(error: &dyn std::error::Error) -> ! {
+    eprintln!("error: {error}");
+    std::process::exit(1);
+}
 
-    let connection_string = std::env::var("DATABASE_URL")
-        .or_else(|_| std::env::var("ZETTELKASTEN_DATABASE_URL"))
-        .unwrap_or_else(|_| {
-            let mut path = dirs::data_dir().unwrap_or_default();
-            path.push("zettelkasten");
-            std::fs::create_dir_all(&path)
-                .unwrap_or_else(|e| panic!("Could not create {path:?}: {e:?}"));
-            path.push("database.db");
-            path.display().to_string()
+/// Print the effective settings, then check that the database can be opened
+async fn check_config(settings: &config::Settings, json: bool) {
+    let report = if json {
+        let report = serde_json::json!({
+            "config_file": settings.config_file,
+            "settings": settings,
         });
-
-    let (connection, config) = zettelkasten_sqlite::Connection::connect(connection_string)
-        .await
-        .expect("Could not open database");
-    (Arc::new(connection), config)
+        serde_json::to_string_pretty(&report).expect("The settings can be written as JSON")
+    } else {
+        settings.to_toml()
+    };
+    println!("{}", report.trim_end());
+    if let Err(e) = database::connect(settings.database_url.clone()).await {
+        exit(&e);
+    }
+    if !json {
+        println!("# the database can be opened");
+    }
 }
 
-#[cfg(feature = "data-postgres")]
-async fn data_policy_should_exist_exactly_once(
-) -> (Arc<dyn storage::Storage>, storage::SystemConfig) {
-    let _ = dotenv::dotenv();
-
-    let connection_string = std::env::var("DATABASE_URL")
-        .or_else(|_| std::env::var("ZETTELKASTEN_DATABASE_URL"))
-        .expect("Postgres database url not set. Please set either `DATABASE_URL` or `ZETTELKASTEN_DATABASE_URL`");
-
-    let (connection, config) = zettelkasten_postgres::Connection::connect(connection_string)
-        .await
-        .expect("Could not open database");
-    (Arc::new(connection), config)
+/// Run `fronts` until the terminal front quits, or until the other fronts stop if there is no terminal
+fn run_fronts(
+    settings: &config::Settings,
+    system_config: storage::SystemConfig,
+    connection: Arc<dyn storage::Storage>,
+) {
+    // editors start the language server as `zettelkasten lsp` and talk to it over stdio, so nothing else may run
+    #[cfg(feature = "front-lsp")]
+    if settings.fronts.contains(&cli::Front::Lsp) {
+        zettelkasten_lsp::Lsp::run(settings.lsp.clone(), system_config, connection);
+        return;
+    }
+    // the web front runs next to the terminal, and keeps running on its own if there is no terminal
+    #[cfg(feature = "front-web")]
+    let web = settings.fronts.contains(&cli::Front::Web).then(|| {
+        let (config, system_config, connection) = (
+            settings.web.clone(),
+            system_config.clone(),
+            Arc::clone(&connection),
+        );
+        std::thread::spawn(move || zettelkasten_web::Web::run(config, system_config, connection))
+    });
+    #[cfg(feature = "front-terminal")]
+    if settings.fronts.contains(&cli::Front::Terminal) {
+        // the terminal has nothing to configure, but gets its config like the other fronts
+        #[allow(clippy::unit_arg)]
+        zettelkasten_terminal::Tui::run(settings.terminal, system_config, connection);
+        return;
+    }
+    #[cfg(feature = "front-web")]
+    if let Some(web) = web {
+        web.join().expect("The web front panicked");
+        return;
+    }
+    // the fronts that were compiled in did not take them
+    let _ = (settings, system_config, connection);
 }